@@ -11,6 +11,7 @@ const SERVICE = process.env.SERVICE!;
 const STAGE = process.env.STAGE!;
 const AUTHORIZER_FUNCTION_ARN = process.env.AUTHORIZER_FUNCTION_ARN!;
 const CERTIFICATE_ARN = process.env.CERTIFICATE_ARN!;
+const SINGLE_API_LAMBDA = process.env.SINGLE_API_LAMBDA === 'true';
 
 const appStackName = `${SERVICE}-${STAGE}-app`;
 
@@ -22,6 +23,7 @@ new AppStack(app, appStackName, {
 	stage: STAGE,
 	authorizerFunctionArn: AUTHORIZER_FUNCTION_ARN,
 	certificateArn: CERTIFICATE_ARN,
+	singleApiLambda: SINGLE_API_LAMBDA,
 	subscriptionEmail: 'aws_alarm@classifind.app',
 	env: {
 		account: CDK_DEFAULT_ACCOUNT,
@@ -75,4 +75,84 @@ cd ..
 echo "'list-users' lambda build complete"
 #####################
 
+#### RESTORE USER ####
+echo "building 'restore-user' lambda"
+echo " "
+cd ./cf-user_restore-user
+rustup target add aarch64-unknown-linux-gnu
+cargo lambda build --release --output-format zip --arm64
+cd ..
+echo "'restore-user' lambda build complete"
+#####################
+
+#### GET USER HISTORY ####
+echo "building 'get-user-history' lambda"
+echo " "
+cd ./cf-user_get-user-history
+rustup target add aarch64-unknown-linux-gnu
+cargo lambda build --release --output-format zip --arm64
+cd ..
+echo "'get-user-history' lambda build complete"
+#####################
+
+#### BATCH GET USERS ####
+echo "building 'batch-get-users' lambda"
+echo " "
+cd ./cf-user_batch-get-users
+rustup target add aarch64-unknown-linux-gnu
+cargo lambda build --release --output-format zip --arm64
+cd ..
+echo "'batch-get-users' lambda build complete"
+#####################
+
+#### IMPORT USERS ####
+echo "building 'import-users' lambda"
+echo " "
+cd ./cf-user_import-users
+rustup target add aarch64-unknown-linux-gnu
+cargo lambda build --release --output-format zip --arm64
+cd ..
+echo "'import-users' lambda build complete"
+#####################
+
+#### EXPORT USERS ####
+echo "building 'export-users' lambda"
+echo " "
+cd ./cf-user_export-users
+rustup target add aarch64-unknown-linux-gnu
+cargo lambda build --release --output-format zip --arm64
+cd ..
+echo "'export-users' lambda build complete"
+#####################
+
+#### STREAM PROCESSOR ####
+echo "building 'stream-processor' lambda"
+echo " "
+cd ./cf-user_stream-processor
+rustup target add aarch64-unknown-linux-gnu
+cargo lambda build --release --output-format zip --arm64
+cd ..
+echo "'stream-processor' lambda build complete"
+#####################
+
+#### OUTBOX RELAY ####
+echo "building 'outbox-relay' lambda"
+echo " "
+cd ./cf-user_outbox-relay
+rustup target add aarch64-unknown-linux-gnu
+cargo lambda build --release --output-format zip --arm64
+cd ..
+echo "'outbox-relay' lambda build complete"
+#####################
+
+#### API ####
+echo "building 'api' lambda"
+echo " "
+cd ./cf-user_api
+rustup target add aarch64-unknown-linux-gnu
+cargo lambda build --release --output-format zip --arm64
+cd ..
+echo "'api' lambda build complete"
+#####################
+
 printf "\nBuilding Task Complete!"
\ No newline at end of file
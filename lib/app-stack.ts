@@ -2,8 +2,10 @@ import * as cdk from 'aws-cdk-lib';
 import * as apigateway from 'aws-cdk-lib/aws-apigateway';
 import * as backup from 'aws-cdk-lib/aws-backup';
 import * as events from 'aws-cdk-lib/aws-events';
+import * as targets from 'aws-cdk-lib/aws-events-targets';
 import * as ddb from 'aws-cdk-lib/aws-dynamodb';
 import * as lambda from 'aws-cdk-lib/aws-lambda';
+import * as lambdaEventSources from 'aws-cdk-lib/aws-lambda-event-sources';
 import * as sns from 'aws-cdk-lib/aws-sns';
 import * as logs from 'aws-cdk-lib/aws-logs';
 import * as cloudwatch from 'aws-cdk-lib/aws-cloudwatch';
@@ -11,6 +13,7 @@ import * as actions from 'aws-cdk-lib/aws-cloudwatch-actions';
 import * as subscriptions from 'aws-cdk-lib/aws-sns-subscriptions';
 import * as codeDeploy from 'aws-cdk-lib/aws-codedeploy';
 import * as iam from 'aws-cdk-lib/aws-iam';
+import * as secretsmanager from 'aws-cdk-lib/aws-secretsmanager';
 import { Construct } from 'constructs';
 
 export interface AppStackProps extends cdk.StackProps {
@@ -19,6 +22,8 @@ export interface AppStackProps extends cdk.StackProps {
 	subscriptionEmail: string;
 	authorizerFunctionArn: string;
 	certificateArn: string;
+	// Serve every route from the cf-user_api Lambda instead of one Lambda per route
+	singleApiLambda?: boolean;
 }
 
 export class AppStack extends cdk.Stack {
@@ -75,62 +80,219 @@ export class AppStack extends cdk.Stack {
 			);
 		}
 
-		// API Lambdas
-
-		const getUser = this.createLambda(
-			'GetUser',
-			'cf-user_get-user',
-			props,
-			snsTopic
+		// Signs list pagination tokens so clients cannot forge start keys
+		const paginationTokenSecret = new secretsmanager.Secret(
+			this,
+			'PaginationTokenSecret',
+			{
+				secretName: `${this.stackName}-pagination-token`,
+				generateSecretString: {
+					excludePunctuation: true,
+					passwordLength: 64,
+				},
+			}
 		);
-		usersTable.grantReadData(getUser);
 
-		const createUser = this.createLambda(
-			'CreateUser',
-			'cf-user_create-user',
-			props,
-			snsTopic
-		);
-		usersTable.grantReadData(createUser);
-		usersTable.grantWriteData(createUser);
+		// API Lambdas, either one serving every route or one per route
+		let integrations: Record<string, apigateway.LambdaIntegration>;
+		if (props.singleApiLambda) {
+			const apiLambda = this.createLambda(
+				'Api',
+				'cf-user_api',
+				props,
+				snsTopic,
+				{
+					USER_CACHE_TTL_SECONDS: '5',
+					PAGINATION_TOKEN_SECRET_ARN: paginationTokenSecret.secretArn,
+				}
+			);
+			usersTable.grantReadData(apiLambda);
+			usersTable.grantWriteData(apiLambda);
+			paginationTokenSecret.grantRead(apiLambda);
+
+			const integration = new apigateway.LambdaIntegration(apiLambda);
+			integrations = {
+				getUser: integration,
+				batchGetUsers: integration,
+				createUser: integration,
+				importUsers: integration,
+				updateUser: integration,
+				deleteUser: integration,
+				restoreUser: integration,
+				listUsers: integration,
+				getUserHistory: integration,
+			};
+		} else {
+			// Caches hot profiles briefly, since updates go through other Lambdas
+			const getUser = this.createLambda(
+				'GetUser',
+				'cf-user_get-user',
+				props,
+				snsTopic,
+				{ USER_CACHE_TTL_SECONDS: '5' }
+			);
+			usersTable.grantReadData(getUser);
+
+			const batchGetUsers = this.createLambda(
+				'BatchGetUsers',
+				'cf-user_batch-get-users',
+				props,
+				snsTopic
+			);
+			usersTable.grantReadData(batchGetUsers);
 
-		const updateUser = this.createLambda(
-			'UpdateUser',
-			'cf-user_update-user',
+			const createUser = this.createLambda(
+				'CreateUser',
+				'cf-user_create-user',
+				props,
+				snsTopic
+			);
+			usersTable.grantReadData(createUser);
+			usersTable.grantWriteData(createUser);
+
+			const importUsers = this.createLambda(
+				'ImportUsers',
+				'cf-user_import-users',
+				props,
+				snsTopic
+			);
+			usersTable.grantReadData(importUsers);
+			usersTable.grantWriteData(importUsers);
+
+			const updateUser = this.createLambda(
+				'UpdateUser',
+				'cf-user_update-user',
+				props,
+				snsTopic
+			);
+			usersTable.grantReadData(updateUser);
+			usersTable.grantWriteData(updateUser);
+
+			const deleteUser = this.createLambda(
+				'DeleteUser',
+				'cf-user_delete-user',
+				props,
+				snsTopic
+			);
+			usersTable.grantReadData(deleteUser);
+			usersTable.grantWriteData(deleteUser);
+
+			const restoreUser = this.createLambda(
+				'RestoreUser',
+				'cf-user_restore-user',
+				props,
+				snsTopic
+			);
+			usersTable.grantReadData(restoreUser);
+			usersTable.grantWriteData(restoreUser);
+
+			const listUsers = this.createLambda(
+				'ListUsers',
+				'cf-user_list-users',
+				props,
+				snsTopic,
+				{
+					PAGINATION_TOKEN_SECRET_ARN: paginationTokenSecret.secretArn,
+				}
+			);
+			usersTable.grantReadData(listUsers);
+			paginationTokenSecret.grantRead(listUsers);
+
+			const getUserHistory = this.createLambda(
+				'GetUserHistory',
+				'cf-user_get-user-history',
+				props,
+				snsTopic,
+				{
+					PAGINATION_TOKEN_SECRET_ARN: paginationTokenSecret.secretArn,
+				}
+			);
+			usersTable.grantReadData(getUserHistory);
+			paginationTokenSecret.grantRead(getUserHistory);
+
+			integrations = {
+				getUser: new apigateway.LambdaIntegration(getUser),
+				batchGetUsers: new apigateway.LambdaIntegration(batchGetUsers),
+				createUser: new apigateway.LambdaIntegration(createUser),
+				importUsers: new apigateway.LambdaIntegration(importUsers),
+				updateUser: new apigateway.LambdaIntegration(updateUser),
+				deleteUser: new apigateway.LambdaIntegration(deleteUser),
+				restoreUser: new apigateway.LambdaIntegration(restoreUser),
+				listUsers: new apigateway.LambdaIntegration(listUsers),
+				getUserHistory: new apigateway.LambdaIntegration(getUserHistory),
+			};
+		}
+
+		// Invoked asynchronously rather than through the API, so it can outlive the 30s gateway limit
+		const exportUsers = this.createLambda(
+			'ExportUsers',
+			'cf-user_export-users',
 			props,
-			snsTopic
+			snsTopic,
+			{
+				EXPORT_DIRECTORY: '/tmp',
+			},
+			cdk.Duration.minutes(15)
 		);
-		usersTable.grantReadData(updateUser);
-		usersTable.grantWriteData(updateUser);
+		usersTable.grantReadData(exportUsers);
 
-		const deleteUser = this.createLambda(
-			'DeleteUser',
-			'cf-user_delete-user',
+		// Relays the event outbox and publishes TTL purges from the table stream
+		const streamProcessor = this.createLambda(
+			'StreamProcessor',
+			'cf-user_stream-processor',
 			props,
 			snsTopic
 		);
-		usersTable.grantReadData(deleteUser);
-		usersTable.grantWriteData(deleteUser);
+		streamProcessor.addEventSource(
+			new lambdaEventSources.DynamoEventSource(usersTable, {
+				startingPosition: lambda.StartingPosition.TRIM_HORIZON,
+				batchSize: 100,
+				retryAttempts: 10,
+				reportBatchItemFailures: true,
+			})
+		);
+		usersTable.grantReadData(streamProcessor);
+		usersTable.grantWriteData(streamProcessor);
 
-		const listUsers = this.createLambda(
-			'ListUsers',
-			'cf-user_list-users',
+		// Retries outbox entries a relay left behind, since the stream processor only relays on new changes
+		const outboxRelay = this.createLambda(
+			'OutboxRelay',
+			'cf-user_outbox-relay',
 			props,
-			snsTopic
+			snsTopic,
+			{},
+			cdk.Duration.minutes(5)
 		);
-		usersTable.grantReadData(listUsers);
+		new events.Rule(this, 'OutboxRelaySchedule', {
+			schedule: events.Schedule.rate(cdk.Duration.minutes(5)),
+			targets: [new targets.LambdaFunction(outboxRelay)],
+		});
+		usersTable.grantReadData(outboxRelay);
+		usersTable.grantWriteData(outboxRelay);
 
 		// Routes
 		const v1 = api.root.addResource('v1');
 		const usersV1 = v1.addResource('users');
 
-		usersV1.addMethod("GET", new apigateway.LambdaIntegration(listUsers));
-		usersV1.addMethod('POST', new apigateway.LambdaIntegration(createUser));
+		usersV1.addMethod("GET", integrations.listUsers);
+		usersV1.addMethod('POST', integrations.createUser);
+
+		const usersBatchGetV1 = v1.addResource('users:batchGet');
+		usersBatchGetV1.addMethod('POST', integrations.batchGetUsers);
+
+		const usersImportV1 = v1.addResource('users:import');
+		usersImportV1.addMethod('POST', integrations.importUsers);
 
 		const usersIdV1 = usersV1.addResource('{userId}');
-		usersIdV1.addMethod('GET', new apigateway.LambdaIntegration(getUser));
-		usersIdV1.addMethod("PUT", new apigateway.LambdaIntegration(updateUser));
-		usersIdV1.addMethod("DELETE", new apigateway.LambdaIntegration(deleteUser));
+		usersIdV1.addMethod('GET', integrations.getUser);
+		usersIdV1.addMethod("PUT", integrations.updateUser);
+		usersIdV1.addMethod("DELETE", integrations.deleteUser);
+
+		const usersIdRestoreV1 = usersIdV1.addResource('restore');
+		usersIdRestoreV1.addMethod('POST', integrations.restoreUser);
+
+		const usersIdHistoryV1 = usersIdV1.addResource('history');
+		usersIdHistoryV1.addMethod('GET', integrations.getUserHistory);
 
 		if (this.isCiCdStage(props.stage)) {
 			new apigateway.CfnBasePathMapping(this, 'BasePathMapping', {
@@ -148,6 +310,9 @@ export class AppStack extends cdk.Stack {
 			billingMode: ddb.BillingMode.PAY_PER_REQUEST,
 			encryption: ddb.TableEncryption.AWS_MANAGED,
 			pointInTimeRecovery: true,
+			// Soft-deleted users and their guard items are purged once ExpiresAt passes
+			timeToLiveAttribute: 'ExpiresAt',
+			stream: ddb.StreamViewType.NEW_AND_OLD_IMAGES,
 			partitionKey: {
 				name: 'PK',
 				type: ddb.AttributeType.STRING,
@@ -171,6 +336,31 @@ export class AppStack extends cdk.Stack {
 			},
 		});
 
+		table.addGlobalSecondaryIndex({
+			indexName: 'GSI2',
+			partitionKey: {
+				name: 'GSI2PK',
+				type: ddb.AttributeType.STRING,
+			},
+			sortKey: {
+				name: 'GSI2SK',
+				type: ddb.AttributeType.STRING,
+			},
+		});
+
+		// Username search, sharded by the first letter of the lowercased username
+		table.addGlobalSecondaryIndex({
+			indexName: 'GSI3',
+			partitionKey: {
+				name: 'GSI3PK',
+				type: ddb.AttributeType.STRING,
+			},
+			sortKey: {
+				name: 'GSI3SK',
+				type: ddb.AttributeType.STRING,
+			},
+		});
+
 		return table;
 	}
 
@@ -260,7 +450,7 @@ export class AppStack extends cdk.Stack {
 			defaultCorsPreflightOptions: {
 				allowMethods: apigateway.Cors.ALL_METHODS,
 				allowOrigins: apigateway.Cors.ALL_ORIGINS,
-				allowHeaders: apigateway.Cors.DEFAULT_HEADERS,
+				allowHeaders: [...apigateway.Cors.DEFAULT_HEADERS, 'If-Match'],
 				maxAge: cdk.Duration.seconds(60),
 			},
 			cloudWatchRole: false,
@@ -274,10 +464,11 @@ export class AppStack extends cdk.Stack {
 				'Access-Control-Allow-Headers': "'*'",
 				'Access-Control-Allow-Methods': "'*'",
 				'Access-Control-Max-Age': "'86400'",
+				'Content-Type': "'application/problem+json'",
 			},
 			templates: {
 				'application/json':
-					'{ "ErrorMessage": "$context.authorizer.errorMessage", "ErrorCode": "$context.error.responseType", "Errors": [] }',
+					'{ "type": "urn:cf-user:problem:forbidden", "title": "Forbidden", "status": 403, "detail": "$context.authorizer.errorMessage", "instance": "$context.requestId", "code": "FORBIDDEN" }',
 			},
 		});
 
@@ -289,10 +480,11 @@ export class AppStack extends cdk.Stack {
 				'Access-Control-Allow-Headers': "'*'",
 				'Access-Control-Allow-Methods': "'*'",
 				'Access-Control-Max-Age': "'86400'",
+				'Content-Type': "'application/problem+json'",
 			},
 			templates: {
 				'application/json':
-					'{ "ErrorMessage": "Unauthorized", "ErrorCode": "$context.error.responseType", "Errors": [] }',
+					'{ "type": "urn:cf-user:problem:unauthorized", "title": "Unauthorized", "status": 401, "instance": "$context.requestId", "code": "UNAUTHORIZED" }',
 			},
 		});
 
@@ -320,7 +512,9 @@ export class AppStack extends cdk.Stack {
 		methodName: string,
 		resourceName: string,
 		props: AppStackProps,
-		snsTopic: sns.Topic
+		snsTopic: sns.Topic,
+		environment: { [key: string]: string } = {},
+		timeout: cdk.Duration = cdk.Duration.seconds(30)
 	): lambda.IFunction {
 		const newLambda = new lambda.Function(this, methodName, {
 			functionName: `${this.stackName}-${methodName}`,
@@ -328,13 +522,26 @@ export class AppStack extends cdk.Stack {
 			handler: 'main',
 			runtime: lambda.Runtime.PROVIDED_AL2,
 			architecture: lambda.Architecture.ARM_64,
-			timeout: cdk.Duration.seconds(30),
+			timeout,
 			memorySize: 1024,
 			environment: {
 				SERVICE: props.service,
 				STAGE: props.stage,
+				REQUIRE_IF_MATCH: 'false',
+				DELETED_USER_RETENTION_DAYS: '30',
+				EMAIL_LOWERCASE_LOCAL_PART: 'true',
+				EMAIL_FOLD_GMAIL: 'false',
+				USER_CACHE_TTL_SECONDS: '0',
+				USER_CACHE_CAPACITY: '1000',
+				...environment,
 			},
 			tracing: lambda.Tracing.ACTIVE,
+			// Serves secrets to the function at cold start, so none are kept in its configuration
+			paramsAndSecrets: environment.PAGINATION_TOKEN_SECRET_ARN
+				? lambda.ParamsAndSecretsLayerVersion.fromVersion(
+						lambda.ParamsAndSecretsVersions.V1_0_103
+				  )
+				: undefined,
 		});
 
 		new logs.LogGroup(this, `${methodName}LogGroup`, {
@@ -0,0 +1,249 @@
+use cf_user_core::{
+    args::export_users_args::ExportUsersArgs,
+    config, dynamo, export,
+    file_format::FileFormat,
+    import,
+    migration::{self, MigrationOptions, MIGRATIONS},
+    models::change_context::ChangeContext,
+    repository::DynamoUserRepository,
+};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+use std::process::ExitCode;
+use std::sync::Arc;
+use tokio::io::{AsyncWrite, BufWriter};
+
+static USAGE: &str = "Usage:
+    cf-user_admin import <FILE> --table <TABLE> [--format csv|ndjson] [--actor <NAME>]
+    cf-user_admin export --table <TABLE> [--output <FILE>] [--format csv|ndjson]
+                         [--columns <A,B,...>] [--created-from <DATE>] [--created-to <DATE>]
+                         [--include-deleted] [--segments <N>]
+    cf-user_admin migrations
+    cf-user_admin migrate --table <TABLE> [--only <NAME>] [--segments <N>] [--dry-run]
+    cf-user_admin migrate-email-keys --table <TABLE> [--dry-run]
+
+Commands:
+    import    Create users from an NDJSON or CSV file and print the import report.
+              The format is taken from the file extension unless --format is given.
+    export    Write users as NDJSON or CSV to --output, or to stdout when it is omitted.
+              The format is taken from the output extension unless --format is given.
+    migrations
+              List the item migrations in the order they are applied.
+    migrate   Apply every migration not yet recorded as applied, or only --only, and
+              print a report per migration. An interrupted run resumes from its
+              checkpoints. --dry-run counts the items that would change.
+    migrate-email-keys
+              Re-key users onto normalized emails and print the report, including
+              users whose emails collide. --dry-run reports without writing.";
+
+/// Positional arguments and `--name value` options of a command. An option with no
+/// value, like `--include-deleted`, is read as `true`.
+struct CommandArgs {
+    positional: Vec<String>,
+    options: HashMap<String, String>,
+}
+
+impl CommandArgs {
+    fn parse(args: impl Iterator<Item = String>) -> Self {
+        let mut positional: Vec<String> = Vec::new();
+        let mut options: HashMap<String, String> = HashMap::new();
+        let mut args = args.peekable();
+
+        while let Some(arg) = args.next() {
+            match arg.strip_prefix("--") {
+                Some(name) => {
+                    let value = match args.peek() {
+                        Some(value) if !value.starts_with("--") => args.next().unwrap(),
+                        _ => "true".to_string(),
+                    };
+                    options.insert(name.to_string(), value);
+                }
+                None => positional.push(arg),
+            }
+        }
+
+        Self {
+            positional,
+            options,
+        }
+    }
+
+    fn required(&self, name: &str) -> Result<&str, String> {
+        self.options
+            .get(name)
+            .map(String::as_str)
+            .ok_or_else(|| format!("Missing --{}", name))
+    }
+}
+
+async fn import_command(args: CommandArgs) -> Result<(), Box<dyn std::error::Error>> {
+    let path = args.positional.first().ok_or("Missing import file")?;
+    let table = args.required("table")?;
+
+    let format = match args.options.get("format") {
+        Some(format) => format.parse::<FileFormat>()?,
+        None => FileFormat::from_path(Path::new(path)),
+    };
+
+    let context = match args.options.get("actor") {
+        Some(actor) => ChangeContext::new(actor),
+        None => ChangeContext::new(&format!(
+            "admin:{}",
+            std::env::var("USER").unwrap_or_else(|_| "unknown".to_string())
+        )),
+    };
+
+    let client = dynamo::get_client().await?;
+    let repository = DynamoUserRepository::new(client, table);
+    let reader = BufReader::new(File::open(path)?);
+
+    let report = import::import_users(&repository, reader, format, &context).await?;
+
+    println!("{}", serde_json::to_string_pretty(&report)?);
+    eprintln!(
+        "{} created, {} skipped, {} rejected",
+        report.created, report.skipped, report.rejected
+    );
+
+    Ok(())
+}
+
+async fn export_command(args: CommandArgs) -> Result<(), Box<dyn std::error::Error>> {
+    let table = args.required("table")?;
+    let output = args.options.get("output");
+
+    let format = match (args.options.get("format"), output) {
+        (Some(format), _) => format.parse::<FileFormat>()?,
+        (None, Some(output)) => FileFormat::from_path(Path::new(output)),
+        (None, None) => FileFormat::default(),
+    };
+
+    // Scan pages are only continued within this run
+    config::use_process_pagination_token_secret();
+
+    let mut export_args = ExportUsersArgs {
+        format,
+        columns: args
+            .options
+            .get("columns")
+            .map(|columns| columns.split(',').map(|c| c.trim().to_string()).collect()),
+        created_from: args.options.get("created-from").cloned(),
+        created_to: args.options.get("created-to").cloned(),
+        include_deleted: args.options.get("include-deleted").map(String::as_str) == Some("true"),
+        ..Default::default()
+    };
+    if let Some(segments) = args.options.get("segments") {
+        export_args.segments = segments
+            .parse()
+            .map_err(|_| format!("Invalid --segments {}", segments))?;
+    }
+
+    let client = dynamo::get_client().await?;
+    let repository = Arc::new(DynamoUserRepository::new(client, table));
+
+    let mut writer: BufWriter<Box<dyn AsyncWrite + Unpin>> = match output {
+        Some(output) => BufWriter::new(Box::new(tokio::fs::File::create(output).await?)),
+        None => BufWriter::new(Box::new(tokio::io::stdout())),
+    };
+
+    let exported = export::export_users(repository, export_args, &mut writer).await?;
+
+    eprintln!("{} users exported", exported);
+
+    Ok(())
+}
+
+fn migrations_command() -> Result<(), Box<dyn std::error::Error>> {
+    for migration in MIGRATIONS.iter() {
+        println!("{}\t{}", migration.name, migration.description);
+    }
+
+    Ok(())
+}
+
+async fn migrate_command(args: CommandArgs) -> Result<(), Box<dyn std::error::Error>> {
+    let table = args.required("table")?;
+
+    let mut options = MigrationOptions {
+        dry_run: args.options.get("dry-run").map(String::as_str) == Some("true"),
+        ..Default::default()
+    };
+    if let Some(segments) = args.options.get("segments") {
+        options.total_segments = segments
+            .parse()
+            .map_err(|_| format!("Invalid --segments {}", segments))?;
+    }
+
+    let migrations =
+        match args.options.get("only") {
+            Some(name) => vec![migration::find_migration(name)
+                .ok_or_else(|| format!("Unknown migration {}", name))?],
+            None => MIGRATIONS.iter().collect(),
+        };
+
+    let client = dynamo::get_client().await?;
+    for migration in migrations {
+        let report = migration::run_migration(&client, table, migration, options, &|progress| {
+            eprintln!(
+                "{} segment {}: {} scanned, {} migrated, {} conflicts",
+                progress.name,
+                progress.segment,
+                progress.scanned,
+                progress.migrated,
+                progress.conflicts
+            )
+        })
+        .await?;
+
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    }
+
+    Ok(())
+}
+
+async fn migrate_email_keys_command(args: CommandArgs) -> Result<(), Box<dyn std::error::Error>> {
+    let table = args.required("table")?;
+    let dry_run = args.options.get("dry-run").map(String::as_str) == Some("true");
+
+    let client = dynamo::get_client().await?;
+    let report = dynamo::migrate_email_keys(&client, table, dry_run).await?;
+
+    println!("{}", serde_json::to_string_pretty(&report)?);
+    eprintln!(
+        "{} scanned, {} rekeyed, {} unchanged, {} collisions, {} failures",
+        report.scanned,
+        report.rekeyed,
+        report.unchanged,
+        report.collisions.len(),
+        report.failures.len()
+    );
+
+    Ok(())
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let mut args = std::env::args().skip(1);
+
+    let result = match args.next().as_deref() {
+        Some("import") => import_command(CommandArgs::parse(args)).await,
+        Some("export") => export_command(CommandArgs::parse(args)).await,
+        Some("migrations") => migrations_command(),
+        Some("migrate") => migrate_command(CommandArgs::parse(args)).await,
+        Some("migrate-email-keys") => migrate_email_keys_command(CommandArgs::parse(args)).await,
+        _ => {
+            eprintln!("{}", USAGE);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("Error: {}", err);
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,25 @@
+use cf_user_core::{router, secrets};
+use lambda_http::{run, service_fn, Error, Request};
+
+/// Serves every user API route from one Lambda, for stacks deployed with a single
+/// API function rather than one per route.
+#[tokio::main]
+async fn main() -> Result<(), Error> {
+    tracing_subscriber::fmt()
+        .with_max_level(tracing::Level::INFO)
+        .with_target(false)
+        .with_ansi(false)
+        .without_time()
+        .init();
+
+    secrets::load_pagination_token_secret().await?;
+
+    let router = router::user_routes();
+
+    run(service_fn(|event: Request| {
+        let router = &router;
+
+        async move { router.handle_request(event, None).await }
+    }))
+    .await
+}
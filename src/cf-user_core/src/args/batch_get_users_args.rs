@@ -0,0 +1,10 @@
+use serde::{Deserialize, Serialize};
+
+/// Most IDs a single batch get may ask for.
+pub static MAX_BATCH_GET_USER_IDS: usize = 1000;
+
+#[derive(Clone, Deserialize, Serialize, Debug)]
+pub struct BatchGetUsersArgs {
+    #[serde(rename = "UserIds")]
+    pub user_ids: Vec<String>,
+}
@@ -1,3 +1,4 @@
+use super::super::models::problem::FieldError;
 use serde::{Deserialize, Serialize};
 
 #[derive(Clone, Deserialize, Serialize, Debug)]
@@ -22,4 +23,49 @@ pub struct CreateUserArgs {
 
     #[serde(rename = "PhoneNumber")]
     pub phone_number: Option<String>,
-}
\ No newline at end of file
+}
+
+impl CreateUserArgs {
+    /// Checks the fields a new user needs before anything is written.
+    pub fn validate(&self) -> Result<(), String> {
+        match self.field_errors().into_iter().next() {
+            Some(error) => Err(error.detail),
+            None => Ok(()),
+        }
+    }
+
+    /// Every field that fails `validate`, with the reason.
+    pub fn field_errors(&self) -> Vec<FieldError> {
+        user_field_errors(&self.username, &self.email)
+    }
+}
+
+/// Every invalid field among those a user must have, with the reason. Shared by
+/// creates and updates, which write the same uniqueness guards.
+pub fn user_field_errors(username: &str, email: &str) -> Vec<FieldError> {
+    let mut errors = Vec::new();
+
+    if username.trim().is_empty() {
+        errors.push(FieldError::new("Username", "Username is required"));
+    } else if username.chars().any(char::is_whitespace) {
+        errors.push(FieldError::new(
+            "Username",
+            "Username must not contain whitespace",
+        ));
+    }
+
+    match email.split_once('@') {
+        Some((local, domain))
+            if !local.is_empty()
+                && domain.contains('.')
+                && !domain.starts_with('.')
+                && !domain.ends_with('.')
+                && !email.chars().any(char::is_whitespace) => {}
+        _ => errors.push(FieldError::new(
+            "Email",
+            &format!("Invalid email address: {}", email),
+        )),
+    }
+
+    errors
+}
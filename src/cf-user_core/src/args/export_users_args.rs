@@ -0,0 +1,28 @@
+use super::super::file_format::FileFormat;
+
+#[derive(Clone, Debug)]
+pub struct ExportUsersArgs {
+    pub format: FileFormat,
+    /// `User` fields to write, in order. Every field is written when unset.
+    pub columns: Option<Vec<String>>,
+    /// Only users created at or after this `CreatedDate`.
+    pub created_from: Option<String>,
+    /// Only users created before this `CreatedDate`.
+    pub created_to: Option<String>,
+    pub include_deleted: bool,
+    /// Scan segments read in parallel.
+    pub segments: i32,
+}
+
+impl Default for ExportUsersArgs {
+    fn default() -> Self {
+        Self {
+            format: FileFormat::default(),
+            columns: None,
+            created_from: None,
+            created_to: None,
+            include_deleted: false,
+            segments: 4,
+        }
+    }
+}
@@ -0,0 +1,19 @@
+use super::list_users_args::SortOrder;
+
+#[derive(Clone, Debug)]
+pub struct ListUserHistoryArgs {
+    pub limit: i32,
+    pub pagination_token: Option<String>,
+    pub order: SortOrder,
+}
+
+impl Default for ListUserHistoryArgs {
+    /// Newest changes first.
+    fn default() -> Self {
+        Self {
+            limit: 25,
+            pagination_token: None,
+            order: SortOrder::Descending,
+        }
+    }
+}
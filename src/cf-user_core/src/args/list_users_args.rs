@@ -0,0 +1,45 @@
+use std::str::FromStr;
+
+/// Direction `list_users` walks its index, which is ordered by creation date, or by
+/// username when searching by prefix.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SortOrder {
+    #[default]
+    Ascending,
+    Descending,
+}
+
+impl FromStr for SortOrder {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_lowercase().as_str() {
+            "asc" | "ascending" => Ok(SortOrder::Ascending),
+            "desc" | "descending" => Ok(SortOrder::Descending),
+            _ => Err(format!("Invalid sort order: {}", value)),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct ListUsersArgs {
+    pub limit: i32,
+    pub pagination_token: Option<String>,
+    pub order: SortOrder,
+    pub include_deleted: bool,
+    /// Only users whose username starts with this, ignoring case. Users are then
+    /// ordered by username rather than creation date.
+    pub username_prefix: Option<String>,
+}
+
+impl Default for ListUsersArgs {
+    fn default() -> Self {
+        Self {
+            limit: 25,
+            pagination_token: None,
+            order: SortOrder::default(),
+            include_deleted: false,
+            username_prefix: None,
+        }
+    }
+}
@@ -1,2 +1,7 @@
+pub mod batch_get_users_args;
 pub mod create_user_args;
-pub mod update_user_args;
\ No newline at end of file
+pub mod export_users_args;
+pub mod list_user_history_args;
+pub mod list_users_args;
+pub mod scan_users_args;
+pub mod update_user_args;
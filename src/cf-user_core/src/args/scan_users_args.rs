@@ -0,0 +1,25 @@
+/// One segment of a parallel Scan over the user items.
+#[derive(Clone, Debug)]
+pub struct ScanUsersArgs {
+    pub segment: i32,
+    pub total_segments: i32,
+    /// Only users created at or after this `CreatedDate`.
+    pub created_from: Option<String>,
+    /// Only users created before this `CreatedDate`.
+    pub created_to: Option<String>,
+    pub include_deleted: bool,
+    pub pagination_token: Option<String>,
+}
+
+impl Default for ScanUsersArgs {
+    fn default() -> Self {
+        Self {
+            segment: 0,
+            total_segments: 1,
+            created_from: None,
+            created_to: None,
+            include_deleted: false,
+            pagination_token: None,
+        }
+    }
+}
@@ -1,6 +1,11 @@
+use super::super::{item::DynamoItem, models::problem::FieldError};
+use super::create_user_args::user_field_errors;
 use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Deserialize, Serialize, Debug)]
+/// The profile written by an update. As an item it holds exactly the attributes a PUT
+/// replaces.
+#[derive(Clone, Deserialize, Serialize, Debug, DynamoItem)]
+#[dynamo(rename_all = "PascalCase")]
 pub struct UpdateUserArgs {
     #[serde(rename = "Username")]
     pub username: String,
@@ -23,3 +28,10 @@ pub struct UpdateUserArgs {
     #[serde(rename = "PhoneNumber")]
     pub phone_number: Option<String>,
 }
+
+impl UpdateUserArgs {
+    /// Every field that fails the checks a new user must pass, with the reason.
+    pub fn field_errors(&self) -> Vec<FieldError> {
+        user_field_errors(&self.username, &self.email)
+    }
+}
@@ -44,18 +44,15 @@ pub async fn create_mock_config(
         let file_value: Value = serde_json::from_str(&data).expect("Error parsing file");
         let expiration_exists = file_value.get("accessToken");
 
-        if let Some(_) = expiration_exists {
+        if expiration_exists.is_some() {
             let file_credentials: CachedCredentials =
                 serde_json::from_str(&data).expect("Error parsing caches credentials");
 
-            if let Some(current_credentials) = cached_credentials {
-                if current_credentials.expires_at < file_credentials.expires_at {
-                    cached_credentials = Some(file_credentials.to_owned());
-                }
+            match &cached_credentials {
+                Some(current_credentials)
+                    if current_credentials.expires_at >= file_credentials.expires_at => {}
+                _ => cached_credentials = Some(file_credentials),
             }
-
-            cached_credentials =
-                Some(serde_json::from_str(&data).expect("Error parsing caches credentials"));
         }
     }
 
@@ -113,7 +110,7 @@ pub async fn create_mock_config(
                 .load()
                 .await;
 
-            return Ok(sdk_config);
+            Ok(sdk_config)
         } else {
             panic!("Error retrieving role credentials")
         }
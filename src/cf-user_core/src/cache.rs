@@ -0,0 +1,231 @@
+use super::{config::Config, dynamo, models::user::User};
+
+use lru::LruCache;
+use std::num::NonZeroUsize;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use tokio::time::{Duration, Instant};
+
+static SHARED_CACHE: OnceLock<Option<Arc<UserCache>>> = OnceLock::new();
+
+/// Lookups answered from the cache and lookups that had to read the table, since
+/// the cache was created.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+}
+
+#[derive(Clone, Debug)]
+struct Entry<T> {
+    value: T,
+    expires_at: Instant,
+}
+
+#[derive(Debug)]
+struct Entries {
+    users: LruCache<String, Entry<Option<User>>>,
+    emails: LruCache<String, Entry<Option<Vec<User>>>>,
+}
+
+/// Bounded LRU cache of user lookups by ID and by email, each kept for a fixed TTL.
+/// Lookups that found nothing are cached too, so repeated 404s stay off the table.
+///
+/// Entries hold users whether or not they are deleted, leaving callers to filter
+/// them, so one entry answers lookups with and without `include_deleted`.
+#[derive(Debug)]
+pub struct UserCache {
+    ttl: Duration,
+    entries: Mutex<Entries>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl UserCache {
+    pub fn new(capacity: NonZeroUsize, ttl: Duration) -> Self {
+        Self {
+            ttl,
+            entries: Mutex::new(Entries {
+                users: LruCache::new(capacity),
+                emails: LruCache::new(capacity),
+            }),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    /// The cache `config` asks for, or `None` when its TTL or capacity is `0`.
+    pub fn from_config(config: &Config) -> Option<Self> {
+        let capacity = NonZeroUsize::new(config.user_cache_capacity)?;
+        if config.user_cache_ttl_seconds == 0 {
+            return None;
+        }
+
+        Some(Self::new(
+            capacity,
+            Duration::from_secs(config.user_cache_ttl_seconds),
+        ))
+    }
+
+    /// The cache of this process, configured from the environment, which outlives
+    /// the invocations a warm Lambda container serves.
+    pub fn shared() -> Option<Arc<Self>> {
+        SHARED_CACHE
+            .get_or_init(|| Self::from_config(&Config::from_env()).map(Arc::new))
+            .clone()
+    }
+
+    /// The cached lookup of `user_id`, where `Some(None)` means the user is known
+    /// not to exist.
+    pub fn get_user(&self, user_id: &str) -> Option<Option<User>> {
+        let mut entries = self.entries.lock().unwrap();
+        let value = fresh(&mut entries.users, user_id);
+        self.count(value.is_some());
+        value
+    }
+
+    pub fn put_user(&self, user_id: &str, user: Option<User>) {
+        let entry = self.entry(user);
+        self.entries
+            .lock()
+            .unwrap()
+            .users
+            .put(user_id.to_owned(), entry);
+    }
+
+    /// The cached lookup of `email`, which shares an entry with every address that
+    /// normalizes to the same key.
+    pub fn get_email(&self, email: &str) -> Option<Option<Vec<User>>> {
+        let mut entries = self.entries.lock().unwrap();
+        let value = fresh(&mut entries.emails, &dynamo::email_key(email));
+        self.count(value.is_some());
+        value
+    }
+
+    pub fn put_email(&self, email: &str, users: Option<Vec<User>>) {
+        let entry = self.entry(users);
+        self.entries
+            .lock()
+            .unwrap()
+            .emails
+            .put(dynamo::email_key(email), entry);
+    }
+
+    /// Drops the lookup of `user_id` along with every email lookup that found the
+    /// user.
+    pub fn invalidate_user(&self, user_id: &str) {
+        let mut entries = self.entries.lock().unwrap();
+        entries.users.pop(user_id);
+
+        let emails: Vec<String> = entries
+            .emails
+            .iter()
+            .filter(|(_, entry)| {
+                entry
+                    .value
+                    .iter()
+                    .flatten()
+                    .any(|user| user.user_id == user_id)
+            })
+            .map(|(email, _)| email.clone())
+            .collect();
+        for email in emails {
+            entries.emails.pop(&email);
+        }
+    }
+
+    pub fn invalidate_email(&self, email: &str) {
+        self.entries
+            .lock()
+            .unwrap()
+            .emails
+            .pop(&dynamo::email_key(email));
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+        }
+    }
+
+    fn entry<T>(&self, value: T) -> Entry<T> {
+        Entry {
+            value,
+            expires_at: Instant::now() + self.ttl,
+        }
+    }
+
+    fn count(&self, hit: bool) {
+        match hit {
+            true => self.hits.fetch_add(1, Ordering::Relaxed),
+            false => self.misses.fetch_add(1, Ordering::Relaxed),
+        };
+    }
+}
+
+/// The value cached under `key` unless it has expired, in which case it is dropped.
+fn fresh<T: Clone>(cache: &mut LruCache<String, Entry<T>>, key: &str) -> Option<T> {
+    match cache.get(key) {
+        Some(entry) if entry.expires_at > Instant::now() => Some(entry.value.clone()),
+        Some(_) => {
+            cache.pop(key);
+            None
+        }
+        None => None,
+    }
+}
+
+#[cfg(test)]
+mod unit_tests {
+    use super::*;
+
+    fn cache(capacity: usize) -> UserCache {
+        UserCache::new(
+            NonZeroUsize::new(capacity).unwrap(),
+            Duration::from_secs(60),
+        )
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn should_expire_entries_after_the_ttl() {
+        let cache = cache(10);
+        cache.put_user("abc123", None);
+
+        tokio::time::advance(Duration::from_secs(59)).await;
+        assert_eq!(cache.get_user("abc123"), Some(None));
+
+        tokio::time::advance(Duration::from_secs(1)).await;
+        assert_eq!(cache.get_user("abc123"), None);
+        assert_eq!(cache.stats(), CacheStats { hits: 1, misses: 1 });
+    }
+
+    #[test]
+    fn should_evict_the_least_recently_used_entry() {
+        let cache = cache(2);
+        cache.put_user("first", None);
+        cache.put_user("second", None);
+        cache.get_user("first");
+
+        cache.put_user("third", None);
+
+        assert_eq!(cache.get_user("first"), Some(None));
+        assert_eq!(cache.get_user("second"), None);
+        assert_eq!(cache.get_user("third"), Some(None));
+    }
+
+    #[test]
+    fn should_only_be_enabled_with_a_ttl_and_capacity() {
+        let config = Config {
+            user_cache_ttl_seconds: 30,
+            ..Default::default()
+        };
+        assert!(UserCache::from_config(&config).is_some());
+        assert!(UserCache::from_config(&Config::default()).is_none());
+        assert!(UserCache::from_config(&Config {
+            user_cache_capacity: 0,
+            ..config
+        })
+        .is_none());
+    }
+}
@@ -0,0 +1,123 @@
+use std::env;
+use std::iter;
+use std::sync::OnceLock;
+
+static DEFAULT_PAGINATION_TOKEN_TTL_SECONDS: i64 = 24 * 60 * 60;
+static DEFAULT_DELETED_USER_RETENTION_DAYS: i64 = 30;
+static DEFAULT_EXPORT_DIRECTORY: &str = "/tmp";
+static DEFAULT_EVENT_SOURCE: &str = "cf-user";
+static DEFAULT_USER_CACHE_CAPACITY: usize = 1000;
+
+/// Runtime switches read from the Lambda environment.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Config {
+    /// Reject PUT and DELETE requests that omit `If-Match` with 428 instead of
+    /// applying them unconditionally.
+    pub require_if_match: bool,
+
+    /// Key used to sign and verify pagination tokens. Empty when none is configured,
+    /// in which case tokens are refused rather than signed with an empty key.
+    pub pagination_token_secret: String,
+
+    /// How long a pagination token stays valid after the page it continues.
+    pub pagination_token_ttl_seconds: i64,
+
+    /// How long a deleted user can be restored before its TTL purges it.
+    pub deleted_user_retention_days: i64,
+
+    /// Directory the export Lambda writes its files to.
+    pub export_directory: String,
+
+    /// Treat the local part of email addresses as case-insensitive when matching
+    /// users, as nearly every mail provider does.
+    pub email_lowercase_local_part: bool,
+
+    /// Match Gmail addresses regardless of dots and `+tag` suffixes in the local part.
+    pub email_fold_gmail: bool,
+
+    /// `source` of the user events the stream processor publishes.
+    pub event_source: String,
+
+    /// How long a warm Lambda may answer user lookups from its in-process cache.
+    /// `0`, the default, turns the cache off.
+    pub user_cache_ttl_seconds: u64,
+
+    /// Most lookups the in-process user cache holds of each kind, by ID and by email.
+    pub user_cache_capacity: usize,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            require_if_match: false,
+            pagination_token_secret: String::new(),
+            pagination_token_ttl_seconds: DEFAULT_PAGINATION_TOKEN_TTL_SECONDS,
+            deleted_user_retention_days: DEFAULT_DELETED_USER_RETENTION_DAYS,
+            export_directory: DEFAULT_EXPORT_DIRECTORY.to_owned(),
+            email_lowercase_local_part: true,
+            email_fold_gmail: false,
+            event_source: DEFAULT_EVENT_SOURCE.to_owned(),
+            user_cache_ttl_seconds: 0,
+            user_cache_capacity: DEFAULT_USER_CACHE_CAPACITY,
+        }
+    }
+}
+
+impl Config {
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+
+        Self {
+            require_if_match: env_flag("REQUIRE_IF_MATCH"),
+            pagination_token_secret: env::var("PAGINATION_TOKEN_SECRET")
+                .ok()
+                .or_else(|| PAGINATION_TOKEN_SECRET.get().cloned())
+                .unwrap_or(defaults.pagination_token_secret),
+            pagination_token_ttl_seconds: env::var("PAGINATION_TOKEN_TTL_SECONDS")
+                .ok()
+                .and_then(|value| value.parse().ok())
+                .unwrap_or(defaults.pagination_token_ttl_seconds),
+            deleted_user_retention_days: env::var("DELETED_USER_RETENTION_DAYS")
+                .ok()
+                .and_then(|value| value.parse().ok())
+                .unwrap_or(defaults.deleted_user_retention_days),
+            export_directory: env::var("EXPORT_DIRECTORY").unwrap_or(defaults.export_directory),
+            email_lowercase_local_part: env::var("EMAIL_LOWERCASE_LOCAL_PART")
+                .map(|_| env_flag("EMAIL_LOWERCASE_LOCAL_PART"))
+                .unwrap_or(defaults.email_lowercase_local_part),
+            email_fold_gmail: env_flag("EMAIL_FOLD_GMAIL"),
+            event_source: env::var("EVENT_SOURCE").unwrap_or(defaults.event_source),
+            user_cache_ttl_seconds: env::var("USER_CACHE_TTL_SECONDS")
+                .ok()
+                .and_then(|value| value.parse().ok())
+                .unwrap_or(defaults.user_cache_ttl_seconds),
+            user_cache_capacity: env::var("USER_CACHE_CAPACITY")
+                .ok()
+                .and_then(|value| value.parse().ok())
+                .unwrap_or(defaults.user_cache_capacity),
+        }
+    }
+}
+
+/// Pagination token secret held by the process, used when `PAGINATION_TOKEN_SECRET`
+/// is unset.
+static PAGINATION_TOKEN_SECRET: OnceLock<String> = OnceLock::new();
+
+/// Sets the pagination token secret of the process, e.g. once it is read at cold
+/// start. Only the first secret set is kept.
+pub fn set_pagination_token_secret(secret: String) {
+    let _ = PAGINATION_TOKEN_SECRET.set(secret);
+}
+
+/// Signs pagination tokens with a random key unless one was set, for processes whose
+/// tokens never leave them, such as an export or the in-memory repository.
+pub fn use_process_pagination_token_secret() {
+    PAGINATION_TOKEN_SECRET
+        .get_or_init(|| iter::repeat_with(fastrand::alphanumeric).take(64).collect());
+}
+
+fn env_flag(name: &str) -> bool {
+    env::var(name)
+        .map(|value| matches!(value.to_lowercase().as_str(), "1" | "true" | "yes"))
+        .unwrap_or(false)
+}
@@ -1,19 +1,54 @@
-use crate::models::paginated_result::{EncodedToken, PaginationToken};
-
 use super::{
-    args::{create_user_args::CreateUserArgs, update_user_args::UpdateUserArgs},
+    args::{
+        create_user_args::CreateUserArgs,
+        list_user_history_args::ListUserHistoryArgs,
+        list_users_args::{ListUsersArgs, SortOrder},
+        scan_users_args::ScanUsersArgs,
+        update_user_args::UpdateUserArgs,
+    },
     aws_config_loader::create_mock_config,
-    models::paginated_result::PaginatedResult,
-    models::user::User,
+    config::Config,
+    email::{normalize_email, plan_email_rekeys, EmailRekey},
+    error::{self, RepositoryError, UniqueField},
+    ext::AttributeValuesExt,
+    fieldset::Fieldset,
+    item::{DynamoItem, Item},
+    models::batch_get_result::BatchGetResult,
+    models::change_context::ChangeContext,
+    models::email_migration_report::{EmailMigrationFailure, EmailMigrationReport},
+    models::outbox_entry::OutboxEntry,
+    models::paginated_result::{EncodedToken, PaginatedResult, PaginationToken},
+    models::partial_user::PartialUser,
+    models::user::{User, UserStatus},
+    models::user_event::{CloudEvent, UserEvent},
+    models::user_history::{HistoryOperation, UserHistory},
+    timestamp,
+    update_expression::UpdateExpression,
 };
 
-use aws_sdk_dynamodb::types::{AttributeAction, AttributeValue, AttributeValueUpdate};
+use aws_sdk_dynamodb::error::SdkError;
+use aws_sdk_dynamodb::operation::transact_write_items::TransactWriteItemsError;
+use aws_sdk_dynamodb::types::{
+    AttributeValue, Delete, KeysAndAttributes, Put, TransactWriteItem, Update,
+};
 use aws_sdk_dynamodb::{Client, Error};
-use chrono;
-use std::collections::HashMap;
-use ulid::Ulid;
+use chrono::{DateTime, Utc};
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+use std::time::Duration;
+use ulid::{Generator, Ulid};
+
+static GSI_1_INDEX: &str = "GSI1";
+static GSI_2_INDEX: &str = "GSI2";
+static GSI_2_USERS_PARTITION: &str = "USERS";
+static OUTBOX_PARTITION: &str = "OUTBOX";
+static OUTBOX_DEAD_LETTER_PARTITION: &str = "OUTBOX_DEAD_LETTER";
+static GSI_3_INDEX: &str = "GSI3";
 
-static GSI_1_INDEX: &'static str = "GSI1";
+/// Most keys BatchGetItem accepts in one request.
+static BATCH_GET_CHUNK_SIZE: usize = 100;
+static BATCH_MAX_ATTEMPTS: u32 = 5;
+static BATCH_BASE_DELAY_MS: u64 = 50;
 
 pub async fn create_client() -> Result<Client, Error> {
     let account_id = "584620395262";
@@ -24,7 +59,7 @@ pub async fn create_client() -> Result<Client, Error> {
 
     if let Ok(config) = sdk_config {
         let client = Client::new(&config);
-        return Ok(client);
+        Ok(client)
     } else {
         panic!("Error accessing mock aws config")
     }
@@ -34,7 +69,7 @@ pub async fn get_client() -> Result<Client, Error> {
     let config = aws_config::load_from_env().await;
     let client = Client::new(&config);
 
-    return Ok(client);
+    Ok(client)
 }
 
 pub async fn get_user_by_id(
@@ -51,7 +86,7 @@ pub async fn get_user_by_id(
         .key("PK", pk)
         .key("SK", sk);
 
-    let resp = request.send().await?;
+    let resp = request.send().await.map_err(RepositoryError::from)?;
 
     if let Some(item) = resp.item {
         let user: User = User::try_from(item.clone())?;
@@ -59,7 +94,31 @@ pub async fn get_user_by_id(
         return Ok(Some(user));
     }
 
-    return Ok(None);
+    Ok(None)
+}
+
+/// Reads only the attributes `fields` projects from a user item.
+pub async fn get_user_fields_by_id(
+    client: &Client,
+    table: &str,
+    user_id: &str,
+    fields: &Fieldset,
+) -> Result<Option<PartialUser>, Box<dyn std::error::Error>> {
+    let key = String::from("USER#") + user_id;
+    let (projection, names) = fields.projection();
+
+    let resp = client
+        .get_item()
+        .table_name(table)
+        .key("PK", AttributeValue::S(key.clone()))
+        .key("SK", AttributeValue::S(key))
+        .projection_expression(projection)
+        .set_expression_attribute_names(Some(names))
+        .send()
+        .await
+        .map_err(RepositoryError::from)?;
+
+    Ok(resp.item.map(|item| fields.select_item(&item)))
 }
 
 pub async fn get_user_by_email(
@@ -67,7 +126,7 @@ pub async fn get_user_by_email(
     table: &str,
     email: &str,
 ) -> Result<Option<Vec<User>>, Box<dyn std::error::Error>> {
-    let gsi1pk: AttributeValue = AttributeValue::S(String::from("EMAIL#") + email);
+    let gsi1pk: AttributeValue = AttributeValue::S(email_key(email));
     let gsi1sk: AttributeValue = AttributeValue::S(String::from("USERNAME#"));
 
     let mut expression_values: HashMap<String, AttributeValue> = HashMap::new();
@@ -83,7 +142,7 @@ pub async fn get_user_by_email(
         ))
         .set_expression_attribute_values(Some(expression_values));
 
-    let resp = request.send().await?;
+    let resp = request.send().await.map_err(RepositoryError::from)?;
 
     if resp.count <= 0 {
         return Ok(None);
@@ -101,292 +160,1343 @@ pub async fn get_user_by_email(
         return Ok(Some(users));
     }
 
-    return Ok(None);
+    Ok(None)
+}
+
+/// Fetches users by ID with BatchGetItem, 100 keys per request. Keys DynamoDB leaves
+/// unprocessed are retried with exponential backoff. Found users are returned in the
+/// order they were requested, and IDs with no user are reported as missing.
+pub async fn get_users_by_ids(
+    client: &Client,
+    table: &str,
+    user_ids: &[String],
+) -> Result<BatchGetResult<User>, Box<dyn std::error::Error>> {
+    // BatchGetItem rejects requests that name the same key twice
+    let mut seen: HashSet<&str> = HashSet::new();
+    let unique_ids: Vec<&str> = user_ids
+        .iter()
+        .map(String::as_str)
+        .filter(|user_id| seen.insert(user_id))
+        .collect();
+
+    let mut found: HashMap<String, User> = HashMap::new();
+
+    for chunk in unique_ids.chunks(BATCH_GET_CHUNK_SIZE) {
+        let keys: Vec<HashMap<String, AttributeValue>> = chunk
+            .iter()
+            .map(|user_id| {
+                let key = String::from("USER#") + user_id;
+                HashMap::from([
+                    ("PK".to_owned(), AttributeValue::S(key.clone())),
+                    ("SK".to_owned(), AttributeValue::S(key)),
+                ])
+            })
+            .collect();
+
+        let mut request_items = Some(HashMap::from([(
+            table.to_owned(),
+            KeysAndAttributes::builder().set_keys(Some(keys)).build(),
+        )]));
+        let mut attempt = 0;
+
+        while let Some(items) = request_items.take().filter(|items| !items.is_empty()) {
+            if attempt == BATCH_MAX_ATTEMPTS {
+                return Err(Box::new(error::Error::InternalError(
+                    "Keys remained unprocessed after retrying",
+                )));
+            }
+            if attempt > 0 {
+                tokio::time::sleep(batch_backoff(attempt)).await;
+            }
+            attempt += 1;
+
+            let resp = client
+                .batch_get_item()
+                .set_request_items(Some(items))
+                .send()
+                .await
+                .map_err(RepositoryError::from)?;
+
+            for item in resp
+                .responses
+                .and_then(|mut responses| responses.remove(table))
+                .unwrap_or_default()
+            {
+                let user = User::try_from(item)?;
+                found.insert(user.user_id.clone(), user);
+            }
+
+            request_items = resp.unprocessed_keys;
+        }
+    }
+
+    Ok(order_batch_result(user_ids, found))
+}
+
+/// Delay before retry `attempt` of a batch request, doubling from 50ms.
+pub fn batch_backoff(attempt: u32) -> Duration {
+    Duration::from_millis(BATCH_BASE_DELAY_MS * 2u64.pow(attempt.saturating_sub(1)))
+}
+
+/// Arranges found users in the order their IDs were requested. IDs asked for more
+/// than once appear once, at their first position.
+pub fn order_batch_result(
+    user_ids: &[String],
+    mut found: HashMap<String, User>,
+) -> BatchGetResult<User> {
+    let mut data: Vec<User> = Vec::new();
+    let mut missing: Vec<String> = Vec::new();
+    let mut seen: HashSet<&str> = HashSet::new();
+
+    for user_id in user_ids {
+        if !seen.insert(user_id) {
+            continue;
+        }
+
+        match found.remove(user_id) {
+            Some(user) => data.push(user),
+            None => missing.push(user_id.to_owned()),
+        }
+    }
+
+    BatchGetResult { data, missing }
 }
 
 pub async fn create_user(
     client: &Client,
     table: &str,
     input: CreateUserArgs,
+    context: &ChangeContext,
 ) -> Result<String, Box<dyn std::error::Error>> {
     let user_id = Ulid::new().to_string();
-    let current_date = chrono::offset::Utc::now().to_string();
-
-    let mut insert_map: HashMap<String, AttributeValue> = HashMap::new();
+    let now = timestamp::now();
+    let current_date = timestamp::format(&now);
 
-    insert_map.insert(
-        "PK".to_owned(),
-        AttributeValue::S(String::from("USER#") + user_id.to_owned().as_str()),
+    let email_guard = email_guard_key(&input.email);
+    let username_guard = username_guard_key(&input.username);
+    let insert_map = create_user_item(&user_id, &now, input);
+    let history_item = create_history_item(
+        &user_id,
+        &context.principal_id,
+        HistoryOperation::Create,
+        None,
+        &insert_map,
+        &current_date,
     );
+    let outbox_item = outbox_item(context, None, Some(&insert_map), &now)?;
+
+    let mut items = vec![
+        (put_if_not_exists(table, insert_map), None),
+        (put_if_not_exists(table, history_item), None),
+        (
+            put_if_not_exists(table, create_guard_item(&email_guard, &user_id)),
+            Some(error::Error::ConflictError(UniqueField::Email)),
+        ),
+        (
+            put_if_not_exists(table, create_guard_item(&username_guard, &user_id)),
+            Some(error::Error::ConflictError(UniqueField::Username)),
+        ),
+    ];
+    items.extend(outbox_item.map(|item| (put_if_not_exists(table, item), None)));
+
+    transact_write_items(client, items).await?;
+
+    Ok(user_id)
+}
+
+/// Returns the ID of the user holding the uniqueness guard for `value`, if any.
+pub async fn get_unique_value_owner(
+    client: &Client,
+    table: &str,
+    field: UniqueField,
+    value: &str,
+) -> Result<Option<String>, Box<dyn std::error::Error>> {
+    let key = guard_key(field, value);
+
+    let resp = client
+        .get_item()
+        .table_name(table)
+        .key("PK", AttributeValue::S(key.clone()))
+        .key("SK", AttributeValue::S(key))
+        .send()
+        .await
+        .map_err(RepositoryError::from)?;
+
+    Ok(resp
+        .item
+        .and_then(|item| item.get("UserId")?.as_s().ok().cloned()))
+}
+
+/// Builds the full item written for a new user, including its primary and index keys.
+pub fn create_user_item(
+    user_id: &str,
+    created_date: &DateTime<Utc>,
+    input: CreateUserArgs,
+) -> HashMap<String, AttributeValue> {
+    let user = User {
+        user_id: user_id.to_owned(),
+        gsi1pk: email_key(&input.email),
+        username: input.username,
+        first_name: input.first_name,
+        last_name: input.last_name,
+        email: input.email,
+        profile_photo: input.profile_photo,
+        summary: input.summary,
+        phone_number: input.phone_number,
+        created_date: *created_date,
+        updated_date: *created_date,
+        version: 1,
+        status: UserStatus::Active,
+        deleted_at: None,
+        expires_at: None,
+    };
+
+    let mut insert_map = HashMap::from(&user);
     insert_map.insert(
-        "SK".to_owned(),
-        AttributeValue::S(String::from("USER#") + user_id.to_owned().as_str()),
+        "GSI2PK".to_owned(),
+        AttributeValue::S(GSI_2_USERS_PARTITION.to_owned()),
     );
-    insert_map.insert("UserId".to_owned(), AttributeValue::S(user_id.to_owned()));
     insert_map.insert(
-        "Username".to_owned(),
-        AttributeValue::S(input.username.to_owned()),
+        "GSI2SK".to_owned(),
+        AttributeValue::S(list_index_sort_key(created_date, user_id)),
     );
     insert_map.insert(
-        "Email".to_owned(),
-        AttributeValue::S(input.email.to_owned()),
+        "GSI3PK".to_owned(),
+        AttributeValue::S(username_index_partition(&user.username)),
     );
     insert_map.insert(
-        "CreatedDate".to_owned(),
-        AttributeValue::S(current_date.clone()),
+        "GSI3SK".to_owned(),
+        AttributeValue::S(username_index_sort_key(&user.username)),
     );
-    insert_map.insert(
-        "UpdatedDate".to_owned(),
-        AttributeValue::S(current_date.clone()),
+
+    insert_map
+}
+
+pub async fn update_user(
+    client: &Client,
+    table: &str,
+    user_id: &str,
+    input: UpdateUserArgs,
+    expected_version: Option<u64>,
+    context: &ChangeContext,
+) -> Result<bool, Box<dyn std::error::Error>> {
+    let now = timestamp::now();
+    let current_date = timestamp::format(&now);
+    let current_user = match get_user_by_id(client, table, user_id).await? {
+        Some(user) if user.deleted_at.is_none() => user,
+        _ => {
+            return Err(Box::new(condition_error(
+                expected_version,
+                "User does not exist or is deleted",
+            )))
+        }
+    };
+
+    let mut items: Vec<(TransactWriteItem, Option<error::Error>)> = Vec::new();
+
+    for (field, current_value, new_value) in [
+        (
+            UniqueField::Email,
+            current_user.email.as_str(),
+            input.email.as_str(),
+        ),
+        (
+            UniqueField::Username,
+            current_user.username.as_str(),
+            input.username.as_str(),
+        ),
+    ] {
+        // Spellings of the same email share a guard, so only a new key needs one
+        let current_guard = guard_key(field, current_value);
+        let new_guard = guard_key(field, new_value);
+        if current_guard == new_guard {
+            continue;
+        }
+
+        items.push((
+            put_if_not_exists(table, create_guard_item(&new_guard, user_id)),
+            Some(error::Error::ConflictError(field)),
+        ));
+        items.push((delete_item(table, &current_guard, &current_guard), None));
+    }
+
+    let key = String::from("USER#") + user_id;
+    let expression = with_list_index_keys(
+        update_user_expression(&current_date, input),
+        &current_user.user_id,
+        &current_user.created_date,
     );
-    insert_map.insert(
-        "GSI1PK".to_owned(),
-        AttributeValue::S(String::from("EMAIL#") + input.email.to_owned().as_str()),
+    // Without If-Match, the guard swap above is only valid for the version it was read
+    // at. Either way, the user must not have been deleted or purged since.
+    let expression = version_condition(
+        expression.condition_attribute_not_exists("DeletedAt"),
+        expected_version.unwrap_or(current_user.version),
     );
-    insert_map.insert(
-        "GSI1SK".to_owned(),
-        AttributeValue::S(String::from("USERNAME#") + input.username.to_owned().as_str()),
+
+    let current_item = HashMap::from(&current_user);
+    items.push((
+        put_if_not_exists(
+            table,
+            updated_history_item(
+                user_id,
+                &context.principal_id,
+                HistoryOperation::Update,
+                Some(&current_item),
+                &expression,
+                &current_date,
+            ),
+        ),
+        None,
+    ));
+
+    let mut updated_item = current_item.clone();
+    expression.apply(&mut updated_item);
+    if let Some(item) = outbox_item(context, Some(&current_item), Some(&updated_item), &now)? {
+        items.push((put_if_not_exists(table, item), None));
+    }
+
+    items.insert(
+        0,
+        (
+            update_item(table, &key, &expression),
+            Some(error::Error::PreconditionError("Version mismatch")),
+        ),
     );
 
-    if let Some(first_name) = input.first_name {
-        insert_map.insert(
-            "FirstName".to_owned(),
-            AttributeValue::S(first_name.to_owned()),
-        );
+    transact_write_items(client, items)
+        .await
+        .map_err(|err| stale_read_error(err, expected_version))?;
+
+    Ok(true)
+}
+
+/// Error reported when the version condition on an updated or deleted user fails.
+/// Without If-Match the write is conditioned on the version it read, so a mismatch
+/// means another write landed in between. That is reported as a transaction conflict,
+/// which is retried with a fresh read.
+pub fn stale_read_error(
+    err: Box<dyn std::error::Error>,
+    expected_version: Option<u64>,
+) -> Box<dyn std::error::Error> {
+    match (expected_version, err.downcast_ref::<error::Error>()) {
+        (None, Some(error::Error::PreconditionError(_))) => Box::new(
+            RepositoryError::TransactionConflict("User changed since it was read".to_owned()),
+        ),
+        _ => err,
     }
-    if let Some(last_name) = input.last_name {
-        insert_map.insert(
-            "LastName".to_owned(),
-            AttributeValue::S(last_name.to_owned()),
-        );
+}
+
+/// Builds the update applied to an existing user. PUT replaces the whole profile, so
+/// optional fields the caller omits are removed from the item.
+pub fn update_user_expression(current_date: &str, input: UpdateUserArgs) -> UpdateExpression {
+    let mut item = HashMap::from(&input);
+
+    UpdateUserArgs::ATTRIBUTES
+        .iter()
+        .fold(UpdateExpression::new(), |expression, attribute| {
+            expression.set_or_remove(attribute, item.remove(*attribute))
+        })
+        .set("UpdatedDate", AttributeValue::S(current_date.to_owned()))
+        .set("GSI1PK", AttributeValue::S(email_key(&input.email)))
+        .set(
+            "GSI1SK",
+            AttributeValue::S(String::from("USERNAME#") + input.username.as_str()),
+        )
+        .set(
+            "GSI3PK",
+            AttributeValue::S(username_index_partition(&input.username)),
+        )
+        .set(
+            "GSI3SK",
+            AttributeValue::S(username_index_sort_key(&input.username)),
+        )
+        .add("Version", AttributeValue::N("1".to_owned()))
+}
+
+/// Soft deletes the user. The user item is marked deleted and, like its uniqueness
+/// guards, given an `ExpiresAt` TTL so DynamoDB purges it once the retention period
+/// ends. Until then the email and username stay reserved for a restore.
+pub async fn delete_user(
+    client: &Client,
+    table: &str,
+    user_id: &str,
+    expected_version: Option<u64>,
+    context: &ChangeContext,
+) -> Result<bool, Box<dyn std::error::Error>> {
+    let config = Config::from_env();
+    let now = timestamp::now();
+    let current_date = timestamp::format(&now);
+    let expires_at = now.timestamp() + config.deleted_user_retention_days * 24 * 60 * 60;
+    let current_user = match get_user_by_id(client, table, user_id).await? {
+        Some(user) if user.deleted_at.is_none() => user,
+        _ => {
+            return Err(Box::new(condition_error(
+                expected_version,
+                "User does not exist or is already deleted",
+            )))
+        }
+    };
+
+    let key = String::from("USER#") + user_id;
+    // Without If-Match, the guards, history and event below are only valid for the
+    // version they were read at
+    let expression = version_condition(
+        soft_delete_user_expression(&current_date, expires_at),
+        expected_version.unwrap_or(current_user.version),
+    );
+
+    let mut items = vec![(
+        update_item(table, &key, &expression),
+        Some(error::Error::PreconditionError("Version mismatch")),
+    )];
+
+    for guard in [
+        email_guard_key(&current_user.email),
+        username_guard_key(&current_user.username),
+    ] {
+        let expression = guard_expiry_expression(user_id, Some(expires_at));
+        items.push((
+            update_item(table, &guard, &expression),
+            Some(error::Error::PreconditionError("Version mismatch")),
+        ));
     }
-    if let Some(profile_photo) = input.profile_photo {
-        insert_map.insert(
-            "ProfilePhoto".to_owned(),
-            AttributeValue::S(profile_photo.to_owned()),
-        );
+
+    let current_item = HashMap::from(&current_user);
+    let history_item = updated_history_item(
+        user_id,
+        &context.principal_id,
+        HistoryOperation::Delete,
+        Some(&current_item),
+        &expression,
+        &current_date,
+    );
+    items.push((put_if_not_exists(table, history_item), None));
+
+    let mut updated_item = current_item.clone();
+    expression.apply(&mut updated_item);
+    if let Some(item) = outbox_item(context, Some(&current_item), Some(&updated_item), &now)? {
+        items.push((put_if_not_exists(table, item), None));
     }
-    if let Some(summary) = input.summary {
-        insert_map.insert("Summary".to_owned(), AttributeValue::S(summary.to_owned()));
+
+    transact_write_items(client, items)
+        .await
+        .map_err(|err| stale_read_error(err, expected_version))?;
+
+    Ok(true)
+}
+
+/// Clears the deletion markers of a user whose retention period has not ended, and
+/// removes the TTL from its uniqueness guards.
+pub async fn restore_user(
+    client: &Client,
+    table: &str,
+    user_id: &str,
+    expected_version: Option<u64>,
+    context: &ChangeContext,
+) -> Result<bool, Box<dyn std::error::Error>> {
+    let now = timestamp::now();
+
+    let key = String::from("USER#") + user_id;
+    let mut expression = restore_user_expression(&timestamp::format(&now), now.timestamp());
+    if let Some(version) = expected_version {
+        expression = version_condition(expression, version);
     }
-    if let Some(phone_number) = input.phone_number {
-        insert_map.insert(
-            "PhoneNumber".to_owned(),
-            AttributeValue::S(phone_number.to_owned()),
+
+    let error = || {
+        condition_error(
+            expected_version,
+            "User is not deleted or can no longer be restored",
+        )
+    };
+    let mut items = vec![(update_item(table, &key, &expression), Some(error()))];
+
+    if let Some(user) = get_user_by_id(client, table, user_id).await? {
+        for guard in [
+            email_guard_key(&user.email),
+            username_guard_key(&user.username),
+        ] {
+            let expression = guard_expiry_expression(user_id, None);
+            items.push((update_item(table, &guard, &expression), Some(error())));
+        }
+
+        let current_item = HashMap::from(&user);
+        let history_item = updated_history_item(
+            user_id,
+            &context.principal_id,
+            HistoryOperation::Restore,
+            Some(&current_item),
+            &expression,
+            &timestamp::format(&now),
         );
+        items.push((put_if_not_exists(table, history_item), None));
+
+        let mut updated_item = current_item.clone();
+        expression.apply(&mut updated_item);
+        if let Some(item) = outbox_item(context, Some(&current_item), Some(&updated_item), &now)? {
+            items.push((put_if_not_exists(table, item), None));
+        }
+    }
+
+    transact_write_items(client, items).await?;
+
+    Ok(true)
+}
+
+/// Marks a user deleted, to be purged by the table TTL at `expires_at`.
+pub fn soft_delete_user_expression(current_date: &str, expires_at: i64) -> UpdateExpression {
+    UpdateExpression::new()
+        .set(
+            "Status",
+            AttributeValue::S(UserStatus::Deleted.value().to_owned()),
+        )
+        .set("DeletedAt", AttributeValue::S(current_date.to_owned()))
+        .set("ExpiresAt", AttributeValue::N(expires_at.to_string()))
+        .set("UpdatedDate", AttributeValue::S(current_date.to_owned()))
+        .add("Version", AttributeValue::N("1".to_owned()))
+        .condition_attribute_not_exists("DeletedAt")
+}
+
+/// Reactivates a deleted user, provided the TTL has not passed. Expired items can
+/// linger until DynamoDB gets round to purging them, so `now` is checked explicitly.
+pub fn restore_user_expression(current_date: &str, now: i64) -> UpdateExpression {
+    UpdateExpression::new()
+        .set(
+            "Status",
+            AttributeValue::S(UserStatus::Active.value().to_owned()),
+        )
+        .set("UpdatedDate", AttributeValue::S(current_date.to_owned()))
+        .remove("DeletedAt")
+        .remove("ExpiresAt")
+        .add("Version", AttributeValue::N("1".to_owned()))
+        .condition_attribute_exists("DeletedAt")
+        .condition_greater_than("ExpiresAt", AttributeValue::N(now.to_string()))
+}
+
+/// Sets or clears the TTL of a uniqueness guard, provided no other user holds it.
+/// `UserId` is written as well, so users created before guards existed get a complete
+/// guard item.
+pub fn guard_expiry_expression(user_id: &str, expires_at: Option<i64>) -> UpdateExpression {
+    UpdateExpression::new()
+        .set("UserId", AttributeValue::S(user_id.to_owned()))
+        .set_or_remove(
+            "ExpiresAt",
+            expires_at.map(|expires_at| AttributeValue::N(expires_at.to_string())),
+        )
+        .condition_missing_or_equals("UserId", AttributeValue::S(user_id.to_owned()))
+}
+
+/// Error reported when the condition on a user item fails: a version mismatch when
+/// the caller sent one, otherwise the given reason.
+pub fn condition_error(expected_version: Option<u64>, reason: &'static str) -> error::Error {
+    match expected_version {
+        Some(_) => error::Error::PreconditionError("Version mismatch"),
+        None => error::Error::ClientError(reason),
     }
+}
+
+/// Hands out outbox entry IDs that increase even within a millisecond, so the relay
+/// publishes changes made by one process in the order they were made.
+static OUTBOX_IDS: Mutex<Option<Generator>> = Mutex::new(None);
+
+fn next_outbox_id() -> String {
+    let mut generator = OUTBOX_IDS.lock().unwrap();
 
+    generator
+        .get_or_insert_with(Generator::new)
+        .generate()
+        .unwrap_or_else(|_| Ulid::new())
+        .to_string()
+}
+
+/// Builds the outbox entry announcing a change of a user item from `before` to
+/// `after`, or `None` when the change is not worth an event. It must be written in
+/// the same transaction as the change.
+pub fn outbox_item(
+    context: &ChangeContext,
+    before: Option<&HashMap<String, AttributeValue>>,
+    after: Option<&HashMap<String, AttributeValue>>,
+    now: &DateTime<Utc>,
+) -> Result<Option<HashMap<String, AttributeValue>>, Box<dyn std::error::Error>> {
+    let before = before.cloned().map(User::try_from).transpose()?;
+    let after = after.cloned().map(User::try_from).transpose()?;
+    let Some(event) = UserEvent::from_change(before, after) else {
+        return Ok(None);
+    };
+
+    let entry_id = next_outbox_id();
+    let event = CloudEvent::new(&entry_id, &Config::from_env().event_source, *now, &event)?
+        .with_context(context);
+    let entry = OutboxEntry {
+        entry_id,
+        event,
+        created_date: *now,
+        attempts: 0,
+    };
+
+    Ok(Some(HashMap::from(&entry)))
+}
+
+/// Reads up to `limit` outbox entries, oldest first, starting after the entry
+/// `after` when given.
+pub async fn list_outbox_entries(
+    client: &Client,
+    table: &str,
+    limit: i32,
+    after: Option<&str>,
+) -> Result<Vec<OutboxEntry>, Box<dyn std::error::Error>> {
     let request = client
-        .put_item()
+        .query()
         .table_name(table)
-        .set_item(Some(insert_map));
+        .expression_attribute_names("#PK", "PK")
+        .expression_attribute_values(":Outbox", AttributeValue::S(OUTBOX_PARTITION.to_owned()));
+    let request = match after {
+        Some(after) => request
+            .key_condition_expression("#PK = :Outbox AND #SK > :After")
+            .expression_attribute_names("#SK", "SK")
+            .expression_attribute_values(
+                ":After",
+                AttributeValue::S(String::from("OUTBOX#") + after),
+            ),
+        None => request.key_condition_expression("#PK = :Outbox"),
+    };
 
-    let _resp = request.send().await?;
+    let resp = request
+        .consistent_read(true)
+        .limit(limit)
+        .send()
+        .await
+        .map_err(RepositoryError::from)?;
 
-    Ok(user_id)
+    let mut entries: Vec<OutboxEntry> = Vec::new();
+    for item in resp.items.unwrap_or_default() {
+        entries.push(OutboxEntry::try_from(item)?);
+    }
+
+    Ok(entries)
 }
 
-pub async fn update_user(
+pub async fn delete_outbox_entry(
     client: &Client,
     table: &str,
-    user_id: &str,
-    input: UpdateUserArgs,
-) -> Result<bool, Box<dyn std::error::Error>> {
-    let current_date = chrono::offset::Utc::now().to_string();
+    entry_id: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    client
+        .delete_item()
+        .table_name(table)
+        .key("PK", AttributeValue::S(OUTBOX_PARTITION.to_owned()))
+        .key("SK", AttributeValue::S(String::from("OUTBOX#") + entry_id))
+        .send()
+        .await
+        .map_err(RepositoryError::from)?;
 
-    let pk: AttributeValue = AttributeValue::S(String::from("USER#") + user_id);
-    let sk: AttributeValue = AttributeValue::S(String::from("USER#") + user_id);
+    Ok(())
+}
 
-    let mut update_map: HashMap<String, AttributeValueUpdate> = HashMap::new();
+/// Counts a failed attempt to publish an outbox entry. An entry that another relay
+/// has published or dead-lettered in the meantime is left alone.
+pub async fn record_outbox_attempt(
+    client: &Client,
+    table: &str,
+    entry_id: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let expression = UpdateExpression::new()
+        .add("Attempts", AttributeValue::N("1".to_owned()))
+        .condition_attribute_exists("PK");
 
-    update_map.insert(
-        "Username".to_owned(),
-        AttributeValueUpdate::builder()
-            .action(AttributeAction::Put)
-            .value(AttributeValue::S(input.username.to_owned()))
-            .build(),
-    );
-    update_map.insert(
-        "FirstName".to_owned(),
-        match input.first_name {
-            Some(first_name) => AttributeValueUpdate::builder()
-                .action(AttributeAction::Put)
-                .value(AttributeValue::S(first_name))
-                .build(),
-            None => AttributeValueUpdate::builder()
-                .action(AttributeAction::Delete)
-                .build(),
-        },
-    );
-    update_map.insert(
-        "LastName".to_owned(),
-        match input.last_name {
-            Some(last_name) => AttributeValueUpdate::builder()
-                .action(AttributeAction::Put)
-                .value(AttributeValue::S(last_name))
-                .build(),
-            None => AttributeValueUpdate::builder()
-                .action(AttributeAction::Delete)
-                .build(),
-        },
-    );
-    update_map.insert(
-        "Email".to_owned(),
-        AttributeValueUpdate::builder()
-            .action(AttributeAction::Put)
-            .value(AttributeValue::S(input.email.to_owned()))
-            .build(),
-    );
-    update_map.insert(
-        "ProfilePhoto".to_owned(),
-        match input.profile_photo {
-            Some(profile_photo) => AttributeValueUpdate::builder()
-                .action(AttributeAction::Put)
-                .value(AttributeValue::S(profile_photo))
-                .build(),
-            None => AttributeValueUpdate::builder()
-                .action(AttributeAction::Delete)
-                .build(),
-        },
-    );
-    update_map.insert(
-        "Summary".to_owned(),
-        match input.summary {
-            Some(summary) => AttributeValueUpdate::builder()
-                .action(AttributeAction::Put)
-                .value(AttributeValue::S(summary))
-                .build(),
-            None => AttributeValueUpdate::builder()
-                .action(AttributeAction::Delete)
-                .build(),
-        },
-    );
-    update_map.insert(
-        "PhoneNumber".to_owned(),
-        match input.phone_number {
-            Some(phone_number) => AttributeValueUpdate::builder()
-                .action(AttributeAction::Put)
-                .value(AttributeValue::S(phone_number))
-                .build(),
-            None => AttributeValueUpdate::builder()
-                .action(AttributeAction::Delete)
-                .build(),
-        },
-    );
-    update_map.insert(
-        "UpdatedDate".to_owned(),
-        AttributeValueUpdate::builder()
-            .action(AttributeAction::Put)
-            .value(AttributeValue::S(current_date.clone()))
-            .build(),
-    );
-    update_map.insert(
-        "GSI1PK".to_owned(),
-        AttributeValueUpdate::builder()
-            .action(AttributeAction::Put)
-            .value(AttributeValue::S(
-                String::from("EMAIL#") + input.email.to_owned().as_str(),
-            ))
-            .build(),
+    let result = client
+        .update_item()
+        .table_name(table)
+        .key("PK", AttributeValue::S(OUTBOX_PARTITION.to_owned()))
+        .key("SK", AttributeValue::S(String::from("OUTBOX#") + entry_id))
+        .update_expression(expression.update_expression())
+        .set_condition_expression(expression.condition_expression())
+        .set_expression_attribute_names(expression.names())
+        .set_expression_attribute_values(expression.values())
+        .send()
+        .await
+        .map_err(RepositoryError::from);
+
+    match result {
+        Ok(_) | Err(RepositoryError::ConditionFailed(_)) => Ok(()),
+        Err(err) => Err(Box::new(err)),
+    }
+}
+
+/// Moves an outbox entry the relay gave up on to the `OUTBOX_DEAD_LETTER` partition,
+/// where it is kept for inspection but no longer relayed.
+pub async fn dead_letter_outbox_entry(
+    client: &Client,
+    table: &str,
+    entry: &OutboxEntry,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let sort_key = String::from("OUTBOX#") + &entry.entry_id;
+    let mut item = HashMap::from(entry);
+    item.insert(
+        "PK".to_owned(),
+        AttributeValue::S(OUTBOX_DEAD_LETTER_PARTITION.to_owned()),
     );
-    update_map.insert(
-        "GSI1SK".to_owned(),
-        AttributeValueUpdate::builder()
-            .action(AttributeAction::Put)
-            .value(AttributeValue::S(
-                String::from("USERNAME#") + input.username.to_owned().as_str(),
-            ))
-            .build(),
+
+    transact_write_items(
+        client,
+        vec![
+            (put_if_not_exists(table, item), None),
+            (delete_item(table, OUTBOX_PARTITION, &sort_key), None),
+        ],
+    )
+    .await
+}
+
+/// Builds the history item recording a mutation of the user item from `before` to
+/// `after`, attributed to `actor`.
+pub fn create_history_item(
+    user_id: &str,
+    actor: &str,
+    operation: HistoryOperation,
+    before: Option<&HashMap<String, AttributeValue>>,
+    after: &HashMap<String, AttributeValue>,
+    current_date: &str,
+) -> HashMap<String, AttributeValue> {
+    let history = UserHistory::new(
+        user_id,
+        actor,
+        operation,
+        UserHistory::diff(before, after),
+        current_date,
     );
 
-    let request = client
-        .update_item()
-        .key("PK", pk)
-        .key("SK", sk)
+    HashMap::from(&history)
+}
+
+/// Builds the history item for an update of the user item as last read. The diff is
+/// only exact when the update is also conditioned on the version that was read.
+pub fn updated_history_item(
+    user_id: &str,
+    actor: &str,
+    operation: HistoryOperation,
+    before: Option<&HashMap<String, AttributeValue>>,
+    expression: &UpdateExpression,
+    current_date: &str,
+) -> HashMap<String, AttributeValue> {
+    let mut after = before.cloned().unwrap_or_default();
+    expression.apply(&mut after);
+
+    create_history_item(user_id, actor, operation, before, &after, current_date)
+}
+
+/// Key of a user's email in GSI1 and in its uniqueness guard, built from the
+/// normalized address so every spelling of it finds the same user.
+pub fn email_key(email: &str) -> String {
+    String::from("EMAIL#") + normalize_email(email, &Config::from_env()).as_str()
+}
+
+pub fn email_guard_key(email: &str) -> String {
+    email_key(email)
+}
+
+pub fn username_guard_key(username: &str) -> String {
+    String::from("USERNAME#") + username
+}
+
+pub fn guard_key(field: UniqueField, value: &str) -> String {
+    match field {
+        UniqueField::Email => email_guard_key(value),
+        UniqueField::Username => username_guard_key(value),
+    }
+}
+
+/// Builds the sentinel item that reserves a unique email or username for a user.
+pub fn create_guard_item(guard_key: &str, user_id: &str) -> HashMap<String, AttributeValue> {
+    let mut guard_map: HashMap<String, AttributeValue> = HashMap::new();
+
+    guard_map.insert("PK".to_owned(), AttributeValue::S(guard_key.to_owned()));
+    guard_map.insert("SK".to_owned(), AttributeValue::S(guard_key.to_owned()));
+    guard_map.insert("UserId".to_owned(), AttributeValue::S(user_id.to_owned()));
+
+    guard_map
+}
+
+fn put_if_not_exists(table: &str, item: HashMap<String, AttributeValue>) -> TransactWriteItem {
+    let put = Put::builder()
+        .table_name(table)
+        .set_item(Some(item))
+        .condition_expression("attribute_not_exists(PK)")
+        .build();
+
+    TransactWriteItem::builder().put(put).build()
+}
+
+fn update_item(table: &str, key: &str, expression: &UpdateExpression) -> TransactWriteItem {
+    let update = Update::builder()
+        .table_name(table)
+        .key("PK", AttributeValue::S(key.to_owned()))
+        .key("SK", AttributeValue::S(key.to_owned()))
+        .update_expression(expression.update_expression())
+        .set_condition_expression(expression.condition_expression())
+        .set_expression_attribute_names(expression.names())
+        .set_expression_attribute_values(expression.values())
+        .build();
+
+    TransactWriteItem::builder().update(update).build()
+}
+
+fn delete_item(table: &str, pk: &str, sk: &str) -> TransactWriteItem {
+    let delete = Delete::builder()
         .table_name(table)
-        .set_attribute_updates(Some(update_map));
+        .key("PK", AttributeValue::S(pk.to_owned()))
+        .key("SK", AttributeValue::S(sk.to_owned()))
+        .build();
+
+    TransactWriteItem::builder().delete(delete).build()
+}
 
-    let _resp = request.send().await?;
+/// Whether an item is a user, rather than one of the history, guard or bookkeeping
+/// items sharing the table.
+pub fn is_user_item(item: &HashMap<String, AttributeValue>) -> bool {
+    let pk = item.get_s("PK");
 
-    return Ok(true);
+    pk.starts_with("USER#") && item.get_s("SK") == pk
 }
 
-pub async fn delete_user(
+/// Sort key of a user within the listing index, ordering users by creation date.
+pub fn list_index_sort_key(created_date: &DateTime<Utc>, user_id: &str) -> String {
+    format!("{}#{}", timestamp::format(created_date), user_id)
+}
+
+/// Partition of a user within the username index. Usernames are sharded by their
+/// lowercased first character, so a prefix search reads exactly one partition.
+pub fn username_index_partition(username: &str) -> String {
+    let shard: String = username
+        .chars()
+        .take(1)
+        .flat_map(char::to_lowercase)
+        .collect();
+
+    String::from("USERNAME#") + shard.as_str()
+}
+
+/// Sort key of a user within the username index, so `begins_with` matches prefixes
+/// regardless of case.
+pub fn username_index_sort_key(username: &str) -> String {
+    username.to_lowercase()
+}
+
+/// Sets the listing index keys, so users written before GSI2 existed are picked up by
+/// `list_users` on their next update.
+pub fn with_list_index_keys(
+    expression: UpdateExpression,
+    user_id: &str,
+    created_date: &DateTime<Utc>,
+) -> UpdateExpression {
+    expression
+        .set(
+            "GSI2PK",
+            AttributeValue::S(GSI_2_USERS_PARTITION.to_owned()),
+        )
+        .set(
+            "GSI2SK",
+            AttributeValue::S(list_index_sort_key(created_date, user_id)),
+        )
+}
+
+/// Adds the condition that the user item exists and is still at `version`. Items
+/// written before versioning was introduced have no `Version` and match version 0.
+pub fn version_condition(expression: UpdateExpression, version: u64) -> UpdateExpression {
+    let expression = expression.condition_attribute_exists("PK");
+
+    match version {
+        0 => expression.condition_attribute_not_exists("Version"),
+        version => expression.condition_equals("Version", AttributeValue::N(version.to_string())),
+    }
+}
+
+/// Sends the items as a single transaction. Each item may be paired with the error
+/// reported when its condition fails, such as a `ConflictError` for a uniqueness
+/// guard or a `PreconditionError` for a stale version. Any other cancellation is
+/// classified by the reason of the item that caused it.
+async fn transact_write_items(
+    client: &Client,
+    items: Vec<(TransactWriteItem, Option<error::Error>)>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let (items, failures): (Vec<TransactWriteItem>, Vec<Option<error::Error>>) =
+        items.into_iter().unzip();
+
+    let request = client
+        .transact_write_items()
+        .set_transact_items(Some(items));
+
+    match request.send().await {
+        Ok(_resp) => Ok(()),
+        Err(err) => {
+            let canceled = match &err {
+                SdkError::ServiceError(service_error) => match service_error.err() {
+                    TransactWriteItemsError::TransactionCanceledException(canceled) => {
+                        Some(canceled)
+                    }
+                    _ => None,
+                },
+                _ => None,
+            };
+
+            if let Some(canceled) = canceled {
+                let reasons = canceled.cancellation_reasons().unwrap_or_default();
+
+                for (reason, failure) in reasons.iter().zip(failures) {
+                    if let (Some("ConditionalCheckFailed"), Some(failure)) =
+                        (reason.code(), failure)
+                    {
+                        return Err(Box::new(failure));
+                    }
+                }
+
+                // Items that did not cause the cancellation report the code "None"
+                if let Some(reason) = reasons
+                    .iter()
+                    .find(|reason| reason.code().is_some_and(|code| code != "None"))
+                {
+                    return Err(Box::new(RepositoryError::from_code(
+                        reason.code().unwrap_or_default(),
+                        reason.message().unwrap_or_default().to_owned(),
+                    )));
+                }
+            }
+
+            Err(Box::new(RepositoryError::from(err)))
+        }
+    }
+}
+
+/// Queries one page of user items in creation order through GSI2, where every user
+/// shares the `USERS` partition, or through the GSI3 username index when searching by
+/// `username_prefix`. Only the attributes `fields` projects are read, if given.
+async fn query_users(
     client: &Client,
     table: &str,
-    user_id: &str,
-) -> Result<bool, Box<dyn std::error::Error>> {
-    let pk: AttributeValue = AttributeValue::S(String::from("USER#") + user_id);
-    let sk: AttributeValue = AttributeValue::S(String::from("USER#") + user_id);
+    args: ListUsersArgs,
+    fields: Option<&Fieldset>,
+) -> Result<(Vec<Item>, Option<String>), Box<dyn std::error::Error>> {
+    let start_key = decode_start_key(args.pagination_token.as_deref())?;
 
     let request = client
-        .delete_item()
-        .key("PK", pk)
-        .key("SK", sk)
-        .table_name(table);
+        .query()
+        .table_name(table)
+        .limit(args.limit)
+        .scan_index_forward(args.order == SortOrder::Ascending);
 
-    let _resp = request.send().await?;
+    let mut request = match args.username_prefix.as_deref() {
+        Some(prefix) => {
+            let partition = username_index_partition(prefix);
+            let start_key = index_start_key(start_key, "GSI3PK", &partition)?;
 
-    return Ok(true);
+            request
+                .index_name(GSI_3_INDEX)
+                .set_exclusive_start_key(start_key)
+                .key_condition_expression("#GSI3PK = :GSI3PK AND begins_with(#GSI3SK, :Prefix)")
+                .expression_attribute_names("#GSI3PK", "GSI3PK")
+                .expression_attribute_names("#GSI3SK", "GSI3SK")
+                .expression_attribute_values(":GSI3PK", AttributeValue::S(partition))
+                .expression_attribute_values(
+                    ":Prefix",
+                    AttributeValue::S(username_index_sort_key(prefix)),
+                )
+        }
+        None => {
+            let start_key = index_start_key(start_key, "GSI2PK", GSI_2_USERS_PARTITION)?;
+
+            request
+                .index_name(GSI_2_INDEX)
+                .set_exclusive_start_key(start_key)
+                .key_condition_expression("#GSI2PK = :GSI2PK")
+                .expression_attribute_names("#GSI2PK", "GSI2PK")
+                .expression_attribute_values(
+                    ":GSI2PK",
+                    AttributeValue::S(GSI_2_USERS_PARTITION.to_owned()),
+                )
+        }
+    };
+
+    // Filtered after Limit is applied, so a page can come back short
+    if !args.include_deleted {
+        request = request
+            .filter_expression("attribute_not_exists(#DeletedAt)")
+            .expression_attribute_names("#DeletedAt", "DeletedAt");
+    }
+
+    if let Some(fields) = fields {
+        let (projection, names) = fields.projection();
+        request = request.projection_expression(projection);
+        for (placeholder, name) in names {
+            request = request.expression_attribute_names(placeholder, name);
+        }
+    }
+
+    let resp = request.send().await.map_err(RepositoryError::from)?;
+    let token = encode_last_key(resp.last_evaluated_key)?;
+
+    Ok((resp.items.unwrap_or_default(), token))
 }
 
-// NOTE: WILL NOT WORK. No secondary indexes on users to list them
 pub async fn list_users(
     client: &Client,
     table: &str,
-    limit: &i32,
-    pagination_token: Option<&str>,
+    args: ListUsersArgs,
 ) -> Result<PaginatedResult<User>, Box<dyn std::error::Error>> {
-    let pk: AttributeValue = AttributeValue::S(String::from("USER#"));
-    let sk: AttributeValue = AttributeValue::S(String::from("USER#"));
+    let (items, token) = query_users(client, table, args, None).await?;
 
-    let mut expression_values: HashMap<String, AttributeValue> = HashMap::new();
-    expression_values.insert(":pk".to_string(), pk);
-    expression_values.insert(":sk".to_string(), sk);
+    let mut users: Vec<User> = Vec::new();
+    for item in items {
+        users.push(User::try_from(item)?);
+    }
+
+    Ok(PaginatedResult { data: users, token })
+}
+
+/// `list_users` reading only the attributes `fields` projects.
+pub async fn list_user_fields(
+    client: &Client,
+    table: &str,
+    args: ListUsersArgs,
+    fields: &Fieldset,
+) -> Result<PaginatedResult<PartialUser>, Box<dyn std::error::Error>> {
+    let (items, token) = query_users(client, table, args, Some(fields)).await?;
+
+    Ok(PaginatedResult {
+        data: items.iter().map(|item| fields.select_item(item)).collect(),
+        token,
+    })
+}
+
+/// Builds the update that rewrites a user item's timestamps in the current format, or
+/// `None` when they already are. It is conditional on the dates still holding their
+/// old values. Dates that cannot be parsed at all are left as they are.
+pub fn timestamp_backfill_expression(
+    item: &HashMap<String, AttributeValue>,
+) -> Option<UpdateExpression> {
+    let mut expression = UpdateExpression::new();
+
+    for attribute in ["CreatedDate", "UpdatedDate", "DeletedAt"] {
+        let Some(value) = item.get_opt_s(attribute) else {
+            continue;
+        };
+        let Some(formatted) = timestamp::parse(&value).map(|date| timestamp::format(&date)) else {
+            continue;
+        };
+
+        if formatted != value {
+            expression = expression
+                .set(attribute, AttributeValue::S(formatted))
+                .condition_equals(attribute, AttributeValue::S(value));
+        }
+    }
+
+    let created_date = timestamp::parse(&item.get_s("CreatedDate"));
+    if let Some(created_date) = created_date {
+        let sort_key = list_index_sort_key(&created_date, &item.get_s("UserId"));
+        if item.get_opt_s("GSI2SK") != Some(sort_key) {
+            expression = with_list_index_keys(expression, &item.get_s("UserId"), &created_date);
+        }
+    }
+
+    (!expression.is_empty()).then_some(expression)
+}
 
-    let start_key: Option<HashMap<String, AttributeValue>> = match pagination_token {
-        Some(token) => Some(PaginationToken::decode_token(token.to_owned().clone()).into()),
-        None => None,
+/// Moves every user onto the normalized form of its email key, re-keying both the
+/// GSI1 partition and the uniqueness guard, which keeps its `ExpiresAt`. Users whose
+/// emails normalize to the same key are reported as collisions and left as they are.
+///
+/// Collisions can only be found once every user has been seen, so the scan keeps
+/// each user's ID and email keys in memory before anything is written.
+pub async fn migrate_email_keys(
+    client: &Client,
+    table: &str,
+    dry_run: bool,
+) -> Result<EmailMigrationReport, Box<dyn std::error::Error>> {
+    let mut args = ScanUsersArgs {
+        include_deleted: true,
+        ..Default::default()
     };
+    let mut records: Vec<EmailRekey> = Vec::new();
 
-    let request = client
-        .query()
+    loop {
+        let page = scan_users(client, table, args.clone()).await?;
+
+        records.extend(page.data.into_iter().map(|user| EmailRekey {
+            new_key: email_key(&user.email),
+            user_id: user.user_id,
+            email: user.email,
+            current_key: user.gsi1pk,
+        }));
+
+        match page.token {
+            Some(token) => args.pagination_token = Some(token),
+            None => break,
+        }
+    }
+
+    let scanned = records.len();
+    let (rekeys, unchanged, collisions) = plan_email_rekeys(records);
+    let mut report = EmailMigrationReport {
+        dry_run,
+        scanned,
+        unchanged,
+        collisions,
+        ..Default::default()
+    };
+
+    if dry_run {
+        report.rekeyed = rekeys.len();
+        return Ok(report);
+    }
+
+    for rekey in rekeys {
+        match rekey_email(client, table, &rekey).await {
+            Ok(()) => report.rekeyed += 1,
+            Err(err) => report.failures.push(EmailMigrationFailure {
+                user_id: rekey.user_id,
+                reason: err.to_string(),
+            }),
+        }
+    }
+
+    Ok(report)
+}
+
+/// Points one user's GSI1 partition and email guard at its normalized key, in one
+/// transaction that fails if the user's email or the guards changed since the scan.
+async fn rekey_email(
+    client: &Client,
+    table: &str,
+    rekey: &EmailRekey,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let user_key = String::from("USER#") + rekey.user_id.as_str();
+    let expression = UpdateExpression::new()
+        .set("GSI1PK", AttributeValue::S(rekey.new_key.clone()))
+        .condition_equals("Email", AttributeValue::S(rekey.email.clone()));
+
+    let mut items = vec![(
+        update_item(table, &user_key, &expression),
+        Some(error::Error::PreconditionError(
+            "Email changed during migration",
+        )),
+    )];
+
+    let current_guard = client
+        .get_item()
+        .table_name(table)
+        .key("PK", AttributeValue::S(rekey.current_key.clone()))
+        .key("SK", AttributeValue::S(rekey.current_key.clone()))
+        .send()
+        .await
+        .map_err(RepositoryError::from)?
+        .item
+        .filter(|guard| guard.get_opt_s("UserId").as_deref() == Some(rekey.user_id.as_str()));
+
+    let mut new_guard = create_guard_item(&rekey.new_key, &rekey.user_id);
+    if let Some(current_guard) = current_guard {
+        if let Some(expires_at) = current_guard.get("ExpiresAt") {
+            new_guard.insert("ExpiresAt".to_owned(), expires_at.clone());
+        }
+
+        let delete = Delete::builder()
+            .table_name(table)
+            .key("PK", AttributeValue::S(rekey.current_key.clone()))
+            .key("SK", AttributeValue::S(rekey.current_key.clone()))
+            .condition_expression("UserId = :UserId")
+            .expression_attribute_values(":UserId", AttributeValue::S(rekey.user_id.clone()))
+            .build();
+        items.push((
+            TransactWriteItem::builder().delete(delete).build(),
+            Some(error::Error::PreconditionError(
+                "Email guard changed during migration",
+            )),
+        ));
+    }
+
+    // The normalized guard may already exist for this user, from an update made
+    // after normalization was introduced
+    let put = Put::builder()
+        .table_name(table)
+        .set_item(Some(new_guard))
+        .condition_expression("attribute_not_exists(PK) OR UserId = :UserId")
+        .expression_attribute_values(":UserId", AttributeValue::S(rekey.user_id.clone()))
+        .build();
+    items.push((
+        TransactWriteItem::builder().put(put).build(),
+        Some(error::Error::ConflictError(UniqueField::Email)),
+    ));
+
+    transact_write_items(client, items).await
+}
+
+/// Checks a decoded start key continues the index partition being queried, so a
+/// token from one listing cannot be replayed against another.
+pub fn index_start_key(
+    start_key: Option<HashMap<String, AttributeValue>>,
+    partition_key: &str,
+    partition: &str,
+) -> Result<Option<HashMap<String, AttributeValue>>, error::Error> {
+    match start_key {
+        Some(key) if key.get_opt_s(partition_key).as_deref() != Some(partition) => Err(
+            error::Error::InvalidToken("Pagination token does not match this query"),
+        ),
+        start_key => Ok(start_key),
+    }
+}
+
+/// Scans one segment of the table for user items, for exports that need every user
+/// rather than a page in listing order. Filters apply after each page is read, so
+/// pages can come back short or empty while a token remains.
+pub async fn scan_users(
+    client: &Client,
+    table: &str,
+    args: ScanUsersArgs,
+) -> Result<PaginatedResult<User>, Box<dyn std::error::Error>> {
+    let start_key = decode_start_key(args.pagination_token.as_deref())?;
+
+    let mut filters = vec!["begins_with(#PK, :User)", "begins_with(#SK, :User)"];
+    let mut request = client
+        .scan()
         .table_name(table)
-        .limit(limit.to_owned())
+        .segment(args.segment)
+        .total_segments(args.total_segments)
         .set_exclusive_start_key(start_key)
-        .set_key_condition_expression(Some("PK = :pk and begins_with(SK, :sk)".to_string()))
-        .set_expression_attribute_values(Some(expression_values));
+        .expression_attribute_names("#PK", "PK")
+        .expression_attribute_names("#SK", "SK")
+        .expression_attribute_values(":User", AttributeValue::S("USER#".to_owned()));
 
-    let resp = request.send().await?;
-    let items = resp.items;
+    if let Some(created_from) = args.created_from {
+        filters.push("#CreatedDate >= :CreatedFrom");
+        request = request
+            .expression_attribute_names("#CreatedDate", "CreatedDate")
+            .expression_attribute_values(":CreatedFrom", AttributeValue::S(created_from));
+    }
+    if let Some(created_to) = args.created_to {
+        filters.push("#CreatedDate < :CreatedTo");
+        request = request
+            .expression_attribute_names("#CreatedDate", "CreatedDate")
+            .expression_attribute_values(":CreatedTo", AttributeValue::S(created_to));
+    }
+    if !args.include_deleted {
+        filters.push("attribute_not_exists(#DeletedAt)");
+        request = request.expression_attribute_names("#DeletedAt", "DeletedAt");
+    }
 
-    if let Some(items) = items {
-        let mut users: Vec<User> = Vec::new();
-        let last_key: Option<HashMap<String, AttributeValue>> = resp.last_evaluated_key;
+    let resp = request
+        .filter_expression(filters.join(" AND "))
+        .send()
+        .await
+        .map_err(RepositoryError::from)?;
 
-        for item in items.iter() {
-            let user: User = User::try_from(item.clone())?;
-            users.push(user);
-        }
+    let mut users: Vec<User> = Vec::new();
+    for item in resp.items.unwrap_or_default() {
+        users.push(User::try_from(item)?);
+    }
 
-        let paginated_result: PaginatedResult<User> = PaginatedResult {
-            data: users,
-            token: match last_key {
-                Some(key) => match PaginationToken::try_from(key) {
-                    Ok(token) => Some(token.into()),
-                    Err(err) => return Err(Box::new(err)),
-                },
-                None => None,
-            },
-        };
+    let token = encode_last_key(resp.last_evaluated_key)?;
 
-        return Ok(paginated_result);
-    } else {
-        panic!("Error parsing returned users");
+    Ok(PaginatedResult { data: users, token })
+}
+
+/// Pages through the history items of a user, which sort chronologically by ULID.
+pub async fn list_user_history(
+    client: &Client,
+    table: &str,
+    user_id: &str,
+    args: ListUserHistoryArgs,
+) -> Result<PaginatedResult<UserHistory>, Box<dyn std::error::Error>> {
+    let start_key = decode_start_key(args.pagination_token.as_deref())?;
+
+    let resp = client
+        .query()
+        .table_name(table)
+        .limit(args.limit)
+        .scan_index_forward(args.order == SortOrder::Ascending)
+        .set_exclusive_start_key(start_key)
+        .key_condition_expression("#PK = :PK and begins_with(#SK, :SK)")
+        .expression_attribute_names("#PK", "PK")
+        .expression_attribute_names("#SK", "SK")
+        .expression_attribute_values(":PK", AttributeValue::S(String::from("USER#") + user_id))
+        .expression_attribute_values(":SK", AttributeValue::S("HISTORY#".to_owned()))
+        .send()
+        .await
+        .map_err(RepositoryError::from)?;
+
+    let mut history: Vec<UserHistory> = Vec::new();
+    for item in resp.items.unwrap_or_default() {
+        history.push(UserHistory::try_from(item)?);
     }
+
+    let token = encode_last_key(resp.last_evaluated_key)?;
+
+    Ok(PaginatedResult {
+        data: history,
+        token,
+    })
+}
+
+/// Verifies the pagination token of a previous page and returns the key to start
+/// after. A malformed, tampered or expired token is an `InvalidToken` error.
+pub fn decode_start_key(
+    pagination_token: Option<&str>,
+) -> Result<Option<HashMap<String, AttributeValue>>, error::Error> {
+    let config = Config::from_env();
+
+    pagination_token
+        .map(|token| {
+            PaginationToken::decode_token(token, config.pagination_token_secret.as_bytes())
+                .map(|token| HashMap::from(&token))
+        })
+        .transpose()
+}
+
+/// Signs the `LastEvaluatedKey` of a page as the token for the next one.
+pub fn encode_last_key(
+    last_key: Option<HashMap<String, AttributeValue>>,
+) -> Result<Option<String>, error::Error> {
+    let config = Config::from_env();
+
+    last_key
+        .map(|key| {
+            PaginationToken::new(key, config.pagination_token_ttl_seconds)
+                .encode_token(config.pagination_token_secret.as_bytes())
+        })
+        .transpose()
+}
+
+#[test]
+fn should_rewrite_only_legacy_timestamps() {
+    let mut item: HashMap<String, AttributeValue> = HashMap::from([
+        (
+            "UserId".to_string(),
+            AttributeValue::S("abc123".to_string()),
+        ),
+        (
+            "CreatedDate".to_string(),
+            AttributeValue::S("2023-07-01 12:00:00.123456789 UTC".to_string()),
+        ),
+        (
+            "UpdatedDate".to_string(),
+            AttributeValue::S("2023-07-02T08:30:00.000Z".to_string()),
+        ),
+        (
+            "GSI2SK".to_string(),
+            AttributeValue::S("2023-07-01 12:00:00.123456789 UTC#abc123".to_string()),
+        ),
+    ]);
+
+    let expression = timestamp_backfill_expression(&item).expect("Nothing to rewrite");
+    assert!(expression.matches(Some(&item)));
+    expression.apply(&mut item);
+
+    assert_eq!(item.get_s("CreatedDate"), "2023-07-01T12:00:00.123Z");
+    assert_eq!(item.get_s("UpdatedDate"), "2023-07-02T08:30:00.000Z");
+    assert_eq!(item.get_s("GSI2SK"), "2023-07-01T12:00:00.123Z#abc123");
+    assert!(!item.contains_key("DeletedAt"));
+
+    assert!(timestamp_backfill_expression(&item).is_none());
 }
 
 #[tokio::test]
+#[ignore = "requires AWS SSO credentials"]
 async fn should_get_user_by_id() {
     let client = create_client().await.expect("Error retrieivng client.");
     let table_name = "cf-user-dev-app-users";
@@ -401,18 +1511,21 @@ async fn should_get_user_by_id() {
 }
 
 #[tokio::test]
+#[ignore = "requires AWS SSO credentials"]
 async fn should_list_users() {
     // BELOW WORKS! TOKEN SENT BACK, PARSED TO STRING, SENT TO FUNCTION, AND CORRECTLY PAGINATED THROUGH THE WHILE LOOP :)))))
     let client = create_client().await.expect("Error retrieivng client.");
     let table_name = "cf-user-dev-app-users";
-    let limit = 1;
+    let args = ListUsersArgs {
+        limit: 1,
+        ..Default::default()
+    };
 
-    let response: PaginatedResult<User> = list_users(&client, table_name, &limit, None)
+    let response: PaginatedResult<User> = list_users(&client, table_name, args.clone())
         .await
         .expect("Unable to retrieve user by ID");
 
     let data = response;
-    let test = "";
 
     let mut token = data.token;
     while let Some(t) = token.to_owned() {
@@ -421,13 +1534,18 @@ async fn should_list_users() {
         //     Err(_err) => None,
         // };
 
-        let response: PaginatedResult<User> =
-            list_users(&client, table_name, &limit, Some(t.as_str()))
-                .await
-                .expect("Unable to retrieve user by ID");
+        let response: PaginatedResult<User> = list_users(
+            &client,
+            table_name,
+            ListUsersArgs {
+                pagination_token: Some(t),
+                ..args.clone()
+            },
+        )
+        .await
+        .expect("Unable to retrieve user by ID");
 
         let data = response;
-        let test = "";
 
         token = data.token;
     }
@@ -0,0 +1,167 @@
+use super::config::Config;
+use super::models::email_migration_report::EmailCollision;
+use std::collections::BTreeMap;
+
+/// Domains whose mailboxes ignore dots and `+tag` suffixes in the local part.
+static GMAIL_DOMAINS: [&str; 2] = ["gmail.com", "googlemail.com"];
+
+/// Reduces an email address to the form used in its identity keys, the `EMAIL#`
+/// GSI1 partition and uniqueness guard, so addresses that reach the same mailbox
+/// map to one user. The address as entered is still what gets stored in `Email`.
+///
+/// The address is trimmed and its domain lowercased. The local part is lowercased
+/// when `email_lowercase_local_part` is set, and Gmail addresses drop dots and
+/// `+tag` suffixes when `email_fold_gmail` is set.
+pub fn normalize_email(email: &str, config: &Config) -> String {
+    let email = email.trim();
+
+    let (local_part, domain) = match email.rsplit_once('@') {
+        Some((local_part, domain)) => (local_part, domain.to_lowercase()),
+        None => return email.to_lowercase(),
+    };
+
+    let mut local_part = match config.email_lowercase_local_part {
+        true => local_part.to_lowercase(),
+        false => local_part.to_owned(),
+    };
+    let mut domain = domain;
+
+    if config.email_fold_gmail && GMAIL_DOMAINS.contains(&domain.as_str()) {
+        if let Some((mailbox, _tag)) = local_part.split_once('+') {
+            local_part = mailbox.to_owned();
+        }
+        local_part = local_part.replace('.', "");
+        domain = GMAIL_DOMAINS[0].to_owned();
+    }
+
+    format!("{}@{}", local_part, domain)
+}
+
+/// A user's email key as stored, and the key its email normalizes to now.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EmailRekey {
+    pub user_id: String,
+    pub email: String,
+    pub current_key: String,
+    pub new_key: String,
+}
+
+/// Splits users into those to move onto their normalized key, the number already on
+/// it, and groups that collide on the same normalized key and must be left alone.
+pub fn plan_email_rekeys(
+    records: Vec<EmailRekey>,
+) -> (Vec<EmailRekey>, usize, Vec<EmailCollision>) {
+    let mut groups: BTreeMap<String, Vec<EmailRekey>> = BTreeMap::new();
+    for record in records {
+        groups
+            .entry(record.new_key.clone())
+            .or_default()
+            .push(record);
+    }
+
+    let mut rekeys: Vec<EmailRekey> = Vec::new();
+    let mut unchanged = 0;
+    let mut collisions: Vec<EmailCollision> = Vec::new();
+
+    for (email_key, mut group) in groups {
+        match group.len() {
+            1 => {
+                let record = group.remove(0);
+                match record.current_key == record.new_key {
+                    true => unchanged += 1,
+                    false => rekeys.push(record),
+                }
+            }
+            _ => collisions.push(EmailCollision {
+                email_key,
+                user_ids: group.iter().map(|record| record.user_id.clone()).collect(),
+                emails: group.into_iter().map(|record| record.email).collect(),
+            }),
+        }
+    }
+
+    (rekeys, unchanged, collisions)
+}
+
+#[cfg(test)]
+mod unit_tests {
+    use super::*;
+
+    #[test]
+    fn should_normalize_case_and_whitespace() {
+        let config = Config::default();
+
+        assert_eq!(
+            normalize_email("  Bob@Example.COM ", &config),
+            "bob@example.com"
+        );
+
+        let config = Config {
+            email_lowercase_local_part: false,
+            ..Default::default()
+        };
+        assert_eq!(
+            normalize_email("Bob@Example.COM", &config),
+            "Bob@example.com"
+        );
+    }
+
+    fn record(user_id: &str, email: &str, current_key: &str) -> EmailRekey {
+        EmailRekey {
+            user_id: user_id.to_string(),
+            email: email.to_string(),
+            current_key: current_key.to_string(),
+            new_key: String::from("EMAIL#") + normalize_email(email, &Config::default()).as_str(),
+        }
+    }
+
+    #[test]
+    fn should_plan_rekeys_and_report_collisions() {
+        let (rekeys, unchanged, collisions) = plan_email_rekeys(vec![
+            record("1", "Ann@Example.com", "EMAIL#Ann@Example.com"),
+            record("2", "bob@example.com", "EMAIL#bob@example.com"),
+            record("3", "Carl@Example.com", "EMAIL#Carl@Example.com"),
+            record("4", "carl@example.com", "EMAIL#carl@example.com"),
+        ]);
+
+        assert_eq!(
+            rekeys,
+            vec![record("1", "Ann@Example.com", "EMAIL#Ann@Example.com")]
+        );
+        assert_eq!(rekeys[0].new_key, "EMAIL#ann@example.com");
+        assert_eq!(unchanged, 1);
+        assert_eq!(
+            collisions,
+            vec![EmailCollision {
+                email_key: "EMAIL#carl@example.com".to_string(),
+                user_ids: vec!["3".to_string(), "4".to_string()],
+                emails: vec![
+                    "Carl@Example.com".to_string(),
+                    "carl@example.com".to_string()
+                ],
+            }]
+        );
+    }
+
+    #[test]
+    fn should_fold_gmail_addresses_only_when_enabled() {
+        let config = Config::default();
+        assert_eq!(
+            normalize_email("Bob.Smith+news@GoogleMail.com", &config),
+            "bob.smith+news@googlemail.com"
+        );
+
+        let config = Config {
+            email_fold_gmail: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            normalize_email("Bob.Smith+news@GoogleMail.com", &config),
+            "bobsmith@gmail.com"
+        );
+        assert_eq!(
+            normalize_email("bob.smith+news@example.com", &config),
+            "bob.smith+news@example.com"
+        );
+    }
+}
@@ -1,3 +1,4 @@
+use aws_sdk_dynamodb::error::{ProvideErrorMetadata, SdkError};
 use aws_sdk_dynamodb::types::AttributeValue;
 use std::error;
 use std::fmt;
@@ -8,6 +9,25 @@ pub enum Error {
     ClientError(&'static str),
     InternalError(&'static str),
     SdkError(String),
+    ConflictError(UniqueField),
+    PreconditionError(&'static str),
+    InvalidToken(&'static str),
+}
+
+/// User attributes that must be unique across the table.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum UniqueField {
+    Email,
+    Username,
+}
+
+impl fmt::Display for UniqueField {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match self {
+            UniqueField::Email => write!(f, "email address"),
+            UniqueField::Username => write!(f, "username"),
+        }
+    }
 }
 
 impl fmt::Display for Error {
@@ -17,12 +37,128 @@ impl fmt::Display for Error {
             Error::ClientError(msg) => write!(f, "ClientError: {}", msg),
             Error::InternalError(msg) => write!(f, "InternalError: {}", msg),
             Error::SdkError(err) => write!(f, "SdkError: {}", err),
+            Error::ConflictError(field) => write!(
+                f,
+                "ConflictError: User record exists with matching {}",
+                field
+            ),
+            Error::PreconditionError(msg) => write!(f, "PreconditionError: {}", msg),
+            Error::InvalidToken(msg) => write!(f, "InvalidToken: {}", msg),
         }
     }
 }
 
 impl error::Error for Error {}
 
+/// Class of a failed DynamoDB call. The class decides whether the call is retried and
+/// which status a handler answers with.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RepositoryError {
+    /// Throughput or request rate limits were exceeded. The request was not applied.
+    Throttled(String),
+    /// A write's condition did not hold, e.g. the item changed since it was read.
+    ConditionFailed(String),
+    /// A transaction collided with another write to the same items and was not applied.
+    TransactionConflict(String),
+    /// The table or index does not exist, usually a deployment problem.
+    ResourceNotFound(String),
+    /// No response arrived in time. The request may still have been applied.
+    Timeout(String),
+    /// DynamoDB could not be reached or failed internally. The request may still have
+    /// been applied.
+    Unavailable(String),
+    /// The request was rejected as invalid, e.g. an item over the size limit. Repeating
+    /// it cannot succeed.
+    Validation(String),
+    /// The request failed for any other reason.
+    Internal(String),
+}
+
+impl RepositoryError {
+    /// Whether repeating the call can succeed. Calls that may have been applied are
+    /// only repeated when `idempotent`, so a timed-out write is never made twice.
+    pub fn is_retryable(&self, idempotent: bool) -> bool {
+        match self {
+            RepositoryError::Throttled(_) | RepositoryError::TransactionConflict(_) => true,
+            RepositoryError::Timeout(_) | RepositoryError::Unavailable(_) => idempotent,
+            RepositoryError::ConditionFailed(_)
+            | RepositoryError::ResourceNotFound(_)
+            | RepositoryError::Validation(_)
+            | RepositoryError::Internal(_) => false,
+        }
+    }
+
+    /// Classifies a DynamoDB error code, as returned by a failed call or as the
+    /// cancellation reason of one item in a transaction.
+    pub fn from_code(code: &str, message: String) -> Self {
+        match code {
+            "ProvisionedThroughputExceededException"
+            | "ProvisionedThroughputExceeded"
+            | "ThrottlingException"
+            | "ThrottlingError"
+            | "RequestLimitExceeded" => RepositoryError::Throttled(message),
+            "ConditionalCheckFailedException" | "ConditionalCheckFailed" => {
+                RepositoryError::ConditionFailed(message)
+            }
+            "TransactionCanceledException"
+            | "TransactionConflictException"
+            | "TransactionConflict"
+            | "TransactionInProgressException" => RepositoryError::TransactionConflict(message),
+            "ResourceNotFoundException" => RepositoryError::ResourceNotFound(message),
+            "ValidationException" | "ValidationError" => RepositoryError::Validation(message),
+            "InternalServerError" | "ServiceUnavailable" => RepositoryError::Unavailable(message),
+            _ => RepositoryError::Internal(message),
+        }
+    }
+}
+
+impl fmt::Display for RepositoryError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match self {
+            RepositoryError::Throttled(msg) => write!(f, "Throttled: {}", msg),
+            RepositoryError::ConditionFailed(msg) => write!(f, "ConditionFailed: {}", msg),
+            RepositoryError::TransactionConflict(msg) => {
+                write!(f, "TransactionConflict: {}", msg)
+            }
+            RepositoryError::ResourceNotFound(msg) => write!(f, "ResourceNotFound: {}", msg),
+            RepositoryError::Timeout(msg) => write!(f, "Timeout: {}", msg),
+            RepositoryError::Unavailable(msg) => write!(f, "Unavailable: {}", msg),
+            RepositoryError::Validation(msg) => write!(f, "Validation: {}", msg),
+            RepositoryError::Internal(msg) => write!(f, "Internal: {}", msg),
+        }
+    }
+}
+
+impl error::Error for RepositoryError {}
+
+impl<E, R> From<SdkError<E, R>> for RepositoryError
+where
+    E: ProvideErrorMetadata + error::Error + 'static,
+    R: fmt::Debug,
+{
+    fn from(err: SdkError<E, R>) -> RepositoryError {
+        match &err {
+            SdkError::ServiceError(service_error) => {
+                let service_error = service_error.err();
+                let message = service_error
+                    .message()
+                    .map(str::to_owned)
+                    .unwrap_or_else(|| service_error.to_string());
+
+                RepositoryError::from_code(service_error.code().unwrap_or_default(), message)
+            }
+            SdkError::TimeoutError(_) => RepositoryError::Timeout(err.to_string()),
+            SdkError::DispatchFailure(failure) if failure.is_timeout() => {
+                RepositoryError::Timeout(err.to_string())
+            }
+            SdkError::DispatchFailure(_) | SdkError::ResponseError(_) => {
+                RepositoryError::Unavailable(err.to_string())
+            }
+            _ => RepositoryError::Internal(err.to_string()),
+        }
+    }
+}
+
 impl From<std::num::ParseFloatError> for Error {
     fn from(_: std::num::ParseFloatError) -> Error {
         Error::InternalError("Unable to parse float")
@@ -33,4 +169,54 @@ impl From<&AttributeValue> for Error {
     fn from(_: &AttributeValue) -> Error {
         Error::InternalError("Invalid value type")
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod unit_tests {
+    use super::*;
+
+    #[test]
+    fn should_classify_error_codes() {
+        for (code, expected) in [
+            (
+                "ProvisionedThroughputExceededException",
+                RepositoryError::Throttled("message".into()),
+            ),
+            (
+                "ConditionalCheckFailed",
+                RepositoryError::ConditionFailed("message".into()),
+            ),
+            (
+                "TransactionConflict",
+                RepositoryError::TransactionConflict("message".into()),
+            ),
+            (
+                "ResourceNotFoundException",
+                RepositoryError::ResourceNotFound("message".into()),
+            ),
+            (
+                "ValidationException",
+                RepositoryError::Validation("message".into()),
+            ),
+            (
+                "ValidationError",
+                RepositoryError::Validation("message".into()),
+            ),
+            (
+                "ServiceUnavailable",
+                RepositoryError::Unavailable("message".into()),
+            ),
+            ("SomethingElse", RepositoryError::Internal("message".into())),
+        ] {
+            assert_eq!(RepositoryError::from_code(code, "message".into()), expected);
+        }
+    }
+
+    #[test]
+    fn should_never_retry_validation_errors() {
+        let err = RepositoryError::Validation("Item size has exceeded the maximum".into());
+
+        assert!(!err.is_retryable(true));
+        assert!(!err.is_retryable(false));
+    }
+}
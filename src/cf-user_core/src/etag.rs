@@ -0,0 +1,98 @@
+use super::models::problem::Problem;
+use lambda_http::http::{header::IF_MATCH, StatusCode};
+use lambda_http::Request;
+
+/// Formats a user version as a strong entity tag.
+pub fn format_etag(version: u64) -> String {
+    format!("\"{}\"", version)
+}
+
+/// Reads the expected version from the `If-Match` header. A missing header or `*`
+/// yields `None`, unless `required` is set, in which case a missing header is
+/// rejected with 428. If-Match uses the strong comparison (RFC 9110 §13.1.1), so a
+/// weak tag, like a tag that is not a version, can never match and is rejected with
+/// 412.
+pub fn parse_if_match(event: &Request, required: bool) -> Result<Option<u64>, Box<Problem>> {
+    let header = match event.headers().get(IF_MATCH) {
+        Some(header) => header,
+        None if required => {
+            return Err(Box::new(
+                Problem::new(StatusCode::PRECONDITION_REQUIRED)
+                    .with_detail("If-Match header is required"),
+            ))
+        }
+        None => return Ok(None),
+    };
+
+    let value = header.to_str().unwrap_or_default().trim();
+    if value == "*" {
+        return Ok(None);
+    }
+
+    value
+        .strip_prefix('"')
+        .and_then(|value| value.strip_suffix('"'))
+        .and_then(|version| version.parse::<u64>().ok())
+        .map(Some)
+        .ok_or_else(|| {
+            Box::new(
+                Problem::new(StatusCode::PRECONDITION_FAILED)
+                    .with_detail("If-Match does not match the current version"),
+            )
+        })
+}
+
+#[cfg(test)]
+mod unit_tests {
+    use super::*;
+    use lambda_http::Body;
+
+    fn request_with_if_match(value: Option<&str>) -> Request {
+        let mut builder = lambda_http::http::Request::builder();
+        if let Some(value) = value {
+            builder = builder.header(IF_MATCH, value);
+        }
+
+        builder.body(Body::Empty).expect("Error building request")
+    }
+
+    #[test]
+    fn should_round_trip_version() {
+        let request = request_with_if_match(Some(&format_etag(7)));
+
+        assert_eq!(parse_if_match(&request, false).ok(), Some(Some(7)));
+    }
+
+    #[test]
+    fn should_accept_wildcard_tag() {
+        let wildcard = request_with_if_match(Some("*"));
+
+        assert_eq!(parse_if_match(&wildcard, true).ok(), Some(None));
+    }
+
+    #[test]
+    fn should_reject_weak_tag() {
+        let request = request_with_if_match(Some("W/\"3\""));
+
+        let err = parse_if_match(&request, false).expect_err("Weak tag was accepted");
+        assert_eq!(err.status_code(), StatusCode::PRECONDITION_FAILED);
+    }
+
+    #[test]
+    fn should_reject_missing_header_when_required() {
+        let request = request_with_if_match(None);
+
+        assert_eq!(parse_if_match(&request, false).ok(), Some(None));
+
+        let err = parse_if_match(&request, true).expect_err("Missing header was accepted");
+        assert_eq!(err.status_code(), StatusCode::PRECONDITION_REQUIRED);
+    }
+
+    #[test]
+    fn should_reject_unparseable_tag() {
+        let request = request_with_if_match(Some("\"abc\""));
+
+        let err = parse_if_match(&request, false).expect_err("Invalid tag was accepted");
+        assert_eq!(err.status_code(), StatusCode::PRECONDITION_FAILED);
+    }
+}
@@ -0,0 +1,74 @@
+use super::models::user_event::CloudEvent;
+
+use async_trait::async_trait;
+use std::collections::HashSet;
+use std::sync::Mutex;
+
+/// Where user events are published to. Implementations must accept the same event
+/// more than once, since events are redelivered when a batch is retried.
+#[async_trait]
+pub trait EventSink: Send + Sync {
+    async fn publish(&self, event: &CloudEvent) -> Result<(), Box<dyn std::error::Error>>;
+}
+
+/// Writes each event to stdout as one line of JSON, for a CloudWatch Logs
+/// subscription to forward.
+#[derive(Debug, Default)]
+pub struct LogEventSink;
+
+#[async_trait]
+impl EventSink for LogEventSink {
+    async fn publish(&self, event: &CloudEvent) -> Result<(), Box<dyn std::error::Error>> {
+        println!("{}", serde_json::to_string(event)?);
+
+        Ok(())
+    }
+}
+
+/// `EventSink` that keeps published events in memory. Events about the subjects
+/// passed to `fail_subject` are rejected, to exercise partial failures.
+#[derive(Debug, Default)]
+pub struct InMemoryEventSink {
+    events: Mutex<Vec<CloudEvent>>,
+    failing_subjects: Mutex<HashSet<String>>,
+}
+
+impl InMemoryEventSink {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn fail_subject(&self, subject: &str) {
+        self.failing_subjects
+            .lock()
+            .unwrap()
+            .insert(subject.to_owned());
+    }
+
+    /// Accepts events about `subject` again after `fail_subject`.
+    pub fn recover_subject(&self, subject: &str) {
+        self.failing_subjects.lock().unwrap().remove(subject);
+    }
+
+    pub fn events(&self) -> Vec<CloudEvent> {
+        self.events.lock().unwrap().clone()
+    }
+}
+
+#[async_trait]
+impl EventSink for InMemoryEventSink {
+    async fn publish(&self, event: &CloudEvent) -> Result<(), Box<dyn std::error::Error>> {
+        if self
+            .failing_subjects
+            .lock()
+            .unwrap()
+            .contains(&event.subject)
+        {
+            return Err(format!("Failed to publish event {}", event.id).into());
+        }
+
+        self.events.lock().unwrap().push(event.clone());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,320 @@
+use super::{
+    args::{export_users_args::ExportUsersArgs, scan_users_args::ScanUsersArgs},
+    error::Error,
+    file_format::{format_csv_record, FileFormat},
+    models::user::{User, USER_FIELDS},
+    repository::UserRepository,
+};
+
+use serde_json::{Map, Value};
+use std::sync::Arc;
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+use tokio::sync::mpsc;
+
+/// Pages each segment may read ahead of the writer.
+static PAGES_IN_FLIGHT_PER_SEGMENT: usize = 2;
+
+/// Writes every user matching `args` to `writer` as NDJSON or CSV, returning how many
+/// were written.
+///
+/// The table is read with a parallel Scan of `args.segments` segments. Pages pass to
+/// the writer through a bounded channel, so at most a couple of pages per segment are
+/// held in memory however large the table is. Users come out in no particular order.
+pub async fn export_users<W: AsyncWrite + Unpin>(
+    repository: Arc<dyn UserRepository>,
+    args: ExportUsersArgs,
+    writer: &mut W,
+) -> Result<usize, Box<dyn std::error::Error>> {
+    let columns: Vec<String> = match args.columns.clone() {
+        Some(columns) if columns.is_empty() => {
+            return Err(Box::new(Error::ClientError("No export columns were given")))
+        }
+        Some(columns) => columns,
+        None => USER_FIELDS
+            .iter()
+            .map(|column| column.to_string())
+            .collect(),
+    };
+    if columns
+        .iter()
+        .any(|column| !USER_FIELDS.contains(&column.as_str()))
+    {
+        return Err(Box::new(Error::ClientError("Unknown export column")));
+    }
+    if args.segments < 1 {
+        return Err(Box::new(Error::ClientError(
+            "Export needs at least one segment",
+        )));
+    }
+
+    // Errors cross task boundaries as strings, since boxed errors are not `Send`
+    let (sender, mut receiver) = mpsc::channel::<Result<Vec<User>, String>>(
+        PAGES_IN_FLIGHT_PER_SEGMENT * args.segments as usize,
+    );
+
+    let mut tasks = Vec::new();
+    for segment in 0..args.segments {
+        let repository = repository.clone();
+        let sender = sender.clone();
+        let mut scan_args = ScanUsersArgs {
+            segment,
+            total_segments: args.segments,
+            created_from: args.created_from.clone(),
+            created_to: args.created_to.clone(),
+            include_deleted: args.include_deleted,
+            pagination_token: None,
+        };
+
+        tasks.push(tokio::spawn(async move {
+            loop {
+                let page = repository
+                    .scan_users(scan_args.clone())
+                    .await
+                    .map_err(|err| err.to_string());
+
+                let token = match page {
+                    Ok(page) => {
+                        if sender.send(Ok(page.data)).await.is_err() {
+                            return;
+                        }
+                        page.token
+                    }
+                    Err(err) => {
+                        let _ = sender.send(Err(err)).await;
+                        return;
+                    }
+                };
+
+                match token {
+                    Some(token) => scan_args.pagination_token = Some(token),
+                    None => return,
+                }
+            }
+        }));
+    }
+    drop(sender);
+
+    if args.format == FileFormat::Csv {
+        writer
+            .write_all(format!("{}\n", format_csv_record(&columns)).as_bytes())
+            .await?;
+    }
+
+    let mut exported = 0;
+    while let Some(page) = receiver.recv().await {
+        let users = match page {
+            Ok(users) => users,
+            Err(err) => {
+                tasks.iter().for_each(|task| task.abort());
+                return Err(Box::new(Error::SdkError(err)));
+            }
+        };
+
+        for user in users {
+            writer
+                .write_all(format_user(&user, &columns, args.format)?.as_bytes())
+                .await?;
+            exported += 1;
+        }
+    }
+    writer.flush().await?;
+
+    Ok(exported)
+}
+
+/// Renders the selected fields of a user as one NDJSON line or CSV record.
+fn format_user(
+    user: &User,
+    columns: &[String],
+    format: FileFormat,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let mut fields = match serde_json::to_value(user)? {
+        Value::Object(fields) => fields,
+        _ => return Err(Box::new(Error::InternalError("User is not an object"))),
+    };
+
+    match format {
+        FileFormat::Ndjson => {
+            let selected: Map<String, Value> = columns
+                .iter()
+                .map(|column| {
+                    (
+                        column.to_owned(),
+                        fields.remove(column).unwrap_or(Value::Null),
+                    )
+                })
+                .collect();
+
+            Ok(format!("{}\n", Value::Object(selected)))
+        }
+        FileFormat::Csv => {
+            let values: Vec<String> = columns
+                .iter()
+                .map(|column| match fields.remove(column) {
+                    Some(Value::String(value)) => value,
+                    Some(Value::Null) | None => String::new(),
+                    Some(value) => value.to_string(),
+                })
+                .collect();
+
+            Ok(format!("{}\n", format_csv_record(&values)))
+        }
+    }
+}
+
+#[cfg(test)]
+mod unit_tests {
+    use super::*;
+    use crate::{
+        args::create_user_args::CreateUserArgs, file_format::parse_csv_record,
+        models::change_context::ChangeContext, repository::InMemoryUserRepository,
+    };
+
+    async fn seed_users(repository: &InMemoryUserRepository, count: usize) -> Vec<String> {
+        let mut user_ids = Vec::new();
+        for i in 0..count {
+            user_ids.push(
+                repository
+                    .create_user(
+                        CreateUserArgs {
+                            username: format!("tester{}", i),
+                            first_name: None,
+                            last_name: None,
+                            email: format!("tester{}@example.com", i),
+                            profile_photo: None,
+                            summary: Some(format!("Likes \"exports\", line {}", i)),
+                            phone_number: None,
+                        },
+                        &ChangeContext::new("admin"),
+                    )
+                    .await
+                    .expect("Failed to seed user"),
+            );
+        }
+
+        user_ids
+    }
+
+    #[tokio::test]
+    async fn should_export_every_user_as_ndjson() {
+        let repository = Arc::new(InMemoryUserRepository::new());
+        let mut user_ids = seed_users(&repository, 250).await;
+        repository
+            .delete_user(&user_ids.pop().unwrap(), None, &ChangeContext::new("admin"))
+            .await
+            .expect("Failed to delete user");
+
+        let mut output: Vec<u8> = Vec::new();
+        let exported = export_users(
+            repository,
+            ExportUsersArgs {
+                segments: 3,
+                ..Default::default()
+            },
+            &mut output,
+        )
+        .await
+        .expect("Export failed");
+
+        assert_eq!(exported, 249);
+
+        let mut exported_ids: Vec<String> = String::from_utf8(output)
+            .expect("Export is not UTF-8")
+            .lines()
+            .map(|line| {
+                let user: User = serde_json::from_str(line).expect("Invalid NDJSON line");
+                user.user_id
+            })
+            .collect();
+        exported_ids.sort();
+        user_ids.sort();
+        assert_eq!(exported_ids, user_ids);
+    }
+
+    #[tokio::test]
+    async fn should_export_selected_columns_as_csv() {
+        let repository = Arc::new(InMemoryUserRepository::new());
+        seed_users(&repository, 2).await;
+
+        let mut output: Vec<u8> = Vec::new();
+        export_users(
+            repository,
+            ExportUsersArgs {
+                format: FileFormat::Csv,
+                columns: Some(vec!["Username".to_string(), "Summary".to_string()]),
+                segments: 1,
+                ..Default::default()
+            },
+            &mut output,
+        )
+        .await
+        .expect("Export failed");
+
+        let output = String::from_utf8(output).expect("Export is not UTF-8");
+        let mut records: Vec<Vec<String>> = output
+            .lines()
+            .map(|line| parse_csv_record(line).expect("Invalid CSV record"))
+            .collect();
+
+        assert_eq!(records[0], vec!["Username", "Summary"]);
+        records[1..].sort();
+        assert_eq!(
+            records[1..].to_vec(),
+            vec![
+                vec!["tester0", "Likes \"exports\", line 0"],
+                vec!["tester1", "Likes \"exports\", line 1"],
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn should_filter_export_by_created_date() {
+        let repository = Arc::new(InMemoryUserRepository::new());
+        seed_users(&repository, 2).await;
+
+        let mut output: Vec<u8> = Vec::new();
+        let exported = export_users(
+            repository.clone(),
+            ExportUsersArgs {
+                created_to: Some("2000-01-01".to_string()),
+                ..Default::default()
+            },
+            &mut output,
+        )
+        .await
+        .expect("Export failed");
+        assert_eq!(exported, 0);
+
+        let exported = export_users(
+            repository,
+            ExportUsersArgs {
+                created_from: Some("2000-01-01".to_string()),
+                ..Default::default()
+            },
+            &mut output,
+        )
+        .await
+        .expect("Export failed");
+        assert_eq!(exported, 2);
+    }
+
+    #[tokio::test]
+    async fn should_reject_unknown_columns() {
+        let repository = Arc::new(InMemoryUserRepository::new());
+
+        let err = export_users(
+            repository,
+            ExportUsersArgs {
+                columns: Some(vec!["PK".to_string()]),
+                ..Default::default()
+            },
+            &mut Vec::new(),
+        )
+        .await
+        .expect_err("Unknown column was exported");
+        assert!(matches!(
+            err.downcast_ref::<Error>(),
+            Some(Error::ClientError(_))
+        ));
+    }
+}
@@ -8,16 +8,12 @@ pub trait AttributeValuesExt {
 }
 
 impl AttributeValuesExt for HashMap<String, AttributeValue> {
-	fn get_s(&self, key: &str) -> String {		
-		match self.get_opt_s(key) {
-			None => {
-				return "".to_string();
-			}
-			Some(v) => {
-				return v;
-			}
-		}
-	}
+    fn get_s(&self, key: &str) -> String {
+        match self.get_opt_s(key) {
+            None => "".to_string(),
+            Some(v) => v,
+        }
+    }
     fn get_opt_s(&self, key: &str) -> Option<String> {
         Some(self.get(key)?.as_s().ok()?.to_owned())
     }
@@ -0,0 +1,215 @@
+use super::{
+    error::Error,
+    item::{AttributeField, AttributeValue, Item},
+    models::{
+        partial_user::PartialUser,
+        user::{User, UserStatus, USER_FIELDS},
+    },
+    timestamp,
+};
+
+use chrono::{DateTime, Utc};
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// Attributes read with every fieldset, for `PartialUser::version` and `status`.
+static BOOKKEEPING_FIELDS: [&str; 2] = ["Version", "Status"];
+
+/// A sparse fieldset, the `User` fields a caller asked for with `?fields=`. `UserId`
+/// is always included, first.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Fieldset {
+    fields: Vec<&'static str>,
+}
+
+impl Fieldset {
+    /// Parses a comma separated list of `USER_FIELDS` names, e.g.
+    /// `Username,ProfilePhoto`. Names are matched exactly and repeats are ignored.
+    pub fn parse(value: &str) -> Result<Self, Error> {
+        let mut fields = vec![USER_FIELDS[0]];
+
+        for name in value.split(',').map(str::trim) {
+            if name.is_empty() {
+                continue;
+            }
+            let field = USER_FIELDS
+                .iter()
+                .find(|field| **field == name)
+                .ok_or(Error::ClientError("Unknown user field"))?;
+            if !fields.contains(field) {
+                fields.push(field);
+            }
+        }
+
+        Ok(Self { fields })
+    }
+
+    pub fn fields(&self) -> &[&'static str] {
+        &self.fields
+    }
+
+    /// The `ProjectionExpression` reading the fields, and the expression attribute
+    /// names it uses, each attribute named `#<Attribute>`.
+    pub fn projection(&self) -> (String, HashMap<String, String>) {
+        let mut attributes: Vec<&str> = self.fields.clone();
+        for field in BOOKKEEPING_FIELDS {
+            if !attributes.contains(&field) {
+                attributes.push(field);
+            }
+        }
+
+        let expression = attributes
+            .iter()
+            .map(|attribute| format!("#{}", attribute))
+            .collect::<Vec<String>>()
+            .join(", ");
+        let names = attributes
+            .iter()
+            .map(|attribute| (format!("#{}", attribute), attribute.to_string()))
+            .collect();
+
+        (expression, names)
+    }
+
+    /// Reads the fields from a user item, which may hold only the projected
+    /// attributes. Missing attributes render as `null`, except `Version` and `Status`,
+    /// which read as `0` and `active` as they do for a whole `User`.
+    pub fn select_item(&self, item: &Item) -> PartialUser {
+        let version: u64 =
+            AttributeField::from_attribute(item.get("Version").cloned()).unwrap_or(0);
+        let status: UserStatus =
+            AttributeField::from_attribute(item.get("Status").cloned()).unwrap_or_default();
+
+        let fields = self
+            .fields
+            .iter()
+            .map(|field| {
+                let value = item.get(*field).cloned();
+                let value = match *field {
+                    "Version" => Value::from(version),
+                    "Status" => Value::from(status.value()),
+                    "CreatedDate" | "UpdatedDate" | "DeletedAt" => {
+                        render::<DateTime<Utc>>(value, |date| timestamp::format(&date).into())
+                    }
+                    _ => render::<String>(value, Value::String),
+                };
+                (field.to_string(), value)
+            })
+            .collect();
+
+        PartialUser {
+            fields,
+            version,
+            status,
+        }
+    }
+
+    pub fn select_user(&self, user: &User) -> PartialUser {
+        self.select_item(&Item::from(user))
+    }
+}
+
+fn render<T: AttributeField>(value: Option<AttributeValue>, to_json: fn(T) -> Value) -> Value {
+    T::from_attribute(value).map(to_json).unwrap_or(Value::Null)
+}
+
+#[cfg(test)]
+mod unit_tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn user() -> User {
+        let time = Utc.with_ymd_and_hms(2023, 7, 1, 12, 0, 0).unwrap();
+
+        User {
+            user_id: "abc123".to_string(),
+            username: "tester".to_string(),
+            first_name: Some("Test".to_string()),
+            last_name: None,
+            email: "tester@example.com".to_string(),
+            profile_photo: Some("https://example.com/tester.png".to_string()),
+            summary: None,
+            phone_number: None,
+            gsi1pk: "EMAIL#tester@example.com".to_string(),
+            created_date: time,
+            updated_date: time,
+            version: 3,
+            status: UserStatus::Deleted,
+            deleted_at: Some(time),
+            expires_at: Some(1690804800),
+        }
+    }
+
+    #[test]
+    fn should_always_select_user_id_first() {
+        let fieldset =
+            Fieldset::parse("Username, ProfilePhoto,Username,").expect("Invalid fieldset");
+
+        assert_eq!(fieldset.fields(), ["UserId", "Username", "ProfilePhoto"]);
+        assert_eq!(
+            Fieldset::parse("").expect("Invalid fieldset").fields(),
+            ["UserId"]
+        );
+    }
+
+    #[test]
+    fn should_reject_fields_outside_the_whitelist() {
+        for value in ["GSI1PK", "ExpiresAt", "username", "PK,Username"] {
+            assert!(matches!(Fieldset::parse(value), Err(Error::ClientError(_))));
+        }
+    }
+
+    #[test]
+    fn should_project_fields_with_version_and_status() {
+        let (expression, names) = Fieldset::parse("Username,Status")
+            .expect("Invalid fieldset")
+            .projection();
+
+        assert_eq!(expression, "#UserId, #Username, #Status, #Version");
+        assert_eq!(names.len(), 4);
+        assert_eq!(names["#Version"], "Version");
+    }
+
+    #[test]
+    fn should_select_fields_as_the_user_serializes_them() {
+        let user = user();
+        let fieldset = Fieldset::parse(&USER_FIELDS.join(",")).expect("Invalid fieldset");
+
+        let selected = fieldset.select_user(&user);
+
+        assert_eq!(
+            serde_json::to_value(&selected).expect("Failed to serialize"),
+            serde_json::to_value(&user).expect("Failed to serialize")
+        );
+        assert_eq!(selected.version, 3);
+        assert!(selected.is_deleted());
+    }
+
+    #[test]
+    fn should_select_from_a_projected_item() {
+        let fieldset = Fieldset::parse("ProfilePhoto,Summary").expect("Invalid fieldset");
+        let item: Item = HashMap::from([
+            (
+                "UserId".to_string(),
+                AttributeValue::S("abc123".to_string()),
+            ),
+            (
+                "ProfilePhoto".to_string(),
+                AttributeValue::S("https://example.com/tester.png".to_string()),
+            ),
+        ]);
+
+        let selected = fieldset.select_item(&item);
+
+        assert_eq!(
+            serde_json::to_value(&selected).expect("Failed to serialize"),
+            serde_json::json!({
+                "UserId": "abc123",
+                "ProfilePhoto": "https://example.com/tester.png",
+                "Summary": null
+            })
+        );
+        assert_eq!(selected.version, 0);
+        assert!(!selected.is_deleted());
+    }
+}
@@ -0,0 +1,133 @@
+use std::path::Path;
+use std::str::FromStr;
+
+/// Formats users are imported from and exported to.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum FileFormat {
+    /// One JSON object per line.
+    #[default]
+    Ndjson,
+    /// A header row naming the columns, then one user per record.
+    Csv,
+}
+
+impl FileFormat {
+    /// Picks the format for a request body, defaulting to NDJSON.
+    pub fn from_content_type(content_type: Option<&str>) -> Self {
+        match content_type {
+            Some(content_type) if content_type.to_lowercase().starts_with("text/csv") => {
+                FileFormat::Csv
+            }
+            _ => FileFormat::Ndjson,
+        }
+    }
+
+    /// Picks the format for a file from its extension, defaulting to NDJSON.
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("csv") => FileFormat::Csv,
+            _ => FileFormat::Ndjson,
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match *self {
+            FileFormat::Ndjson => "ndjson",
+            FileFormat::Csv => "csv",
+        }
+    }
+}
+
+impl FromStr for FileFormat {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_lowercase().as_str() {
+            "ndjson" | "jsonl" | "json" => Ok(FileFormat::Ndjson),
+            "csv" => Ok(FileFormat::Csv),
+            _ => Err(format!("Invalid file format: {}", value)),
+        }
+    }
+}
+
+/// Splits an RFC 4180 record into its fields. Quoted fields may hold commas, line
+/// breaks and `""` escaped quotes.
+pub fn parse_csv_record(record: &str) -> Result<Vec<String>, String> {
+    let mut fields: Vec<String> = Vec::new();
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut chars = record.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match (c, in_quotes) {
+            ('"', true) if chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            ('"', true) => in_quotes = false,
+            ('"', false) if field.is_empty() => in_quotes = true,
+            (',', false) => fields.push(std::mem::take(&mut field)),
+            (c, _) => field.push(c),
+        }
+    }
+
+    if in_quotes {
+        return Err("Unterminated quoted field".to_string());
+    }
+    fields.push(field);
+
+    Ok(fields)
+}
+
+/// Joins fields into an RFC 4180 record, without the line break. Fields holding a
+/// comma, quote or line break are quoted.
+pub fn format_csv_record<S: AsRef<str>>(fields: &[S]) -> String {
+    fields
+        .iter()
+        .map(|field| {
+            let field = field.as_ref();
+            if field.contains([',', '"', '\n', '\r']) {
+                format!("\"{}\"", field.replace('"', "\"\""))
+            } else {
+                field.to_string()
+            }
+        })
+        .collect::<Vec<String>>()
+        .join(",")
+}
+
+#[cfg(test)]
+mod unit_tests {
+    use super::*;
+
+    #[test]
+    fn should_parse_quoted_csv_fields() {
+        assert_eq!(
+            parse_csv_record(r#"tester,"Hello, ""world""",,"line one"#)
+                .expect_err("Unterminated field was accepted"),
+            "Unterminated quoted field"
+        );
+
+        assert_eq!(
+            parse_csv_record("tester,\"Hello, \"\"world\"\"\",,\"line one\nline two\"")
+                .expect("Failed to parse record"),
+            vec!["tester", "Hello, \"world\"", "", "line one\nline two"]
+        );
+    }
+
+    #[test]
+    fn should_format_csv_record_that_parses_back() {
+        let fields = vec!["tester", "Hello, \"world\"", "", "line one\nline two"];
+
+        let record = format_csv_record(&fields);
+
+        assert_eq!(
+            record,
+            "tester,\"Hello, \"\"world\"\"\",,\"line one\nline two\""
+        );
+        assert_eq!(
+            parse_csv_record(&record).expect("Failed to parse record"),
+            fields
+        );
+    }
+}
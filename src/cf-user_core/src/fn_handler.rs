@@ -1,26 +1,234 @@
-use super::{dynamo, models::handler_response::HandleResponse};
-use aws_sdk_dynamodb::Client;
-use lambda_http::http::StatusCode;
+use super::{
+    cache::UserCache,
+    dynamo,
+    error::{Error, RepositoryError},
+    fieldset::Fieldset,
+    models::{
+        change_context::ChangeContext,
+        handler_response::HandleResponse,
+        problem::{Problem, ProblemCode, PROBLEM_CONTENT_TYPE},
+    },
+    repository::{CachedUserRepository, DynamoUserRepository, UserRepository},
+};
+use lambda_http::http::{header::CACHE_CONTROL, StatusCode};
 use lambda_http::request::RequestContext;
 use lambda_http::{Request, RequestExt, Response};
+use serde::Serialize;
 use serde_json::json;
 use std::future::Future;
-use tracing::info;
+use std::sync::Arc;
+use std::time::{Duration, UNIX_EPOCH};
+use tracing::{error, info};
 
 use super::models::permissions::Permission;
 
-pub async fn handle_request<F, Fut>(
+/// Most items a listing returns per page.
+static MAX_PAGE_LIMIT: i32 = 100;
+
+/// Whether the authorizer granted `permission` to the caller. Handlers use this for
+/// checks beyond the permission required to invoke them.
+pub fn has_permission(event: &Request, permission: Permission) -> bool {
+    let request_context = match event.request_context_ref() {
+        Some(RequestContext::ApiGatewayV1(ctx)) => ctx,
+        _ => return false,
+    };
+
+    let authorizer_permissions = request_context
+        .authorizer
+        .get("permissions")
+        .expect("Auth context missing permissions.");
+
+    let user_permissions: Vec<&str> = serde_json::from_str(
+        authorizer_permissions
+            .as_str()
+            .expect("User permissions are invalid type."),
+    )
+    .expect("Parsing user permissions failed");
+
+    user_permissions.contains(&permission.value().as_str())
+}
+
+/// The principal the authorizer authenticated, recorded as the actor of any change
+/// the request makes.
+pub fn principal_id(event: &Request) -> String {
+    match event.request_context_ref() {
+        Some(RequestContext::ApiGatewayV1(ctx)) => ctx
+            .authorizer
+            .get("principalId")
+            .and_then(|principal_id| principal_id.as_str())
+            .unwrap_or("unknown")
+            .to_string(),
+        _ => "unknown".to_string(),
+    }
+}
+
+/// The API Gateway request ID, the `instance` of any problem the request answers
+/// with.
+pub fn request_id(event: &Request) -> Option<String> {
+    match event.request_context_ref() {
+        Some(RequestContext::ApiGatewayV1(ctx)) => ctx.request_id.clone(),
+        _ => None,
+    }
+}
+
+/// The principal making the request and the API Gateway request ID, recorded with
+/// every change the request makes.
+pub fn change_context(event: &Request) -> ChangeContext {
+    let context = ChangeContext::new(&principal_id(event));
+
+    match request_id(event) {
+        Some(request_id) => context.with_request_id(&request_id),
+        None => context,
+    }
+}
+
+/// Whether the request was sent with `Cache-Control: no-cache`, asking for users to
+/// be read from the table rather than the in-process cache.
+pub fn no_cache(event: &Request) -> bool {
+    event
+        .headers()
+        .get_all(CACHE_CONTROL)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|directive| directive.trim().eq_ignore_ascii_case("no-cache"))
+}
+
+/// Reads `?includeDeleted=true`, which only admins may send.
+pub fn parse_include_deleted(event: &Request) -> Result<bool, Box<Problem>> {
+    let include_deleted = event
+        .query_string_parameters_ref()
+        .and_then(|qp| qp.first("includeDeleted"))
+        .map(|value| value.eq_ignore_ascii_case("true"))
+        .unwrap_or(false);
+
+    if include_deleted && !has_permission(event, Permission::UserAdmin) {
+        return Err(Box::new(
+            Problem::new(StatusCode::FORBIDDEN)
+                .with_detail("User unauthorized to include deleted users"),
+        ));
+    }
+
+    Ok(include_deleted)
+}
+
+/// Reads `?limit=`, the page size of a listing, or `default` when it is absent. Sizes
+/// outside 1 to 100 are clamped to that range.
+pub fn parse_limit(event: &Request, default: i32) -> Result<i32, Box<Problem>> {
+    let limit = match event
+        .query_string_parameters_ref()
+        .and_then(|qp| qp.first("limit"))
+    {
+        Some(limit) => limit,
+        None => return Ok(default),
+    };
+
+    match limit.trim().parse::<i64>() {
+        Ok(limit) => Ok(limit.clamp(1, MAX_PAGE_LIMIT.into()) as i32),
+        Err(_) => Err(Box::new(
+            Problem::new(StatusCode::BAD_REQUEST).with_detail("limit must be a whole number"),
+        )),
+    }
+}
+
+/// Reads `?fields=`, the `User` fields to return instead of the whole user.
+pub fn parse_fields(event: &Request) -> Result<Option<Fieldset>, Box<Problem>> {
+    let fields = match event
+        .query_string_parameters_ref()
+        .and_then(|qp| qp.first("fields"))
+    {
+        Some(fields) => fields,
+        None => return Ok(None),
+    };
+
+    match Fieldset::parse(fields) {
+        Ok(fields) => Ok(Some(fields)),
+        Err(Error::ClientError(msg)) => Err(Box::new(
+            Problem::new(StatusCode::BAD_REQUEST).with_detail(msg),
+        )),
+        Err(err) => Err(Box::new(
+            Problem::new(StatusCode::BAD_REQUEST).with_detail(&err.to_string()),
+        )),
+    }
+}
+
+/// Answers a failed repository call with the status and code for its class of
+/// error, so clients can tell a conflict or throttling from a fault. `message` says
+/// what was being done, e.g. "Error fetching user by ID", and is the whole detail of
+/// server errors, whose cause is only logged.
+pub fn error_response<T>(
+    message: &str,
+    err: &(dyn std::error::Error + 'static),
+) -> HandleResponse<T> {
+    let status = match (
+        err.downcast_ref::<RepositoryError>(),
+        err.downcast_ref::<Error>(),
+    ) {
+        (Some(RepositoryError::Throttled(_)), _) => StatusCode::TOO_MANY_REQUESTS,
+        (Some(RepositoryError::ConditionFailed(_)), _) => StatusCode::PRECONDITION_FAILED,
+        (Some(RepositoryError::TransactionConflict(_)), _) => StatusCode::CONFLICT,
+        (Some(RepositoryError::ResourceNotFound(_)), _)
+        | (Some(RepositoryError::Timeout(_)), _)
+        | (Some(RepositoryError::Unavailable(_)), _) => StatusCode::SERVICE_UNAVAILABLE,
+        (Some(RepositoryError::Validation(_)), _) => StatusCode::BAD_REQUEST,
+        (_, Some(Error::ConflictError(_))) => StatusCode::CONFLICT,
+        (_, Some(Error::PreconditionError(_))) => StatusCode::PRECONDITION_FAILED,
+        (_, Some(Error::ClientError(_))) | (_, Some(Error::InvalidToken(_))) => {
+            StatusCode::BAD_REQUEST
+        }
+        _ => StatusCode::INTERNAL_SERVER_ERROR,
+    };
+
+    let code = match (
+        err.downcast_ref::<RepositoryError>(),
+        err.downcast_ref::<Error>(),
+    ) {
+        (_, Some(Error::ConflictError(field))) => ProblemCode::from(*field),
+        (_, Some(Error::InvalidToken(_))) => ProblemCode::InvalidPageToken,
+        _ => ProblemCode::from_status(status),
+    };
+
+    let problem = Problem::with_status(code, status);
+    if status.is_server_error() {
+        error!("{}: {}", message, err);
+        return HandleResponse::problem(problem.with_detail(message));
+    }
+
+    HandleResponse::problem(problem.with_detail(format!("{}: {}", message, err).as_str()))
+}
+
+/// Renders `problem` as an `application/problem+json` response.
+pub fn problem_response(problem: &Problem) -> Result<Response<String>, Box<dyn std::error::Error>> {
+    Ok(Response::builder()
+        .status(problem.status_code())
+        .header("Content-Type", PROBLEM_CONTENT_TYPE)
+        .body(serde_json::to_string(problem)?)
+        .map_err(Box::new)?)
+}
+
+/// Users table of the app stack a function was deployed in, e.g.
+/// `cf-user-app-get-user` reads `cf-user-app-users`.
+pub fn table_name(function_name: &str) -> String {
+    let app_stack = String::from(function_name.split("-app").collect::<Vec<&str>>()[0]) + "-app";
+
+    format!("{app_stack}-users")
+}
+
+pub async fn handle_request<F, Fut, T>(
     event: Request,
     fn_handler: F,
-    client: Option<Client>,
+    repository: Option<Arc<dyn UserRepository>>,
     permission: Permission,
 ) -> Result<Response<String>, Box<dyn std::error::Error>>
 where
-    F: FnOnce(Request, Client, String) -> Fut,
-    Fut: Future<Output = Result<HandleResponse, Box<dyn std::error::Error>>>,
+    F: FnOnce(Request, Arc<dyn UserRepository>) -> Fut,
+    Fut: Future<Output = Result<HandleResponse<T>, Box<dyn std::error::Error>>>,
+    T: Serialize,
 {
-    let function_name = event.lambda_context().env_config.function_name;
-    let app_stack = String::from(function_name.split("-app").collect::<Vec<&str>>()[0]) + "-app";
+    let lambda_context = event.lambda_context();
+    let function_name = lambda_context.env_config.function_name;
+    let deadline = UNIX_EPOCH + Duration::from_millis(lambda_context.deadline);
+    let request_id = request_id(&event).unwrap_or_default();
     let request_context = match event.request_context() {
         RequestContext::ApiGatewayV1(ctx) => ctx,
         _ => {
@@ -32,7 +240,7 @@ where
         "domain_name": request_context.domain_name.unwrap_or("".to_string()),
         "function_name": function_name,
         "path": request_context.path.unwrap_or("".to_string()),
-        "request_id": request_context.request_id.unwrap_or("".to_string()),
+        "request_id": request_id,
         "request_time": request_context.request_time.unwrap_or("".to_string()),
         "resource_path": request_context.resource_path.unwrap_or("".to_string()),
         "stage": request_context.stage.unwrap_or("".to_string()),
@@ -41,108 +249,120 @@ where
 
     info!("Request Data: {}", req_data);
 
-    let authorizer_permissions = request_context
-        .authorizer
-        .clone()
-        .get("permissions")
-        .expect("Auth context missing permissions.")
-        .to_owned();
-
-    let user_permissions: Vec<&str> = serde_json::from_str(
-        authorizer_permissions
-            .as_str()
-            .expect("User permissions are invalid type."),
-    )
-    .expect("Parsing user permissions failed");
-
-    if !user_permissions.contains(&permission.value().as_str()) {
-        return Ok(Response::builder()
-            .status(StatusCode::FORBIDDEN)
-            .header("Content-Type", "application/json")
-            .body(
-                json!({
-                    "error": "User unauthorized to perform this action".to_string(),
-                })
-                .to_string(),
-            )
-            .map_err(Box::new)?);
+    if !has_permission(&event, permission) {
+        return problem_response(
+            &Problem::new(StatusCode::FORBIDDEN)
+                .with_detail("User unauthorized to perform this action")
+                .with_instance(&request_id),
+        );
     }
 
-    let client = match client {
-        Some(client) => client,
+    let cache = UserCache::shared();
+    let repository = match repository {
+        Some(repository) => repository,
         None => match dynamo::get_client().await {
-            Ok(client) => client,
-            Err(_e) => {
-                return Ok(Response::builder()
-                    .status(StatusCode::BAD_REQUEST)
-                    .header("Content-Type", "application/json")
-                    .body(
-                        json!({
-                            "error": "Error fetching DynamoDB Client".to_string(),
-                        })
-                        .to_string(),
-                    )
-                    .map_err(Box::new)?);
+            Ok(client) => {
+                let repository: Arc<dyn UserRepository> = Arc::new(
+                    DynamoUserRepository::new(client, table_name(&function_name).as_str())
+                        .with_deadline(deadline),
+                );
+
+                match &cache {
+                    Some(cache) => Arc::new(
+                        CachedUserRepository::new(repository, cache.clone())
+                            .with_bypass(no_cache(&event)),
+                    ),
+                    None => repository,
+                }
+            }
+            Err(err) => {
+                error!("Error fetching DynamoDB Client: {}", err);
+                return problem_response(
+                    &Problem::new(StatusCode::SERVICE_UNAVAILABLE)
+                        .with_detail("Error fetching DynamoDB Client")
+                        .with_instance(&request_id),
+                );
             }
         },
     };
 
-    match fn_handler(event, client, app_stack).await {
-        Ok(res) => {
-            let status = match res.status_code.to_owned() {
-                Some(status_code) => status_code,
-                None => match res.err_message.to_owned() {
-                    Some(_err) => StatusCode::BAD_REQUEST,
-                    None => StatusCode::OK,
-                },
-            };
-
-            if let 0..=299 = status.as_u16() {
-                return match res.body.to_owned() {
-                    Some(body) => Ok(Response::builder()
-                        .status(status)
-                        .header("Content-Type", "application/json")
-                        .body(body.into())
-                        .map_err(Box::new)?),
-                    None => Ok(Response::builder()
-                        .status(StatusCode::NO_CONTENT)
-                        .header("Content-Type", "application/json")
-                        .body("".into())
-                        .map_err(Box::new)?),
-                };
-            } else {
-                return match res.err_message.to_owned() {
-                    Some(err) => Ok(Response::builder()
-                        .status(status)
-                        .header("Content-Type", "application/json")
-                        .body(
-                            json!({
-                                "error": err.to_string(),
-                            })
-                            .to_string()
-                            .into(),
-                        )
-                        .map_err(Box::new)?),
-                    None => Ok(Response::builder()
-                        .status(StatusCode::BAD_REQUEST)
-                        .header("Content-Type", "application/json")
-                        .body(
-                            json!({
-                                "error": "Unhandled Exception".to_string(),
-                            })
-                            .to_string()
-                            .into(),
-                        )
-                        .map_err(Box::new)?),
-                };
-            }
-        }
+    let result = fn_handler(event, repository).await;
+
+    if let Some(cache) = cache {
+        let stats = cache.stats();
+        info!(
+            "User Cache: {}",
+            json!({ "hits": stats.hits, "misses": stats.misses })
+        );
+    }
+
+    let res = match result {
+        Ok(res) => res,
+        Err(err) => error_response("Error handling request", err.as_ref()),
+    };
+
+    let status = res.status();
+    if let Some(problem) = res.problem {
+        let mut response = problem_response(&problem.with_instance(&request_id))?;
+        response.headers_mut().extend(res.headers);
+        return Ok(response);
+    }
+    if !status.is_success() {
+        return problem_response(
+            &Problem::new(StatusCode::BAD_REQUEST)
+                .with_detail("Unhandled Exception")
+                .with_instance(&request_id),
+        );
+    }
+
+    let body = match res.body.as_ref().map(serde_json::to_string).transpose() {
+        Ok(body) => body,
         Err(err) => {
-            return Ok(Response::builder()
-                .status(StatusCode::INTERNAL_SERVER_ERROR)
-                .header("Content-Type", "application/json")
-                .body(err.to_string().into())
-                .map_err(Box::new)?);
+            error!("Error serializing response: {}", err);
+            return problem_response(
+                &Problem::new(StatusCode::INTERNAL_SERVER_ERROR)
+                    .with_detail("Error serializing response")
+                    .with_instance(&request_id),
+            );
+        }
+    };
+
+    let mut response = match body {
+        Some(body) => Response::builder()
+            .status(status)
+            .header("Content-Type", "application/json")
+            .body(body),
+        None => Response::builder().status(status).body("".into()),
+    }
+    .map_err(Box::new)?;
+    response.headers_mut().extend(res.headers);
+
+    Ok(response)
+}
+
+#[cfg(test)]
+mod unit_tests {
+    use super::*;
+
+    #[test]
+    fn should_answer_repository_errors_by_class() {
+        for (err, status) in [
+            (
+                RepositoryError::Validation("Item size has exceeded the maximum".into()),
+                StatusCode::BAD_REQUEST,
+            ),
+            (
+                RepositoryError::Throttled("slow down".into()),
+                StatusCode::TOO_MANY_REQUESTS,
+            ),
+            (
+                RepositoryError::Internal("failed".into()),
+                StatusCode::INTERNAL_SERVER_ERROR,
+            ),
+        ] {
+            let response: HandleResponse = error_response("Error updating user by ID", &err);
+
+            assert_eq!(response.status(), status);
         }
     }
 }
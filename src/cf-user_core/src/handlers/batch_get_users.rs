@@ -0,0 +1,167 @@
+use super::super::{
+    args::batch_get_users_args::{BatchGetUsersArgs, MAX_BATCH_GET_USER_IDS},
+    fn_handler,
+    models::{batch_get_result::BatchGetResult, handler_response::HandleResponse, user::User},
+    repository::UserRepository,
+};
+use lambda_http::Request;
+use std::sync::Arc;
+
+pub async fn function_handler(
+    event: Request,
+    repository: Arc<dyn UserRepository>,
+) -> Result<HandleResponse<BatchGetResult<User>>, Box<dyn std::error::Error>> {
+    let include_deleted = match fn_handler::parse_include_deleted(&event) {
+        Ok(include_deleted) => include_deleted,
+        Err(problem) => return Ok(HandleResponse::problem(*problem)),
+    };
+
+    let body = event.body();
+    let s = std::str::from_utf8(body).expect("invalid utf-8 sequence");
+
+    let args = match serde_json::from_str::<BatchGetUsersArgs>(s) {
+        Ok(args) => args,
+        Err(_err) => {
+            return Ok(HandleResponse::error(Some(
+                "Error parsing incoming request object",
+            )));
+        }
+    };
+
+    if args.user_ids.is_empty() {
+        return Ok(HandleResponse::error(Some("No user IDs were requested")));
+    }
+    if args.user_ids.len() > MAX_BATCH_GET_USER_IDS {
+        return Ok(HandleResponse::error(Some(
+            format!(
+                "At most {} user IDs can be requested at once",
+                MAX_BATCH_GET_USER_IDS
+            )
+            .as_str(),
+        )));
+    }
+
+    let result = match repository
+        .get_users_by_ids(&args.user_ids, include_deleted)
+        .await
+    {
+        Ok(result) => result,
+        Err(err) => {
+            return Ok(fn_handler::error_response(
+                "Error fetching users by ID",
+                err.as_ref(),
+            ));
+        }
+    };
+
+    Ok(HandleResponse::success(result))
+}
+
+#[cfg(test)]
+mod unit_tests {
+    use super::*;
+    use crate::{
+        args::create_user_args::CreateUserArgs, mock_request::create_mock_request,
+        models::change_context::ChangeContext, models::permissions::Permission,
+        repository::InMemoryUserRepository,
+    };
+    use lambda_http::http::StatusCode;
+    use lambda_http::{http, Body};
+    use serde_json::json;
+
+    fn batch_get_request(user_ids: &[String]) -> Request {
+        create_mock_request(
+            http::Method::POST,
+            "https://dev-api.classifind.app/user/v1/users:batchGet",
+            Body::from(json!({ "UserIds": user_ids }).to_string()),
+            &[Permission::UserGet],
+        )
+    }
+
+    #[tokio::test]
+    async fn batch_get_users_should_succeed() {
+        let repository = Arc::new(InMemoryUserRepository::new());
+
+        let mut user_ids: Vec<String> = Vec::new();
+        for i in 0..2 {
+            user_ids.push(
+                repository
+                    .create_user(
+                        CreateUserArgs {
+                            username: format!("tester{}", i),
+                            first_name: None,
+                            last_name: None,
+                            email: format!("tester{}@example.com", i),
+                            profile_photo: None,
+                            summary: None,
+                            phone_number: None,
+                        },
+                        &ChangeContext::new("tester"),
+                    )
+                    .await
+                    .expect("Failed to seed user"),
+            );
+        }
+
+        let requested = vec![
+            user_ids[1].clone(),
+            "01H5FS6FKMB0YY0VDJ015741BJ".to_string(),
+            user_ids[0].clone(),
+        ];
+
+        let response = fn_handler::handle_request(
+            batch_get_request(&requested),
+            function_handler,
+            Some(repository),
+            Permission::UserGet,
+        )
+        .await
+        .expect("Handler failed");
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let result: BatchGetResult<User> =
+            serde_json::from_str(response.body()).expect("Invalid result body");
+        let found: Vec<String> = result.data.into_iter().map(|u| u.user_id).collect();
+        assert_eq!(found, vec![user_ids[1].clone(), user_ids[0].clone()]);
+        assert_eq!(
+            result.missing,
+            vec!["01H5FS6FKMB0YY0VDJ015741BJ".to_string()]
+        );
+    }
+
+    #[tokio::test]
+    async fn batch_get_without_ids_should_fail() {
+        let repository = Arc::new(InMemoryUserRepository::new());
+
+        let response = fn_handler::handle_request(
+            batch_get_request(&[]),
+            function_handler,
+            Some(repository),
+            Permission::UserGet,
+        )
+        .await
+        .expect("Handler failed");
+
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn batch_get_too_many_ids_should_fail() {
+        let repository = Arc::new(InMemoryUserRepository::new());
+        let user_ids: Vec<String> = (0..=MAX_BATCH_GET_USER_IDS)
+            .map(|i| i.to_string())
+            .collect();
+
+        let response = fn_handler::handle_request(
+            batch_get_request(&user_ids),
+            function_handler,
+            Some(repository),
+            Permission::UserGet,
+        )
+        .await
+        .expect("Handler failed");
+
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+}
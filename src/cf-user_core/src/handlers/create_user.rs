@@ -0,0 +1,218 @@
+use super::super::{
+    args::create_user_args::CreateUserArgs,
+    etag, fn_handler,
+    models::{
+        handler_response::HandleResponse,
+        problem::{Problem, ProblemCode},
+        user::User,
+    },
+    repository::UserRepository,
+};
+use lambda_http::{http::StatusCode, Request};
+use std::sync::Arc;
+use tracing::info;
+
+pub async fn function_handler(
+    event: Request,
+    repository: Arc<dyn UserRepository>,
+) -> Result<HandleResponse<User>, Box<dyn std::error::Error>> {
+    let body = event.body();
+    let s = std::str::from_utf8(body).expect("invalid utf-8 sequence");
+
+    info!("Create New User: {}", s);
+
+    let item = match serde_json::from_str::<CreateUserArgs>(s) {
+        Ok(item) => item,
+        Err(_err) => {
+            return Ok(HandleResponse::error(Some(
+                "Error parsing incoming request object",
+            )));
+        }
+    };
+
+    let errors = item.field_errors();
+    if !errors.is_empty() {
+        return Ok(HandleResponse::problem(
+            Problem::with_status(ProblemCode::ValidationFailed, StatusCode::BAD_REQUEST)
+                .with_detail("Invalid user")
+                .with_errors(errors),
+        ));
+    }
+
+    let user_id = match repository
+        .create_user(item.clone(), &fn_handler::change_context(&event))
+        .await
+    {
+        Ok(user_id) => user_id,
+        Err(err) => {
+            return Ok(fn_handler::error_response(
+                "Error creating user",
+                err.as_ref(),
+            ));
+        }
+    };
+
+    let user = match repository.get_user_by_id(&user_id, false).await {
+        Ok(user) => user,
+        Err(err) => {
+            return Ok(fn_handler::error_response(
+                "Error fetching user by ID",
+                err.as_ref(),
+            ));
+        }
+    };
+
+    match user {
+        Some(user) => {
+            let location = format!("/v1/users/{}", user.user_id);
+            let etag = etag::format_etag(user.version);
+
+            Ok(HandleResponse::success(user)
+                .with_status(StatusCode::CREATED)
+                .with_location(&location)
+                .with_etag(&etag))
+        }
+        None => Ok(HandleResponse::error(Some("Error parsing user data"))),
+    }
+}
+
+#[cfg(test)]
+mod unit_tests {
+    use super::*;
+    use crate::{
+        mock_request::create_mock_request, models::permissions::Permission,
+        models::problem::FieldError, models::user::User, repository::InMemoryUserRepository,
+    };
+    use lambda_http::http;
+    use serde_json::json;
+
+    fn create_request(email: &str) -> Request {
+        let user_request = json!({
+            "Username": "taylorlaing121234",
+            "FirstName": "Taylor",
+            "LastName": "Laing",
+            "Email": email,
+            "PhoneNumber": "8013911705",
+            "Summary": "Bruh this is gonna take forever..."
+        });
+
+        create_mock_request(
+            http::Method::POST,
+            "https://dev-api.classifind.app/user/v1/users",
+            user_request.to_string().into(),
+            &[Permission::UserCreate],
+        )
+    }
+
+    #[tokio::test]
+    async fn create_new_user_should_succeed() {
+        let repository = Arc::new(InMemoryUserRepository::new());
+
+        let response = fn_handler::handle_request(
+            create_request("taylorlaing121234@gmail.com"),
+            function_handler,
+            Some(repository.clone()),
+            Permission::UserCreate,
+        )
+        .await
+        .expect("Handler failed");
+
+        assert_eq!(response.status(), StatusCode::CREATED);
+
+        let user: User = serde_json::from_str(response.body()).expect("Invalid user body");
+        assert_eq!(user.email, "taylorlaing121234@gmail.com");
+        assert_eq!(
+            response.headers()["Location"],
+            format!("/v1/users/{}", user.user_id).as_str()
+        );
+        assert_eq!(response.headers()["ETag"], "\"1\"");
+    }
+
+    #[tokio::test]
+    async fn create_user_with_existing_email_should_conflict() {
+        let repository = Arc::new(InMemoryUserRepository::new());
+
+        for expected_status in [StatusCode::CREATED, StatusCode::CONFLICT] {
+            let response = fn_handler::handle_request(
+                create_request("taylorlaing121234@gmail.com"),
+                function_handler,
+                Some(repository.clone()),
+                Permission::UserCreate,
+            )
+            .await
+            .expect("Handler failed");
+
+            assert_eq!(response.status(), expected_status);
+            if expected_status == StatusCode::CONFLICT {
+                let problem: Problem =
+                    serde_json::from_str(response.body()).expect("Invalid problem");
+                assert_eq!(problem.code, ProblemCode::EmailTaken);
+            }
+        }
+
+        let users = repository
+            .get_user_by_email("taylorlaing121234@gmail.com", false)
+            .await
+            .expect("Failed to get user by email")
+            .expect("User not found");
+        assert_eq!(users.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn create_invalid_user_should_fail() {
+        let repository = Arc::new(InMemoryUserRepository::new());
+
+        let response = fn_handler::handle_request(
+            create_request("not-an-email"),
+            function_handler,
+            Some(repository.clone()),
+            Permission::UserCreate,
+        )
+        .await
+        .expect("Handler failed");
+
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let problem: Problem = serde_json::from_str(response.body()).expect("Invalid problem");
+        assert_eq!(problem.code, ProblemCode::ValidationFailed);
+        assert_eq!(
+            problem.errors,
+            vec![FieldError::new(
+                "Email",
+                "Invalid email address: not-an-email"
+            )]
+        );
+        assert!(repository
+            .get_user_by_email("not-an-email", true)
+            .await
+            .expect("Failed to get user by email")
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn concurrent_creates_with_same_email_should_create_one_user() {
+        let repository = Arc::new(InMemoryUserRepository::new());
+
+        let (first, second) = tokio::join!(
+            fn_handler::handle_request(
+                create_request("taylorlaing121234@gmail.com"),
+                function_handler,
+                Some(repository.clone()),
+                Permission::UserCreate,
+            ),
+            fn_handler::handle_request(
+                create_request("taylorlaing121234@gmail.com"),
+                function_handler,
+                Some(repository.clone()),
+                Permission::UserCreate,
+            ),
+        );
+
+        let mut statuses: Vec<StatusCode> = [first, second]
+            .into_iter()
+            .map(|response| response.expect("Handler failed").status())
+            .collect();
+        statuses.sort();
+
+        assert_eq!(statuses, vec![StatusCode::CREATED, StatusCode::CONFLICT]);
+    }
+}
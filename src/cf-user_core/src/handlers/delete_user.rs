@@ -0,0 +1,216 @@
+use super::super::{
+    config::Config, error::Error, etag, fn_handler, models::handler_response::HandleResponse,
+    repository::UserRepository,
+};
+use lambda_http::http::StatusCode;
+use lambda_http::{Request, RequestExt};
+use std::sync::Arc;
+
+pub async fn function_handler(
+    event: Request,
+    repository: Arc<dyn UserRepository>,
+) -> Result<HandleResponse, Box<dyn std::error::Error>> {
+    let parameters = event
+        .path_parameters_ref()
+        .expect("Missing path parameters.");
+
+    let expected_version = match etag::parse_if_match(&event, Config::from_env().require_if_match) {
+        Ok(expected_version) => expected_version,
+        Err(problem) => return Ok(HandleResponse::problem(*problem)),
+    };
+
+    match parameters.first("userId") {
+        Some(user_id) => {
+            let user = match repository.get_user_by_id(user_id, false).await {
+                Ok(user) => user,
+                Err(err) => {
+                    return Ok(fn_handler::error_response(
+                        "Error fetching user by ID",
+                        err.as_ref(),
+                    ));
+                }
+            };
+
+            if user.is_none() {
+                return Ok(HandleResponse::set_error(
+                    Some("User not found"),
+                    StatusCode::NOT_FOUND,
+                ));
+            }
+
+            match repository
+                .delete_user(
+                    user_id,
+                    expected_version,
+                    &fn_handler::change_context(&event),
+                )
+                .await
+            {
+                Ok(_success) => Ok(HandleResponse::no_content()),
+                Err(err) => match err.downcast_ref::<Error>() {
+                    Some(Error::PreconditionError(_)) => Ok(HandleResponse::set_error(
+                        Some("User has been modified since it was last read"),
+                        StatusCode::PRECONDITION_FAILED,
+                    )),
+                    _ => Ok(fn_handler::error_response(
+                        "Error deleting user by ID",
+                        err.as_ref(),
+                    )),
+                },
+            }
+        }
+        None => Ok(HandleResponse::error(Some(
+            "Error locating User ID within path parameters",
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod unit_tests {
+    use super::*;
+    use crate::{
+        args::create_user_args::CreateUserArgs,
+        mock_request::create_mock_request,
+        models::change_context::ChangeContext,
+        models::permissions::Permission,
+        models::problem::{Problem, ProblemCode},
+        repository::InMemoryUserRepository,
+    };
+    use lambda_http::{http, Body};
+    use std::collections::HashMap;
+
+    fn delete_request(user_id: &str) -> Request {
+        create_mock_request(
+            http::Method::DELETE,
+            &format!("https://dev-api.classifind.app/user/v1/users/{}", user_id),
+            Body::Empty,
+            &[Permission::UserDelete],
+        )
+        .with_path_parameters(HashMap::from([("userId".to_string(), user_id.to_string())]))
+    }
+
+    #[tokio::test]
+    async fn delete_user_by_id_should_succeed() {
+        let repository = Arc::new(InMemoryUserRepository::new());
+        let user_id = repository
+            .create_user(
+                CreateUserArgs {
+                    username: "taylorlaing".to_string(),
+                    first_name: None,
+                    last_name: None,
+                    email: "taylorlaing@gmail.com".to_string(),
+                    profile_photo: None,
+                    summary: None,
+                    phone_number: None,
+                },
+                &ChangeContext::new("tester"),
+            )
+            .await
+            .expect("Failed to seed user");
+
+        let response = fn_handler::handle_request(
+            delete_request(&user_id),
+            function_handler,
+            Some(repository.clone()),
+            Permission::UserDelete,
+        )
+        .await
+        .expect("Handler failed");
+
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        assert!(repository
+            .get_user_by_id(&user_id, false)
+            .await
+            .expect("Failed to get user")
+            .is_none());
+
+        let user = repository
+            .get_user_by_id(&user_id, true)
+            .await
+            .expect("Failed to get user")
+            .expect("Deleted user was purged immediately");
+        assert!(user.is_deleted());
+        assert!(user.expires_at.is_some());
+    }
+
+    #[tokio::test]
+    async fn delete_user_with_stale_if_match_should_fail_precondition() {
+        let repository = Arc::new(InMemoryUserRepository::new());
+        let user_id = repository
+            .create_user(
+                CreateUserArgs {
+                    username: "taylorlaing".to_string(),
+                    first_name: None,
+                    last_name: None,
+                    email: "taylorlaing@gmail.com".to_string(),
+                    profile_photo: None,
+                    summary: None,
+                    phone_number: None,
+                },
+                &ChangeContext::new("tester"),
+            )
+            .await
+            .expect("Failed to seed user");
+
+        let mut request = delete_request(&user_id);
+        request.headers_mut().insert(
+            http::header::IF_MATCH,
+            http::HeaderValue::from_static("\"2\""),
+        );
+
+        let response = fn_handler::handle_request(
+            request,
+            function_handler,
+            Some(repository.clone()),
+            Permission::UserDelete,
+        )
+        .await
+        .expect("Handler failed");
+
+        assert_eq!(response.status(), StatusCode::PRECONDITION_FAILED);
+        assert!(repository
+            .get_user_by_id(&user_id, false)
+            .await
+            .expect("Failed to get user")
+            .is_some());
+    }
+
+    #[tokio::test]
+    async fn delete_missing_user_should_fail() {
+        let repository = Arc::new(InMemoryUserRepository::new());
+        let user_id = repository
+            .create_user(
+                CreateUserArgs {
+                    username: "taylorlaing".to_string(),
+                    first_name: None,
+                    last_name: None,
+                    email: "taylorlaing@gmail.com".to_string(),
+                    profile_photo: None,
+                    summary: None,
+                    phone_number: None,
+                },
+                &ChangeContext::new("tester"),
+            )
+            .await
+            .expect("Failed to seed user");
+        repository
+            .delete_user(&user_id, None, &ChangeContext::new("tester"))
+            .await
+            .expect("Failed to delete user");
+
+        for user_id in ["01H5FS6FKMB0YY0VDJ015741BJ", user_id.as_str()] {
+            let response = fn_handler::handle_request(
+                delete_request(user_id),
+                function_handler,
+                Some(repository.clone()),
+                Permission::UserDelete,
+            )
+            .await
+            .expect("Handler failed");
+
+            assert_eq!(response.status(), StatusCode::NOT_FOUND);
+            let problem: Problem = serde_json::from_str(response.body()).expect("Invalid problem");
+            assert_eq!(problem.code, ProblemCode::NotFound);
+        }
+    }
+}
@@ -0,0 +1,347 @@
+use super::super::{
+    etag, fn_handler,
+    models::handler_response::HandleResponse,
+    models::partial_user::{PartialUser, UserView},
+    models::user::User,
+    repository::UserRepository,
+};
+use lambda_http::http::StatusCode;
+use lambda_http::{Request, RequestExt};
+use std::sync::Arc;
+use ulid::Ulid;
+
+pub async fn function_handler(
+    event: Request,
+    repository: Arc<dyn UserRepository>,
+) -> Result<HandleResponse<UserView>, Box<dyn std::error::Error>> {
+    let parameters = event
+        .path_parameters_ref()
+        .expect("Missing path parameters.");
+
+    let include_deleted = match fn_handler::parse_include_deleted(&event) {
+        Ok(include_deleted) => include_deleted,
+        Err(problem) => return Ok(HandleResponse::problem(*problem)),
+    };
+
+    let fields = match fn_handler::parse_fields(&event) {
+        Ok(fields) => fields,
+        Err(problem) => return Ok(HandleResponse::problem(*problem)),
+    };
+
+    match parameters.first("userId") {
+        Some(user_id) => {
+            let mut user: Option<User> = None;
+
+            if let (Ok(_uuid), Some(fields)) = (Ulid::from_string(user_id), &fields) {
+                return match repository
+                    .get_user_fields_by_id(user_id, include_deleted, fields)
+                    .await
+                {
+                    Ok(Some(user)) => Ok(partial_user_response(user)),
+                    Ok(None) => Ok(HandleResponse::set_error(
+                        Some("User not found"),
+                        StatusCode::NOT_FOUND,
+                    )),
+                    Err(err) => Ok(fn_handler::error_response(
+                        "Error fetching user by ID",
+                        err.as_ref(),
+                    )),
+                };
+            }
+
+            if let Ok(_uuid) = Ulid::from_string(user_id) {
+                user = match repository.get_user_by_id(user_id, include_deleted).await {
+                    // Ok(user) => user,
+                    Ok(user) => match user {
+                        Some(u) => Some(u),
+                        None => {
+                            return Ok(HandleResponse::set_error(
+                                Some("User not found"),
+                                StatusCode::NOT_FOUND,
+                            ))
+                        }
+                    },
+                    Err(err) => {
+                        return Ok(fn_handler::error_response(
+                            "Error fetching user by ID",
+                            err.as_ref(),
+                        ));
+                    }
+                };
+            } else {
+                let users = match repository.get_user_by_email(user_id, include_deleted).await {
+                    Ok(users) => users,
+                    Err(err) => {
+                        return Ok(fn_handler::error_response(
+                            "Error fetching user by email",
+                            err.as_ref(),
+                        ));
+                    }
+                };
+
+                if let Some(users) = users {
+                    if let Some(usr) = users.first() {
+                        user = Some(usr.clone());
+                    }
+                } else {
+                    return Ok(HandleResponse::set_error(
+                        Some("User not found"),
+                        StatusCode::NOT_FOUND,
+                    ));
+                }
+            }
+
+            if let (Some(user_obj), Some(fields)) = (&user, &fields) {
+                return Ok(partial_user_response(fields.select_user(user_obj)));
+            }
+
+            if let Some(user_obj) = user {
+                let etag = etag::format_etag(user_obj.version);
+
+                Ok(HandleResponse::success(UserView::Whole(Box::new(user_obj))).with_etag(&etag))
+            } else {
+                Ok(HandleResponse::error(Some("Error parsing user data")))
+            }
+        }
+        None => Ok(HandleResponse::error(Some(
+            "Error locating User ID within path parameters",
+        ))),
+    }
+}
+
+fn partial_user_response(user: PartialUser) -> HandleResponse<UserView> {
+    let etag = etag::format_etag(user.version);
+
+    HandleResponse::success(UserView::Partial(user)).with_etag(&etag)
+}
+
+#[cfg(test)]
+mod unit_tests {
+    use super::*;
+    use crate::{
+        args::create_user_args::CreateUserArgs,
+        mock_request::create_mock_request,
+        models::change_context::ChangeContext,
+        models::permissions::Permission,
+        models::problem::{Problem, ProblemCode},
+        repository::InMemoryUserRepository,
+    };
+    use lambda_http::{http, Body};
+    use std::collections::HashMap;
+
+    async fn seed_user(repository: &InMemoryUserRepository, email: &str) -> String {
+        repository
+            .create_user(
+                CreateUserArgs {
+                    username: "tester".to_string(),
+                    first_name: Some("Test".to_string()),
+                    last_name: Some("User".to_string()),
+                    email: email.to_string(),
+                    profile_photo: None,
+                    summary: None,
+                    phone_number: None,
+                },
+                &ChangeContext::new("tester"),
+            )
+            .await
+            .expect("Failed to seed user")
+    }
+
+    fn get_request(user_id: &str, permissions: &[Permission]) -> Request {
+        create_mock_request(
+            http::Method::GET,
+            &format!("https://dev-api.classifind.app/user/v1/users/{}", user_id),
+            Body::Empty,
+            permissions,
+        )
+        .with_path_parameters(HashMap::from([("userId".to_string(), user_id.to_string())]))
+    }
+
+    #[tokio::test]
+    async fn get_user_by_id_should_succeed() {
+        let repository = Arc::new(InMemoryUserRepository::new());
+        let user_id = seed_user(&repository, "tester@example.com").await;
+
+        let response = fn_handler::handle_request(
+            get_request(&user_id, &[Permission::UserGet]),
+            function_handler,
+            Some(repository),
+            Permission::UserGet,
+        )
+        .await
+        .expect("Handler failed");
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers().get("ETag").and_then(|v| v.to_str().ok()),
+            Some("\"1\"")
+        );
+
+        let user: User = serde_json::from_str(response.body()).expect("Invalid user body");
+        assert_eq!(user.user_id, user_id);
+        assert_eq!(user.version, 1);
+    }
+
+    #[tokio::test]
+    async fn get_user_by_email_should_succeed() {
+        let repository = Arc::new(InMemoryUserRepository::new());
+        let user_id = seed_user(&repository, "tester@example.com").await;
+
+        let response = fn_handler::handle_request(
+            get_request("tester@example.com", &[Permission::UserGet]),
+            function_handler,
+            Some(repository),
+            Permission::UserGet,
+        )
+        .await
+        .expect("Handler failed");
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let user: User = serde_json::from_str(response.body()).expect("Invalid user body");
+        assert_eq!(user.user_id, user_id);
+        assert_eq!(user.email, "tester@example.com");
+    }
+
+    #[tokio::test]
+    async fn get_missing_user_should_return_not_found() {
+        let repository = Arc::new(InMemoryUserRepository::new());
+
+        let response = fn_handler::handle_request(
+            get_request(&Ulid::new().to_string(), &[Permission::UserGet]),
+            function_handler,
+            Some(repository),
+            Permission::UserGet,
+        )
+        .await
+        .expect("Handler failed");
+
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert_eq!(
+            response.headers()["Content-Type"],
+            "application/problem+json"
+        );
+        let problem: Problem = serde_json::from_str(response.body()).expect("Invalid problem");
+        assert_eq!(problem.code, ProblemCode::NotFound);
+        assert_eq!(problem.status, 404);
+        assert_eq!(problem.instance.as_deref(), Some("mock-request-id"));
+    }
+
+    #[tokio::test]
+    async fn get_user_without_permission_should_be_forbidden() {
+        let repository = Arc::new(InMemoryUserRepository::new());
+        let user_id = seed_user(&repository, "tester@example.com").await;
+
+        let response = fn_handler::handle_request(
+            get_request(&user_id, &[Permission::UserList]),
+            function_handler,
+            Some(repository),
+            Permission::UserGet,
+        )
+        .await
+        .expect("Handler failed");
+
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    }
+
+    #[tokio::test]
+    async fn get_deleted_user_should_require_admin() {
+        let repository = Arc::new(InMemoryUserRepository::new());
+        let user_id = seed_user(&repository, "tester@example.com").await;
+        repository
+            .delete_user(&user_id, None, &ChangeContext::new("tester"))
+            .await
+            .expect("Failed to delete user");
+
+        let response = fn_handler::handle_request(
+            get_request(&user_id, &[Permission::UserGet]),
+            function_handler,
+            Some(repository.clone()),
+            Permission::UserGet,
+        )
+        .await
+        .expect("Handler failed");
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        let include_deleted = HashMap::from([("includeDeleted".to_string(), "true".to_string())]);
+
+        let response = fn_handler::handle_request(
+            get_request(&user_id, &[Permission::UserGet])
+                .with_query_string_parameters(include_deleted.clone()),
+            function_handler,
+            Some(repository.clone()),
+            Permission::UserGet,
+        )
+        .await
+        .expect("Handler failed");
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        let response = fn_handler::handle_request(
+            get_request(&user_id, &[Permission::UserGet, Permission::UserAdmin])
+                .with_query_string_parameters(include_deleted),
+            function_handler,
+            Some(repository),
+            Permission::UserGet,
+        )
+        .await
+        .expect("Handler failed");
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let user: User = serde_json::from_str(response.body()).expect("Invalid user body");
+        assert!(user.is_deleted());
+    }
+
+    #[tokio::test]
+    async fn get_user_with_fields_should_succeed() {
+        let repository = Arc::new(InMemoryUserRepository::new());
+        let user_id = seed_user(&repository, "tester@example.com").await;
+        let fields = HashMap::from([("fields".to_string(), "FirstName,Email".to_string())]);
+
+        for path in [user_id.as_str(), "tester@example.com"] {
+            let response = fn_handler::handle_request(
+                get_request(path, &[Permission::UserGet])
+                    .with_query_string_parameters(fields.clone()),
+                function_handler,
+                Some(repository.clone()),
+                Permission::UserGet,
+            )
+            .await
+            .expect("Handler failed");
+
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_eq!(
+                response.headers().get("ETag").and_then(|v| v.to_str().ok()),
+                Some("\"1\"")
+            );
+            let user: serde_json::Value =
+                serde_json::from_str(response.body()).expect("Invalid user body");
+            assert_eq!(
+                user,
+                serde_json::json!({
+                    "UserId": user_id,
+                    "FirstName": "Test",
+                    "Email": "tester@example.com"
+                })
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn get_user_with_unknown_fields_should_fail() {
+        let repository = Arc::new(InMemoryUserRepository::new());
+        let user_id = seed_user(&repository, "tester@example.com").await;
+
+        let response = fn_handler::handle_request(
+            get_request(&user_id, &[Permission::UserGet]).with_query_string_parameters(
+                HashMap::from([("fields".to_string(), "ExpiresAt".to_string())]),
+            ),
+            function_handler,
+            Some(repository),
+            Permission::UserGet,
+        )
+        .await
+        .expect("Handler failed");
+
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+}
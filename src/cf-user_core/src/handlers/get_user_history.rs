@@ -0,0 +1,220 @@
+use super::super::{
+    args::{list_user_history_args::ListUserHistoryArgs, list_users_args::SortOrder},
+    error, fn_handler,
+    models::{
+        handler_response::HandleResponse,
+        paginated_result::PaginatedResult,
+        problem::{Problem, ProblemCode},
+        user_history::UserHistory,
+    },
+    repository::UserRepository,
+};
+use lambda_http::http::StatusCode;
+use lambda_http::{Request, RequestExt};
+use std::sync::Arc;
+
+pub async fn function_handler(
+    event: Request,
+    repository: Arc<dyn UserRepository>,
+) -> Result<HandleResponse<PaginatedResult<UserHistory>>, Box<dyn std::error::Error>> {
+    let parameters = event
+        .path_parameters_ref()
+        .expect("Missing path parameters.");
+
+    let user_id = match parameters.first("userId") {
+        Some(user_id) => user_id,
+        None => {
+            return Ok(HandleResponse::error(Some(
+                "Error locating User ID within path parameters",
+            )))
+        }
+    };
+
+    let mut args = ListUserHistoryArgs::default();
+
+    if let Some(qp) = event.query_string_parameters_ref() {
+        if let Some(limit) = qp.first("limit") {
+            args.limit = match limit.parse::<i32>() {
+                Ok(limit) if limit > 0 => limit,
+                _ => return Ok(HandleResponse::error(Some("Invalid limit"))),
+            };
+        }
+
+        args.pagination_token = qp.first("paginationToken").map(|token| token.to_string());
+
+        if let Some(order) = qp.first("order") {
+            args.order = match order.parse::<SortOrder>() {
+                Ok(order) => order,
+                Err(err) => return Ok(HandleResponse::error(Some(err.as_str()))),
+            };
+        }
+    }
+
+    // History outlives a soft delete, so it stays readable until the user is purged
+    match repository.get_user_by_id(user_id, true).await {
+        Ok(Some(_user)) => {}
+        Ok(None) => {
+            return Ok(HandleResponse::set_error(
+                Some("User not found"),
+                StatusCode::NOT_FOUND,
+            ))
+        }
+        Err(err) => {
+            return Ok(fn_handler::error_response(
+                "Error fetching user by ID",
+                err.as_ref(),
+            ));
+        }
+    }
+
+    let history: PaginatedResult<UserHistory> = match repository
+        .list_user_history(user_id, args)
+        .await
+    {
+        Ok(history) => history,
+        Err(err) => {
+            return match err.downcast_ref::<error::Error>() {
+                Some(error::Error::InvalidToken(msg)) => Ok(HandleResponse::problem(
+                    Problem::with_status(ProblemCode::InvalidPageToken, StatusCode::BAD_REQUEST)
+                        .with_detail(msg),
+                )),
+                _ => Ok(fn_handler::error_response(
+                    "Error fetching user history",
+                    err.as_ref(),
+                )),
+            };
+        }
+    };
+
+    Ok(HandleResponse::success(history))
+}
+
+#[cfg(test)]
+mod unit_tests {
+    use super::*;
+    use crate::{
+        args::create_user_args::CreateUserArgs,
+        mock_request::{create_mock_request, MOCK_PRINCIPAL_ID},
+        models::change_context::ChangeContext,
+        models::permissions::Permission,
+        models::user_history::HistoryOperation,
+        repository::InMemoryUserRepository,
+    };
+    use lambda_http::{http, Body};
+    use serde::Deserialize;
+    use std::collections::HashMap;
+
+    #[derive(Deserialize)]
+    struct HistoryPage {
+        data: Vec<UserHistory>,
+        token: Option<String>,
+    }
+
+    fn history_request(user_id: &str, query: HashMap<String, String>) -> Request {
+        create_mock_request(
+            http::Method::GET,
+            &format!(
+                "https://dev-api.classifind.app/user/v1/users/{}/history",
+                user_id
+            ),
+            Body::Empty,
+            &[Permission::UserHistory],
+        )
+        .with_path_parameters(HashMap::from([("userId".to_string(), user_id.to_string())]))
+        .with_query_string_parameters(query)
+    }
+
+    #[tokio::test]
+    async fn get_user_history_should_succeed() {
+        let repository = Arc::new(InMemoryUserRepository::new());
+        let user_id = repository
+            .create_user(
+                CreateUserArgs {
+                    username: "taylorlaing".to_string(),
+                    first_name: None,
+                    last_name: None,
+                    email: "taylorlaing@gmail.com".to_string(),
+                    profile_photo: None,
+                    summary: None,
+                    phone_number: None,
+                },
+                &ChangeContext::new(MOCK_PRINCIPAL_ID),
+            )
+            .await
+            .expect("Failed to seed user");
+        repository
+            .delete_user(&user_id, None, &ChangeContext::new(MOCK_PRINCIPAL_ID))
+            .await
+            .expect("Failed to delete user");
+
+        let mut token: Option<String> = None;
+        let mut history: Vec<UserHistory> = Vec::new();
+
+        loop {
+            let mut query = HashMap::from([("limit".to_string(), "1".to_string())]);
+            if let Some(token) = token {
+                query.insert("paginationToken".to_string(), token);
+            }
+
+            let response = fn_handler::handle_request(
+                history_request(&user_id, query),
+                function_handler,
+                Some(repository.clone()),
+                Permission::UserHistory,
+            )
+            .await
+            .expect("Handler failed");
+
+            assert_eq!(response.status(), StatusCode::OK);
+
+            let page: HistoryPage =
+                serde_json::from_str(response.body()).expect("Invalid page body");
+            history.extend(page.data);
+
+            match page.token {
+                Some(next) => token = Some(next),
+                None => break,
+            }
+        }
+
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[0].operation, HistoryOperation::Delete);
+        assert_eq!(history[1].operation, HistoryOperation::Create);
+        assert!(history.iter().all(|h| h.actor == MOCK_PRINCIPAL_ID));
+    }
+
+    #[tokio::test]
+    async fn get_missing_user_history_should_return_not_found() {
+        let repository = Arc::new(InMemoryUserRepository::new());
+
+        let response = fn_handler::handle_request(
+            history_request("01H5FS6FKMB0YY0VDJ015741BJ", HashMap::new()),
+            function_handler,
+            Some(repository),
+            Permission::UserHistory,
+        )
+        .await
+        .expect("Handler failed");
+
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn get_user_history_with_invalid_order_should_fail() {
+        let repository = Arc::new(InMemoryUserRepository::new());
+
+        let response = fn_handler::handle_request(
+            history_request(
+                "01H5FS6FKMB0YY0VDJ015741BJ",
+                HashMap::from([("order".to_string(), "sideways".to_string())]),
+            ),
+            function_handler,
+            Some(repository),
+            Permission::UserHistory,
+        )
+        .await
+        .expect("Handler failed");
+
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+}
@@ -0,0 +1,159 @@
+use super::super::{
+    error::Error,
+    file_format::FileFormat,
+    fn_handler, import,
+    models::{handler_response::HandleResponse, import_report::ImportReport},
+    repository::UserRepository,
+};
+use lambda_http::http::{header::CONTENT_TYPE, StatusCode};
+use lambda_http::{Request, RequestExt};
+use std::sync::Arc;
+use tracing::info;
+
+pub async fn function_handler(
+    event: Request,
+    repository: Arc<dyn UserRepository>,
+) -> Result<HandleResponse<ImportReport>, Box<dyn std::error::Error>> {
+    let format = match event
+        .query_string_parameters_ref()
+        .and_then(|qp| qp.first("format"))
+    {
+        Some(format) => match format.parse::<FileFormat>() {
+            Ok(format) => format,
+            Err(err) => return Ok(HandleResponse::error(Some(err.as_str()))),
+        },
+        None => FileFormat::from_content_type(
+            event
+                .headers()
+                .get(CONTENT_TYPE)
+                .and_then(|value| value.to_str().ok()),
+        ),
+    };
+
+    let context = fn_handler::change_context(&event);
+    let body: &[u8] = event.body();
+
+    let report = match import::import_users(repository.as_ref(), body, format, &context).await {
+        Ok(report) => report,
+        Err(err) => {
+            return match err.downcast_ref::<Error>() {
+                Some(Error::ClientError(msg)) => Ok(HandleResponse::set_error(
+                    Some(msg),
+                    StatusCode::BAD_REQUEST,
+                )),
+                _ => Ok(fn_handler::error_response(
+                    "Error importing users",
+                    err.as_ref(),
+                )),
+            };
+        }
+    };
+
+    info!(
+        "Imported users: {} created, {} skipped, {} rejected",
+        report.created, report.skipped, report.rejected
+    );
+
+    Ok(HandleResponse::success(report))
+}
+
+#[cfg(test)]
+mod unit_tests {
+    use super::*;
+    use crate::{
+        mock_request::create_mock_request,
+        models::import_report::{ImportOutcome, ImportReport},
+        models::permissions::Permission,
+        repository::InMemoryUserRepository,
+    };
+    use lambda_http::{http, Body};
+
+    fn import_request(body: &str, content_type: &str) -> Request {
+        let mut request = create_mock_request(
+            http::Method::POST,
+            "https://dev-api.classifind.app/user/v1/users:import",
+            Body::from(body.to_string()),
+            &[Permission::UserImport],
+        );
+        request.headers_mut().insert(
+            CONTENT_TYPE,
+            http::HeaderValue::from_str(content_type).expect("Invalid content type"),
+        );
+
+        request
+    }
+
+    #[tokio::test]
+    async fn import_csv_should_succeed() {
+        let repository = Arc::new(InMemoryUserRepository::new());
+
+        let response = fn_handler::handle_request(
+            import_request(
+                "Username,Email\ntester,tester@example.com\nother,tester@example.com\n",
+                "text/csv",
+            ),
+            function_handler,
+            Some(repository.clone()),
+            Permission::UserImport,
+        )
+        .await
+        .expect("Handler failed");
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let report: ImportReport =
+            serde_json::from_str(response.body()).expect("Invalid report body");
+        assert_eq!(
+            report
+                .rows
+                .iter()
+                .map(|row| row.outcome)
+                .collect::<Vec<_>>(),
+            vec![ImportOutcome::Created, ImportOutcome::Skipped]
+        );
+        assert!(repository
+            .get_user_by_email("tester@example.com", false)
+            .await
+            .expect("Failed to get user")
+            .is_some());
+    }
+
+    #[tokio::test]
+    async fn import_ndjson_should_reject_invalid_rows() {
+        let repository = Arc::new(InMemoryUserRepository::new());
+
+        let response = fn_handler::handle_request(
+            import_request(
+                "{\"Username\":\"tester\",\"Email\":\"tester@example.com\"}\n{\"Username\":\"\"}\n",
+                "application/x-ndjson",
+            ),
+            function_handler,
+            Some(repository),
+            Permission::UserImport,
+        )
+        .await
+        .expect("Handler failed");
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let report: ImportReport =
+            serde_json::from_str(response.body()).expect("Invalid report body");
+        assert_eq!((report.created, report.rejected), (1, 1));
+    }
+
+    #[tokio::test]
+    async fn import_csv_without_header_should_fail() {
+        let repository = Arc::new(InMemoryUserRepository::new());
+
+        let response = fn_handler::handle_request(
+            import_request("tester,tester@example.com\n", "text/csv"),
+            function_handler,
+            Some(repository),
+            Permission::UserImport,
+        )
+        .await
+        .expect("Handler failed");
+
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+}
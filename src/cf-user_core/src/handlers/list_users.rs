@@ -0,0 +1,456 @@
+use super::super::{
+    args::list_users_args::{ListUsersArgs, SortOrder},
+    error, fn_handler,
+    models::{
+        handler_response::HandleResponse,
+        paginated_result::PaginatedResult,
+        partial_user::UserView,
+        problem::{Problem, ProblemCode},
+    },
+    repository::UserRepository,
+};
+use lambda_http::http::StatusCode;
+use lambda_http::{Request, RequestExt};
+use std::sync::Arc;
+
+pub async fn function_handler(
+    event: Request,
+    repository: Arc<dyn UserRepository>,
+) -> Result<HandleResponse<PaginatedResult<UserView>>, Box<dyn std::error::Error>> {
+    let mut args = ListUsersArgs {
+        include_deleted: match fn_handler::parse_include_deleted(&event) {
+            Ok(include_deleted) => include_deleted,
+            Err(problem) => return Ok(HandleResponse::problem(*problem)),
+        },
+        ..Default::default()
+    };
+
+    let fields = match fn_handler::parse_fields(&event) {
+        Ok(fields) => fields,
+        Err(problem) => return Ok(HandleResponse::problem(*problem)),
+    };
+
+    args.limit = match fn_handler::parse_limit(&event, args.limit) {
+        Ok(limit) => limit,
+        Err(problem) => return Ok(HandleResponse::problem(*problem)),
+    };
+
+    let query_parameters = event.query_string_parameters_ref();
+
+    if let Some(qp) = query_parameters {
+        args.pagination_token = qp.first("paginationToken").map(|token| token.to_string());
+
+        if let Some(order) = qp.first("order") {
+            args.order = match order.parse::<SortOrder>() {
+                Ok(order) => order,
+                Err(err) => return Ok(HandleResponse::error(Some(err.as_str()))),
+            };
+        }
+
+        if let Some(prefix) = qp.first("usernamePrefix") {
+            if prefix.trim().is_empty() {
+                return Ok(HandleResponse::error(Some(
+                    "usernamePrefix must not be empty",
+                )));
+            }
+            args.username_prefix = Some(prefix.to_string());
+        }
+    }
+
+    let paginated_users = match fields {
+        Some(fields) => repository
+            .list_user_fields(args, &fields)
+            .await
+            .map(|users| page_of(users, UserView::Partial)),
+        None => repository
+            .list_users(args)
+            .await
+            .map(|users| page_of(users, |user| UserView::Whole(Box::new(user)))),
+    };
+
+    match paginated_users {
+        Ok(users) => Ok(HandleResponse::success(users)),
+        Err(err) => match err.downcast_ref::<error::Error>() {
+            Some(error::Error::InvalidToken(msg)) => Ok(HandleResponse::problem(
+                Problem::with_status(ProblemCode::InvalidPageToken, StatusCode::BAD_REQUEST)
+                    .with_detail(msg),
+            )),
+            _ => Ok(fn_handler::error_response(
+                "Error listing users",
+                err.as_ref(),
+            )),
+        },
+    }
+}
+
+fn page_of<T>(page: PaginatedResult<T>, view: fn(T) -> UserView) -> PaginatedResult<UserView> {
+    PaginatedResult {
+        data: page.data.into_iter().map(view).collect(),
+        token: page.token,
+    }
+}
+
+#[cfg(test)]
+mod unit_tests {
+    use super::*;
+    use crate::{
+        args::create_user_args::CreateUserArgs, mock_request::create_mock_request,
+        models::change_context::ChangeContext, models::permissions::Permission, models::user::User,
+        repository::InMemoryUserRepository,
+    };
+    use lambda_http::{http, Body};
+    use serde::Deserialize;
+    use std::collections::HashMap;
+
+    #[derive(Deserialize)]
+    struct UserPage {
+        data: Vec<User>,
+        token: Option<String>,
+    }
+
+    fn list_request(limit: i32, pagination_token: Option<&str>, order: &str) -> Request {
+        let mut query = HashMap::from([
+            ("limit".to_string(), limit.to_string()),
+            ("order".to_string(), order.to_string()),
+        ]);
+        if let Some(token) = pagination_token {
+            query.insert("paginationToken".to_string(), token.to_string());
+        }
+
+        create_mock_request(
+            http::Method::GET,
+            "https://dev-api.classifind.app/user/v1/users",
+            Body::Empty,
+            &[Permission::UserList],
+        )
+        .with_query_string_parameters(query)
+    }
+
+    #[tokio::test]
+    async fn list_users_should_succeed() {
+        let repository = Arc::new(InMemoryUserRepository::new());
+
+        for i in 0..3 {
+            repository
+                .create_user(
+                    CreateUserArgs {
+                        username: format!("tester{}", i),
+                        first_name: None,
+                        last_name: None,
+                        email: format!("tester{}@example.com", i),
+                        profile_photo: None,
+                        summary: None,
+                        phone_number: None,
+                    },
+                    &ChangeContext::new("tester"),
+                )
+                .await
+                .expect("Failed to seed user");
+        }
+
+        let mut token: Option<String> = None;
+        let mut users: Vec<User> = Vec::new();
+
+        loop {
+            let response = fn_handler::handle_request(
+                list_request(2, token.as_deref(), "desc"),
+                function_handler,
+                Some(repository.clone()),
+                Permission::UserList,
+            )
+            .await
+            .expect("Handler failed");
+
+            assert_eq!(response.status(), StatusCode::OK);
+
+            let page: UserPage = serde_json::from_str(response.body()).expect("Invalid page body");
+            users.extend(page.data);
+
+            match page.token {
+                Some(next) => token = Some(next),
+                None => break,
+            }
+        }
+
+        assert_eq!(users.len(), 3);
+        assert!(users
+            .windows(2)
+            .all(|pair| pair[0].created_date >= pair[1].created_date));
+    }
+
+    #[tokio::test]
+    async fn list_users_by_username_prefix_should_succeed() {
+        let repository = Arc::new(InMemoryUserRepository::new());
+
+        for username in ["Tester", "tester2", "other"] {
+            repository
+                .create_user(
+                    CreateUserArgs {
+                        username: username.to_string(),
+                        first_name: None,
+                        last_name: None,
+                        email: format!("{}@example.com", username),
+                        profile_photo: None,
+                        summary: None,
+                        phone_number: None,
+                    },
+                    &ChangeContext::new("tester"),
+                )
+                .await
+                .expect("Failed to seed user");
+        }
+
+        let response = fn_handler::handle_request(
+            create_mock_request(
+                http::Method::GET,
+                "https://dev-api.classifind.app/user/v1/users",
+                Body::Empty,
+                &[Permission::UserList],
+            )
+            .with_query_string_parameters(HashMap::from([(
+                "usernamePrefix".to_string(),
+                "test".to_string(),
+            )])),
+            function_handler,
+            Some(repository),
+            Permission::UserList,
+        )
+        .await
+        .expect("Handler failed");
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let page: UserPage = serde_json::from_str(response.body()).expect("Invalid page body");
+        let usernames: Vec<String> = page.data.into_iter().map(|user| user.username).collect();
+        assert_eq!(usernames, vec!["Tester", "tester2"]);
+    }
+
+    #[tokio::test]
+    async fn list_users_with_invalid_order_should_fail() {
+        let repository = Arc::new(InMemoryUserRepository::new());
+
+        let response = fn_handler::handle_request(
+            list_request(2, None, "sideways"),
+            function_handler,
+            Some(repository),
+            Permission::UserList,
+        )
+        .await
+        .expect("Handler failed");
+
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn list_users_with_malformed_limit_should_fail() {
+        let repository = Arc::new(InMemoryUserRepository::new());
+
+        let response = fn_handler::handle_request(
+            list_request(2, None, "asc").with_query_string_parameters(HashMap::from([(
+                "limit".to_string(),
+                "abc".to_string(),
+            )])),
+            function_handler,
+            Some(repository),
+            Permission::UserList,
+        )
+        .await
+        .expect("Handler failed");
+
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert!(response.body().contains("limit must be a whole number"));
+    }
+
+    #[tokio::test]
+    async fn list_users_should_clamp_limit() {
+        let repository = Arc::new(InMemoryUserRepository::new());
+
+        for i in 0..2 {
+            repository
+                .create_user(
+                    CreateUserArgs {
+                        username: format!("tester{}", i),
+                        first_name: None,
+                        last_name: None,
+                        email: format!("tester{}@example.com", i),
+                        profile_photo: None,
+                        summary: None,
+                        phone_number: None,
+                    },
+                    &ChangeContext::new("tester"),
+                )
+                .await
+                .expect("Failed to seed user");
+        }
+
+        for (limit, expected) in [(0, 1), (-5, 1), (1_000_000, 2)] {
+            let response = fn_handler::handle_request(
+                list_request(limit, None, "asc"),
+                function_handler,
+                Some(repository.clone()),
+                Permission::UserList,
+            )
+            .await
+            .expect("Handler failed");
+
+            assert_eq!(response.status(), StatusCode::OK);
+            let page: UserPage = serde_json::from_str(response.body()).expect("Invalid page body");
+            assert_eq!(page.data.len(), expected);
+        }
+    }
+
+    #[tokio::test]
+    async fn list_users_with_malformed_token_should_fail() {
+        let repository = Arc::new(InMemoryUserRepository::new());
+
+        let response = fn_handler::handle_request(
+            list_request(2, Some("USER#1.USER#1"), "asc"),
+            function_handler,
+            Some(repository),
+            Permission::UserList,
+        )
+        .await
+        .expect("Handler failed");
+
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert!(response.body().contains("Pagination token"));
+    }
+
+    #[tokio::test]
+    async fn list_users_should_hide_deleted_users() {
+        let repository = Arc::new(InMemoryUserRepository::new());
+
+        let user_id = repository
+            .create_user(
+                CreateUserArgs {
+                    username: "tester".to_string(),
+                    first_name: None,
+                    last_name: None,
+                    email: "tester@example.com".to_string(),
+                    profile_photo: None,
+                    summary: None,
+                    phone_number: None,
+                },
+                &ChangeContext::new("tester"),
+            )
+            .await
+            .expect("Failed to seed user");
+        repository
+            .delete_user(&user_id, None, &ChangeContext::new("tester"))
+            .await
+            .expect("Failed to delete user");
+
+        let response = fn_handler::handle_request(
+            list_request(10, None, "asc"),
+            function_handler,
+            Some(repository.clone()),
+            Permission::UserList,
+        )
+        .await
+        .expect("Handler failed");
+
+        let page: UserPage = serde_json::from_str(response.body()).expect("Invalid page body");
+        assert!(page.data.is_empty());
+
+        let response = fn_handler::handle_request(
+            create_mock_request(
+                http::Method::GET,
+                "https://dev-api.classifind.app/user/v1/users",
+                Body::Empty,
+                &[Permission::UserList, Permission::UserAdmin],
+            )
+            .with_query_string_parameters(HashMap::from([(
+                "includeDeleted".to_string(),
+                "true".to_string(),
+            )])),
+            function_handler,
+            Some(repository),
+            Permission::UserList,
+        )
+        .await
+        .expect("Handler failed");
+
+        let page: UserPage = serde_json::from_str(response.body()).expect("Invalid page body");
+        assert_eq!(page.data.len(), 1);
+        assert!(page.data[0].is_deleted());
+    }
+
+    #[tokio::test]
+    async fn list_users_with_fields_should_succeed() {
+        let repository = Arc::new(InMemoryUserRepository::new());
+        let user_id = repository
+            .create_user(
+                CreateUserArgs {
+                    username: "tester".to_string(),
+                    first_name: Some("Test".to_string()),
+                    last_name: None,
+                    email: "tester@example.com".to_string(),
+                    profile_photo: Some("https://example.com/tester.png".to_string()),
+                    summary: None,
+                    phone_number: None,
+                },
+                &ChangeContext::new("tester"),
+            )
+            .await
+            .expect("Failed to seed user");
+
+        let request = create_mock_request(
+            http::Method::GET,
+            "https://dev-api.classifind.app/user/v1/users",
+            Body::Empty,
+            &[Permission::UserList],
+        )
+        .with_query_string_parameters(HashMap::from([(
+            "fields".to_string(),
+            "Username,ProfilePhoto".to_string(),
+        )]));
+
+        let response = fn_handler::handle_request(
+            request,
+            function_handler,
+            Some(repository),
+            Permission::UserList,
+        )
+        .await
+        .expect("Handler failed");
+
+        assert_eq!(response.status(), StatusCode::OK);
+        let page: serde_json::Value =
+            serde_json::from_str(response.body()).expect("Invalid page body");
+        assert_eq!(
+            page["data"],
+            serde_json::json!([{
+                "UserId": user_id,
+                "Username": "tester",
+                "ProfilePhoto": "https://example.com/tester.png"
+            }])
+        );
+    }
+
+    #[tokio::test]
+    async fn list_users_with_unknown_fields_should_fail() {
+        let repository = Arc::new(InMemoryUserRepository::new());
+
+        let request = create_mock_request(
+            http::Method::GET,
+            "https://dev-api.classifind.app/user/v1/users",
+            Body::Empty,
+            &[Permission::UserList],
+        )
+        .with_query_string_parameters(HashMap::from([(
+            "fields".to_string(),
+            "Username,GSI1PK".to_string(),
+        )]));
+
+        let response = fn_handler::handle_request(
+            request,
+            function_handler,
+            Some(repository),
+            Permission::UserList,
+        )
+        .await
+        .expect("Handler failed");
+
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+}
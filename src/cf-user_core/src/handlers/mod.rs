@@ -0,0 +1,9 @@
+pub mod batch_get_users;
+pub mod create_user;
+pub mod delete_user;
+pub mod get_user;
+pub mod get_user_history;
+pub mod import_users;
+pub mod list_users;
+pub mod restore_user;
+pub mod update_user;
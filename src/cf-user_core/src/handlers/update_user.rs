@@ -0,0 +1,331 @@
+use super::super::{
+    args::update_user_args::UpdateUserArgs,
+    config::Config,
+    error::Error,
+    etag, fn_handler,
+    models::{
+        handler_response::HandleResponse,
+        problem::{Problem, ProblemCode},
+    },
+    repository::UserRepository,
+};
+use lambda_http::http::StatusCode;
+use lambda_http::{Request, RequestExt};
+use std::sync::Arc;
+
+pub async fn function_handler(
+    event: Request,
+    repository: Arc<dyn UserRepository>,
+) -> Result<HandleResponse, Box<dyn std::error::Error>> {
+    let parameters = event
+        .path_parameters_ref()
+        .expect("Missing path parameters.");
+
+    let expected_version = match etag::parse_if_match(&event, Config::from_env().require_if_match) {
+        Ok(expected_version) => expected_version,
+        Err(problem) => return Ok(HandleResponse::problem(*problem)),
+    };
+
+    let body = event.body();
+    let s = std::str::from_utf8(body).expect("invalid utf-8 sequence");
+
+    let item = match serde_json::from_str::<UpdateUserArgs>(s) {
+        Ok(item) => item,
+        Err(_err) => {
+            return Ok(HandleResponse::error(Some(
+                "Error parsing incoming request object",
+            )));
+        }
+    };
+
+    let errors = item.field_errors();
+    if !errors.is_empty() {
+        return Ok(HandleResponse::problem(
+            Problem::with_status(ProblemCode::ValidationFailed, StatusCode::BAD_REQUEST)
+                .with_detail("Invalid user")
+                .with_errors(errors),
+        ));
+    }
+
+    match parameters.first("userId") {
+        Some(user_id) => {
+            let user = match repository.get_user_by_id(user_id, false).await {
+                Ok(user) => user,
+                Err(err) => {
+                    return Ok(fn_handler::error_response(
+                        "Error fetching user by ID",
+                        err.as_ref(),
+                    ));
+                }
+            };
+
+            if user.is_none() {
+                return Ok(HandleResponse::set_error(
+                    Some("User not found"),
+                    StatusCode::NOT_FOUND,
+                ));
+            }
+
+            match repository
+                .update_user(
+                    user_id,
+                    item.clone(),
+                    expected_version,
+                    &fn_handler::change_context(&event),
+                )
+                .await
+            {
+                Ok(_success) => Ok(HandleResponse::no_content()),
+                Err(err) => match err.downcast_ref::<Error>() {
+                    Some(Error::PreconditionError(_)) => Ok(HandleResponse::set_error(
+                        Some("User has been modified since it was last read"),
+                        StatusCode::PRECONDITION_FAILED,
+                    )),
+                    _ => Ok(fn_handler::error_response(
+                        "Error updating user by ID",
+                        err.as_ref(),
+                    )),
+                },
+            }
+        }
+        None => Ok(HandleResponse::error(Some(
+            "Error locating User ID within path parameters",
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod unit_tests {
+    use super::*;
+    use crate::{
+        args::create_user_args::CreateUserArgs, mock_request::create_mock_request,
+        models::change_context::ChangeContext, models::permissions::Permission,
+        models::problem::FieldError, repository::InMemoryUserRepository,
+    };
+    use lambda_http::http;
+    use serde_json::json;
+    use std::collections::HashMap;
+
+    fn update_request(user_id: &str) -> Request {
+        let user_request = json!({
+            "Username": "taylorlaing8",
+            "FirstName": "Taylor",
+            "LastName": "Laing",
+            "Email": "taylorlaing8@gmail.com",
+            "PhoneNumber": "8013911705",
+            "Summary": "UPDATE: Bruh this is gonna take forever..."
+        });
+
+        create_mock_request(
+            http::Method::PUT,
+            &format!("https://dev-api.classifind.app/user/v1/users/{}", user_id),
+            user_request.to_string().into(),
+            &[Permission::UserUpdate],
+        )
+        .with_path_parameters(HashMap::from([("userId".to_string(), user_id.to_string())]))
+    }
+
+    #[tokio::test]
+    async fn update_user_by_id_should_succeed() {
+        let repository = Arc::new(InMemoryUserRepository::new());
+        let user_id = repository
+            .create_user(
+                CreateUserArgs {
+                    username: "taylorlaing".to_string(),
+                    first_name: None,
+                    last_name: None,
+                    email: "taylorlaing@gmail.com".to_string(),
+                    profile_photo: None,
+                    summary: None,
+                    phone_number: None,
+                },
+                &ChangeContext::new("tester"),
+            )
+            .await
+            .expect("Failed to seed user");
+
+        let response = fn_handler::handle_request(
+            update_request(&user_id),
+            function_handler,
+            Some(repository.clone()),
+            Permission::UserUpdate,
+        )
+        .await
+        .expect("Handler failed");
+
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+        let user = repository
+            .get_user_by_id(&user_id, false)
+            .await
+            .expect("Failed to get user")
+            .expect("User not found");
+        assert_eq!(user.email, "taylorlaing8@gmail.com");
+        assert_eq!(user.username, "taylorlaing8");
+    }
+
+    #[tokio::test]
+    async fn update_user_with_stale_if_match_should_fail_precondition() {
+        let repository = Arc::new(InMemoryUserRepository::new());
+        let user_id = repository
+            .create_user(
+                CreateUserArgs {
+                    username: "taylorlaing".to_string(),
+                    first_name: None,
+                    last_name: None,
+                    email: "taylorlaing@gmail.com".to_string(),
+                    profile_photo: None,
+                    summary: None,
+                    phone_number: None,
+                },
+                &ChangeContext::new("tester"),
+            )
+            .await
+            .expect("Failed to seed user");
+
+        for (if_match, status) in [
+            ("\"1\"", StatusCode::NO_CONTENT),
+            ("\"1\"", StatusCode::PRECONDITION_FAILED),
+        ] {
+            let mut request = update_request(&user_id);
+            request.headers_mut().insert(
+                http::header::IF_MATCH,
+                http::HeaderValue::from_static(if_match),
+            );
+
+            let response = fn_handler::handle_request(
+                request,
+                function_handler,
+                Some(repository.clone()),
+                Permission::UserUpdate,
+            )
+            .await
+            .expect("Handler failed");
+
+            assert_eq!(response.status(), status);
+        }
+
+        let user = repository
+            .get_user_by_id(&user_id, false)
+            .await
+            .expect("Failed to get user")
+            .expect("User not found");
+        assert_eq!(user.version, 2);
+    }
+
+    #[tokio::test]
+    async fn update_user_to_taken_email_should_conflict() {
+        let repository = Arc::new(InMemoryUserRepository::new());
+
+        for (username, email) in [
+            ("taylorlaing", "taylorlaing@gmail.com"),
+            ("someoneelse", "taylorlaing8@gmail.com"),
+        ] {
+            repository
+                .create_user(
+                    CreateUserArgs {
+                        username: username.to_string(),
+                        first_name: None,
+                        last_name: None,
+                        email: email.to_string(),
+                        profile_photo: None,
+                        summary: None,
+                        phone_number: None,
+                    },
+                    &ChangeContext::new("tester"),
+                )
+                .await
+                .expect("Failed to seed user");
+        }
+
+        let user = repository
+            .get_user_by_email("taylorlaing@gmail.com", false)
+            .await
+            .expect("Failed to get user by email")
+            .and_then(|users| users.first().cloned())
+            .expect("User not found");
+
+        let response = fn_handler::handle_request(
+            update_request(&user.user_id),
+            function_handler,
+            Some(repository.clone()),
+            Permission::UserUpdate,
+        )
+        .await
+        .expect("Handler failed");
+
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+    }
+
+    #[tokio::test]
+    async fn update_user_with_invalid_fields_should_fail() {
+        let repository = Arc::new(InMemoryUserRepository::new());
+        let user_id = repository
+            .create_user(
+                CreateUserArgs {
+                    username: "taylorlaing".to_string(),
+                    first_name: None,
+                    last_name: None,
+                    email: "taylorlaing@gmail.com".to_string(),
+                    profile_photo: None,
+                    summary: None,
+                    phone_number: None,
+                },
+                &ChangeContext::new("tester"),
+            )
+            .await
+            .expect("Failed to seed user");
+        let len = repository.len();
+
+        let request = create_mock_request(
+            http::Method::PUT,
+            &format!("https://dev-api.classifind.app/user/v1/users/{}", user_id),
+            json!({ "Username": "  ", "Email": "not-an-email" })
+                .to_string()
+                .into(),
+            &[Permission::UserUpdate],
+        )
+        .with_path_parameters(HashMap::from([("userId".to_string(), user_id.clone())]));
+
+        let response = fn_handler::handle_request(
+            request,
+            function_handler,
+            Some(repository.clone()),
+            Permission::UserUpdate,
+        )
+        .await
+        .expect("Handler failed");
+
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let problem: Problem = serde_json::from_str(response.body()).expect("Invalid problem");
+        assert_eq!(problem.code, ProblemCode::ValidationFailed);
+        assert_eq!(
+            problem.errors,
+            vec![
+                FieldError::new("Username", "Username is required"),
+                FieldError::new("Email", "Invalid email address: not-an-email"),
+            ]
+        );
+        assert_eq!(repository.len(), len);
+        assert!(repository.get_item("USERNAME#  ", "USERNAME#  ").is_none());
+    }
+
+    #[tokio::test]
+    async fn update_missing_user_should_fail() {
+        let repository = Arc::new(InMemoryUserRepository::new());
+
+        let response = fn_handler::handle_request(
+            update_request("01H2PFJW211F4A7D98DS551H2M"),
+            function_handler,
+            Some(repository.clone()),
+            Permission::UserUpdate,
+        )
+        .await
+        .expect("Handler failed");
+
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        let problem: Problem = serde_json::from_str(response.body()).expect("Invalid problem");
+        assert_eq!(problem.code, ProblemCode::NotFound);
+        assert!(repository.is_empty());
+    }
+}
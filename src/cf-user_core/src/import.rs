@@ -0,0 +1,542 @@
+use super::{
+    args::create_user_args::CreateUserArgs,
+    dynamo,
+    error::{Error, UniqueField},
+    file_format::{parse_csv_record, FileFormat},
+    models::change_context::ChangeContext,
+    models::import_report::{ImportOutcome, ImportReport, ImportRowResult},
+    repository::UserRepository,
+};
+
+use futures::future::join_all;
+use std::collections::HashMap;
+use std::io::BufRead;
+
+/// Users created at once. Each is written in its own transaction by `create_user`,
+/// so its guards keep the email and username unique even against concurrent writes.
+static IMPORT_BATCH_SIZE: usize = 25;
+
+/// A row and the line it starts on. Rows that cannot be parsed carry the reason.
+type ImportRow = (usize, Result<CreateUserArgs, String>);
+
+/// Reads `CreateUserArgs` from the rows of an import, one at a time, so the import
+/// can report rows that cannot be parsed and carry on.
+struct ImportRows<R> {
+    reader: R,
+    format: FileFormat,
+    line: usize,
+    columns: Option<Vec<Option<CsvColumn>>>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum CsvColumn {
+    Username,
+    FirstName,
+    LastName,
+    Email,
+    ProfilePhoto,
+    Summary,
+    PhoneNumber,
+}
+
+impl CsvColumn {
+    fn from_header(header: &str) -> Option<Self> {
+        match header
+            .trim()
+            .to_lowercase()
+            .replace(['_', ' '], "")
+            .as_str()
+        {
+            "username" => Some(CsvColumn::Username),
+            "firstname" => Some(CsvColumn::FirstName),
+            "lastname" => Some(CsvColumn::LastName),
+            "email" => Some(CsvColumn::Email),
+            "profilephoto" => Some(CsvColumn::ProfilePhoto),
+            "summary" => Some(CsvColumn::Summary),
+            "phonenumber" => Some(CsvColumn::PhoneNumber),
+            _ => None,
+        }
+    }
+}
+
+impl<R: BufRead> ImportRows<R> {
+    fn new(reader: R, format: FileFormat) -> Self {
+        Self {
+            reader,
+            format,
+            line: 0,
+            columns: None,
+        }
+    }
+
+    /// Reads the next non-blank record and the line it starts on. A CSV record keeps
+    /// reading lines while a quoted field is open.
+    fn next_record(&mut self) -> Result<Option<(usize, String)>, Error> {
+        loop {
+            let mut record = String::new();
+            if !self.read_line(&mut record)? {
+                return Ok(None);
+            }
+            let start = self.line;
+
+            if self.format == FileFormat::Csv {
+                while record.matches('"').count() % 2 == 1 {
+                    record.push('\n');
+                    if !self.read_line(&mut record)? {
+                        break;
+                    }
+                }
+            }
+
+            if !record.trim().is_empty() {
+                return Ok(Some((start, record)));
+            }
+        }
+    }
+
+    fn read_line(&mut self, buffer: &mut String) -> Result<bool, Error> {
+        let mut line = String::new();
+        let read = self
+            .reader
+            .read_line(&mut line)
+            .map_err(|err| Error::SdkError(format!("Error reading import: {}", err)))?;
+        if read == 0 {
+            return Ok(false);
+        }
+
+        self.line += 1;
+        if self.line == 1 {
+            line = line.trim_start_matches('\u{feff}').to_string();
+        }
+        buffer.push_str(line.trim_end_matches(['\r', '\n']));
+
+        Ok(true)
+    }
+
+    fn next_row(&mut self) -> Result<Option<ImportRow>, Error> {
+        if self.format == FileFormat::Csv && self.columns.is_none() {
+            let header = match self.next_record()? {
+                Some((_, header)) => header,
+                None => return Ok(None),
+            };
+            let columns: Vec<Option<CsvColumn>> = parse_csv_record(&header)
+                .map_err(|_| Error::ClientError("CSV header is malformed"))?
+                .iter()
+                .map(|name| CsvColumn::from_header(name))
+                .collect();
+
+            for required in [CsvColumn::Username, CsvColumn::Email] {
+                if !columns.contains(&Some(required)) {
+                    return Err(Error::ClientError(
+                        "CSV header must name the Username and Email columns",
+                    ));
+                }
+            }
+
+            self.columns = Some(columns);
+        }
+
+        let (line, record) = match self.next_record()? {
+            Some(record) => record,
+            None => return Ok(None),
+        };
+
+        let row = match self.format {
+            FileFormat::Ndjson => serde_json::from_str::<CreateUserArgs>(&record)
+                .map_err(|err| format!("Invalid JSON: {}", err)),
+            FileFormat::Csv => self.parse_csv_row(&record),
+        };
+
+        Ok(Some((line, row)))
+    }
+
+    fn parse_csv_row(&self, record: &str) -> Result<CreateUserArgs, String> {
+        let columns = self.columns.as_deref().unwrap_or_default();
+        let fields = parse_csv_record(record)?;
+        if fields.len() != columns.len() {
+            return Err(format!(
+                "Expected {} fields but found {}",
+                columns.len(),
+                fields.len()
+            ));
+        }
+
+        let mut args = CreateUserArgs {
+            username: String::new(),
+            first_name: None,
+            last_name: None,
+            email: String::new(),
+            profile_photo: None,
+            summary: None,
+            phone_number: None,
+        };
+
+        for (column, field) in columns.iter().zip(fields) {
+            let value = Some(field).filter(|field| !field.is_empty());
+            match column {
+                Some(CsvColumn::Username) => args.username = value.unwrap_or_default(),
+                Some(CsvColumn::Email) => args.email = value.unwrap_or_default(),
+                Some(CsvColumn::FirstName) => args.first_name = value,
+                Some(CsvColumn::LastName) => args.last_name = value,
+                Some(CsvColumn::ProfilePhoto) => args.profile_photo = value,
+                Some(CsvColumn::Summary) => args.summary = value,
+                Some(CsvColumn::PhoneNumber) => args.phone_number = value,
+                None => {}
+            }
+        }
+
+        Ok(args)
+    }
+}
+
+/// Imports users from NDJSON or CSV rows, a batch at a time so large files are never
+/// held in memory.
+///
+/// Each row is validated, then skipped if its email or username already appeared
+/// earlier in the file, is registered in GSI1 or is reserved by a guard item. The
+/// rest are created a batch at a time. Only failures to read the input or check for
+/// duplicates fail the import; problems with a row, including a failure to write it,
+/// are recorded in the report.
+pub async fn import_users<R: BufRead>(
+    repository: &dyn UserRepository,
+    reader: R,
+    format: FileFormat,
+    context: &ChangeContext,
+) -> Result<ImportReport, Box<dyn std::error::Error>> {
+    let mut rows = ImportRows::new(reader, format);
+    let mut report = ImportReport::default();
+
+    let mut seen_emails: HashMap<String, usize> = HashMap::new();
+    let mut seen_usernames: HashMap<String, usize> = HashMap::new();
+    let mut pending: Vec<(usize, CreateUserArgs)> = Vec::new();
+
+    while let Some((line, row)) = rows.next_row()? {
+        let args = match row.and_then(|args| args.validate().map(|_| args)) {
+            Ok(args) => args,
+            Err(reason) => {
+                report.push(row_result(
+                    line,
+                    ImportOutcome::Rejected,
+                    None,
+                    None,
+                    reason,
+                ));
+                continue;
+            }
+        };
+
+        if let Some(reason) = duplicate_reason(
+            repository,
+            &args,
+            &mut seen_emails,
+            &mut seen_usernames,
+            line,
+        )
+        .await?
+        {
+            report.push(row_result(
+                line,
+                ImportOutcome::Skipped,
+                None,
+                Some(args.email),
+                reason,
+            ));
+            continue;
+        }
+
+        pending.push((line, args));
+        if pending.len() == IMPORT_BATCH_SIZE {
+            write_batch(repository, &mut pending, context, &mut report).await;
+        }
+    }
+    write_batch(repository, &mut pending, context, &mut report).await;
+
+    report.rows.sort_by_key(|row| row.line);
+
+    Ok(report)
+}
+
+/// Why a valid row cannot be created, if its email or username is already taken.
+/// Otherwise records both as seen at `line`.
+async fn duplicate_reason(
+    repository: &dyn UserRepository,
+    args: &CreateUserArgs,
+    seen_emails: &mut HashMap<String, usize>,
+    seen_usernames: &mut HashMap<String, usize>,
+    line: usize,
+) -> Result<Option<String>, Box<dyn std::error::Error>> {
+    let email_key = dynamo::email_key(&args.email);
+    if let Some(first) = seen_emails.get(&email_key) {
+        return Ok(Some(format!("Duplicate email of line {}", first)));
+    }
+    if let Some(first) = seen_usernames.get(&args.username) {
+        return Ok(Some(format!("Duplicate username of line {}", first)));
+    }
+
+    if repository
+        .get_user_by_email(&args.email, true)
+        .await?
+        .is_some()
+        || repository
+            .get_unique_value_owner(UniqueField::Email, &args.email)
+            .await?
+            .is_some()
+    {
+        return Ok(Some(format!(
+            "User record exists with matching {}",
+            UniqueField::Email
+        )));
+    }
+    if repository
+        .get_unique_value_owner(UniqueField::Username, &args.username)
+        .await?
+        .is_some()
+    {
+        return Ok(Some(format!(
+            "User record exists with matching {}",
+            UniqueField::Username
+        )));
+    }
+
+    seen_emails.insert(email_key, line);
+    seen_usernames.insert(args.username.clone(), line);
+
+    Ok(None)
+}
+
+/// Creates the pending users concurrently and reports each row on its own. A row whose
+/// email or username was taken since it was checked is skipped, and one that could
+/// not be written is rejected with the error, so the report always says which users
+/// exist.
+async fn write_batch(
+    repository: &dyn UserRepository,
+    pending: &mut Vec<(usize, CreateUserArgs)>,
+    context: &ChangeContext,
+    report: &mut ImportReport,
+) {
+    let rows = join_all(pending.drain(..).map(|(line, input)| async move {
+        let email = input.email.clone();
+
+        match repository.create_user(input, context).await {
+            Ok(user_id) => ImportRowResult {
+                line,
+                outcome: ImportOutcome::Created,
+                user_id: Some(user_id),
+                email: Some(email),
+                reason: None,
+            },
+            Err(err) => match err.downcast_ref::<Error>() {
+                Some(Error::ConflictError(field)) => row_result(
+                    line,
+                    ImportOutcome::Skipped,
+                    None,
+                    Some(email),
+                    format!("User record exists with matching {}", field),
+                ),
+                _ => row_result(
+                    line,
+                    ImportOutcome::Rejected,
+                    None,
+                    Some(email),
+                    err.to_string(),
+                ),
+            },
+        }
+    }))
+    .await;
+
+    rows.into_iter().for_each(|row| report.push(row));
+}
+
+fn row_result(
+    line: usize,
+    outcome: ImportOutcome,
+    user_id: Option<String>,
+    email: Option<String>,
+    reason: String,
+) -> ImportRowResult {
+    ImportRowResult {
+        line,
+        outcome,
+        user_id,
+        email,
+        reason: Some(reason),
+    }
+}
+
+#[cfg(test)]
+mod unit_tests {
+    use super::*;
+    use crate::repository::InMemoryUserRepository;
+
+    #[tokio::test]
+    async fn should_import_csv_with_multiline_fields() {
+        let repository = InMemoryUserRepository::new();
+        let csv = "\u{feff}Username,Email,Summary,Unused\r\n\
+                   tester,tester@example.com,\"Likes\r\ncommas, and quotes\",x\r\n\
+                   \r\n\
+                   other,other@example.com,,x\r\n";
+
+        let report = import_users(
+            &repository,
+            csv.as_bytes(),
+            FileFormat::Csv,
+            &ChangeContext::new("admin"),
+        )
+        .await
+        .expect("Import failed");
+
+        assert_eq!(report.created, 2);
+        assert_eq!(
+            report
+                .rows
+                .iter()
+                .map(|row| row.line)
+                .collect::<Vec<usize>>(),
+            vec![2, 5]
+        );
+
+        let user = repository
+            .get_user_by_email("tester@example.com", false)
+            .await
+            .expect("Failed to get user")
+            .expect("Imported user not found");
+        assert_eq!(
+            user[0].summary.as_deref(),
+            Some("Likes\ncommas, and quotes")
+        );
+    }
+
+    #[tokio::test]
+    async fn should_report_created_skipped_and_rejected_rows() {
+        let repository = InMemoryUserRepository::new();
+        repository
+            .create_user(
+                CreateUserArgs {
+                    username: "existing".to_string(),
+                    first_name: None,
+                    last_name: None,
+                    email: "existing@example.com".to_string(),
+                    profile_photo: None,
+                    summary: None,
+                    phone_number: None,
+                },
+                &ChangeContext::new("admin"),
+            )
+            .await
+            .expect("Failed to seed user");
+
+        let ndjson = [
+            r#"{"Username":"first","Email":"first@example.com"}"#,
+            r#"{"Username":"second","Email":"first@example.com"}"#,
+            r#"{"Username":"existing","Email":"new@example.com"}"#,
+            r#"{"Username":"third","Email":"existing@example.com"}"#,
+            r#"{"Username":"fourth","Email":"not-an-email"}"#,
+            r#"{"Username":"fifth""#,
+            r#"{"Username":"sixth","Email":"sixth@example.com","FirstName":"Six"}"#,
+        ]
+        .join("\n");
+
+        let report = import_users(
+            &repository,
+            ndjson.as_bytes(),
+            FileFormat::Ndjson,
+            &ChangeContext::new("admin"),
+        )
+        .await
+        .expect("Import failed");
+
+        let outcomes: Vec<ImportOutcome> = report.rows.iter().map(|row| row.outcome).collect();
+        assert_eq!(
+            outcomes,
+            vec![
+                ImportOutcome::Created,
+                ImportOutcome::Skipped,
+                ImportOutcome::Skipped,
+                ImportOutcome::Skipped,
+                ImportOutcome::Rejected,
+                ImportOutcome::Rejected,
+                ImportOutcome::Created,
+            ]
+        );
+        assert_eq!((report.created, report.skipped, report.rejected), (2, 3, 2));
+        assert_eq!(
+            report.rows[1].reason.as_deref(),
+            Some("Duplicate email of line 1")
+        );
+
+        let user_id = report.rows[6].user_id.clone().expect("Missing user ID");
+        let user = repository
+            .get_user_by_id(&user_id, false)
+            .await
+            .expect("Failed to get user")
+            .expect("Imported user not found");
+        assert_eq!(user.first_name.as_deref(), Some("Six"));
+        assert!(repository
+            .get_unique_value_owner(UniqueField::Username, "sixth")
+            .await
+            .expect("Failed to get guard")
+            .is_some());
+    }
+
+    #[tokio::test]
+    async fn should_skip_rows_taken_after_they_were_checked() {
+        let repository = InMemoryUserRepository::new();
+        let args = |username: &str, email: &str| CreateUserArgs {
+            username: username.to_string(),
+            first_name: None,
+            last_name: None,
+            email: email.to_string(),
+            profile_photo: None,
+            summary: None,
+            phone_number: None,
+        };
+
+        // Checked as free, then taken through the API before the batch is written
+        let mut pending = vec![
+            (1, args("first", "first@example.com")),
+            (2, args("second", "second@example.com")),
+        ];
+        repository
+            .create_user(
+                args("other", "second@example.com"),
+                &ChangeContext::new("api"),
+            )
+            .await
+            .expect("Failed to create user");
+
+        let mut report = ImportReport::default();
+        write_batch(
+            &repository,
+            &mut pending,
+            &ChangeContext::new("admin"),
+            &mut report,
+        )
+        .await;
+
+        assert_eq!((report.created, report.skipped, report.rejected), (1, 1, 0));
+        assert_eq!(
+            report.rows[1].reason.as_deref(),
+            Some("User record exists with matching email address")
+        );
+        assert!(repository
+            .get_unique_value_owner(UniqueField::Username, "second")
+            .await
+            .expect("Failed to get guard")
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn should_fail_csv_without_required_columns() {
+        let repository = InMemoryUserRepository::new();
+
+        import_users(
+            &repository,
+            "Username,FirstName\ntester,Test\n".as_bytes(),
+            FileFormat::Csv,
+            &ChangeContext::new("admin"),
+        )
+        .await
+        .expect_err("CSV without an Email column was imported");
+    }
+}
@@ -0,0 +1,243 @@
+use super::error::Error;
+use super::timestamp;
+use chrono::{DateTime, Utc};
+use std::collections::{BTreeMap, HashMap};
+
+pub use aws_sdk_dynamodb::types::AttributeValue;
+pub use cf_user_derive::DynamoItem;
+
+pub type Item = HashMap<String, AttributeValue>;
+
+/// A struct stored as a DynamoDB item, usually implemented with
+/// `#[derive(DynamoItem)]`. See `cf_user_derive` for the attribute options.
+pub trait DynamoItem: Sized {
+    /// Names of the attributes written from fields, in declaration order. Keys and
+    /// flattened fields are not included.
+    const ATTRIBUTES: &'static [&'static str];
+
+    fn to_item(&self) -> Item;
+    fn from_item(item: Item) -> Result<Self, Error>;
+}
+
+/// Conversion of one field to and from the attribute that stores it.
+pub trait AttributeField: Sized {
+    /// The attribute for the field, or `None` to leave it out of the item.
+    fn to_attribute(&self) -> Option<AttributeValue>;
+
+    /// Reads the field, returning `None` when the attribute is missing or has the
+    /// wrong type.
+    fn from_attribute(value: Option<AttributeValue>) -> Option<Self>;
+}
+
+impl AttributeField for String {
+    fn to_attribute(&self) -> Option<AttributeValue> {
+        Some(AttributeValue::S(self.clone()))
+    }
+
+    fn from_attribute(value: Option<AttributeValue>) -> Option<Self> {
+        match value? {
+            AttributeValue::S(value) => Some(value),
+            _ => None,
+        }
+    }
+}
+
+macro_rules! number_field {
+    ($($ty:ty),*) => {
+        $(
+            impl AttributeField for $ty {
+                fn to_attribute(&self) -> Option<AttributeValue> {
+                    Some(AttributeValue::N(self.to_string()))
+                }
+
+                fn from_attribute(value: Option<AttributeValue>) -> Option<Self> {
+                    value?.as_n().ok()?.parse().ok()
+                }
+            }
+        )*
+    };
+}
+
+number_field!(i32, i64, u32, u64);
+
+impl AttributeField for bool {
+    fn to_attribute(&self) -> Option<AttributeValue> {
+        Some(AttributeValue::Bool(*self))
+    }
+
+    fn from_attribute(value: Option<AttributeValue>) -> Option<Self> {
+        value?.as_bool().ok().copied()
+    }
+}
+
+/// Stored as `timestamp::format` writes it. Legacy formats are still read.
+impl AttributeField for DateTime<Utc> {
+    fn to_attribute(&self) -> Option<AttributeValue> {
+        Some(AttributeValue::S(timestamp::format(self)))
+    }
+
+    fn from_attribute(value: Option<AttributeValue>) -> Option<Self> {
+        timestamp::parse(value?.as_s().ok()?)
+    }
+}
+
+/// `None` is left out of the item. A missing or `NULL` attribute reads as `None`.
+impl<T: AttributeField> AttributeField for Option<T> {
+    fn to_attribute(&self) -> Option<AttributeValue> {
+        self.as_ref()?.to_attribute()
+    }
+
+    fn from_attribute(value: Option<AttributeValue>) -> Option<Self> {
+        match value {
+            None | Some(AttributeValue::Null(_)) => Some(None),
+            value => T::from_attribute(value).map(Some),
+        }
+    }
+}
+
+/// A map attribute holding an item, such as a key.
+impl AttributeField for Item {
+    fn to_attribute(&self) -> Option<AttributeValue> {
+        Some(AttributeValue::M(self.clone()))
+    }
+
+    fn from_attribute(value: Option<AttributeValue>) -> Option<Self> {
+        match value? {
+            AttributeValue::M(item) => Some(item),
+            _ => None,
+        }
+    }
+}
+
+/// A map attribute of nested items.
+impl<T: DynamoItem> AttributeField for BTreeMap<String, T> {
+    fn to_attribute(&self) -> Option<AttributeValue> {
+        Some(AttributeValue::M(
+            self.iter()
+                .map(|(name, value)| (name.to_owned(), AttributeValue::M(value.to_item())))
+                .collect(),
+        ))
+    }
+
+    fn from_attribute(value: Option<AttributeValue>) -> Option<Self> {
+        match value? {
+            AttributeValue::M(items) => items
+                .into_iter()
+                .map(|(name, value)| match value {
+                    AttributeValue::M(item) => Some((name, T::from_item(item).ok()?)),
+                    _ => None,
+                })
+                .collect(),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod unit_tests {
+    use super::*;
+    use crate::ext::AttributeValuesExt;
+
+    #[derive(Clone, Debug, Default, PartialEq, DynamoItem)]
+    #[dynamo(
+        rename_all = "PascalCase",
+        key(names = ["PK", "SK"], field = "widget_id", prefix = "WIDGET#")
+    )]
+    struct Widget {
+        widget_id: String,
+        display_name: Option<String>,
+        #[dynamo(rename = "Qty")]
+        quantity: u64,
+        #[dynamo(default)]
+        enabled: bool,
+        #[dynamo(skip)]
+        cached: Option<String>,
+    }
+
+    #[derive(Clone, Debug, PartialEq, DynamoItem)]
+    struct Extras {
+        #[dynamo(rename = "Name")]
+        name: String,
+        #[dynamo(flatten)]
+        rest: Item,
+    }
+
+    #[test]
+    fn should_write_keys_renames_and_omit_none() {
+        let widget = Widget {
+            widget_id: "w1".to_string(),
+            display_name: None,
+            quantity: 3,
+            enabled: true,
+            cached: Some("ignored".to_string()),
+        };
+
+        let item = Item::from(&widget);
+
+        assert_eq!(item.get_s("PK"), "WIDGET#w1");
+        assert_eq!(item.get_s("SK"), "WIDGET#w1");
+        assert_eq!(item.get_s("WidgetId"), "w1");
+        assert_eq!(item.get("Qty"), Some(&AttributeValue::N("3".to_string())));
+        assert_eq!(item.get("Enabled"), Some(&AttributeValue::Bool(true)));
+        assert!(!item.contains_key("DisplayName"));
+        assert!(!item.contains_key("Cached"));
+        assert_eq!(
+            Widget::ATTRIBUTES,
+            ["WidgetId", "DisplayName", "Qty", "Enabled"]
+        );
+
+        assert_eq!(
+            Widget::try_from(item).expect("Invalid item"),
+            Widget {
+                cached: None,
+                ..widget
+            }
+        );
+    }
+
+    #[test]
+    fn should_read_null_as_none_and_apply_defaults() {
+        let item = Item::from([
+            ("WidgetId".to_string(), AttributeValue::S("w1".to_string())),
+            ("DisplayName".to_string(), AttributeValue::Null(true)),
+            ("Qty".to_string(), AttributeValue::N("0".to_string())),
+        ]);
+
+        let widget = Widget::try_from(item).expect("Invalid item");
+
+        assert_eq!(widget.display_name, None);
+        assert!(!widget.enabled);
+    }
+
+    #[test]
+    fn should_reject_missing_or_mistyped_attributes() {
+        let missing = Item::from([("WidgetId".to_string(), AttributeValue::S("w1".to_string()))]);
+        let mistyped = Item::from([
+            ("WidgetId".to_string(), AttributeValue::S("w1".to_string())),
+            ("Qty".to_string(), AttributeValue::S("three".to_string())),
+        ]);
+
+        assert!(matches!(
+            Widget::try_from(missing),
+            Err(Error::InternalError("Missing or invalid Qty"))
+        ));
+        assert!(Widget::try_from(mistyped).is_err());
+    }
+
+    #[test]
+    fn should_collect_unread_attributes_into_flattened_field() {
+        let item = Item::from([
+            ("Name".to_string(), AttributeValue::S("extras".to_string())),
+            ("Other".to_string(), AttributeValue::N("1".to_string())),
+        ]);
+
+        let extras = Extras::try_from(item.clone()).expect("Invalid item");
+
+        assert_eq!(extras.name, "extras");
+        assert_eq!(
+            extras.rest,
+            Item::from([("Other".to_string(), AttributeValue::N("1".to_string()))])
+        );
+        assert_eq!(Item::from(&extras), item);
+    }
+}
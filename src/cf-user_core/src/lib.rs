@@ -1,7 +1,30 @@
+// Lets `#[derive(DynamoItem)]` name this crate the same way inside it as outside
+extern crate self as cf_user_core;
+
 pub mod args;
 pub mod aws_config_loader;
+pub mod cache;
+pub mod config;
 pub mod dynamo;
+pub mod email;
 pub mod error;
+pub mod etag;
+pub mod event_sink;
+pub mod export;
 pub mod ext;
+pub mod fieldset;
+pub mod file_format;
 pub mod fn_handler;
+pub mod handlers;
+pub mod import;
+pub mod item;
+pub mod migration;
+pub mod mock_request;
 pub mod models;
+pub mod outbox;
+pub mod repository;
+pub mod retry;
+pub mod router;
+pub mod secrets;
+pub mod timestamp;
+pub mod update_expression;
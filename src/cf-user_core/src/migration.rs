@@ -0,0 +1,487 @@
+use super::{
+    dynamo,
+    error::{Error, RepositoryError},
+    ext::AttributeValuesExt,
+    item::{DynamoItem, Item},
+    models::{
+        migration_record::{MigrationCheckpoint, MigrationRecord, MigrationStatus},
+        migration_report::MigrationReport,
+    },
+    retry::RetryPolicy,
+    timestamp,
+    update_expression::UpdateExpression,
+};
+
+use aws_sdk_dynamodb::types::AttributeValue;
+use aws_sdk_dynamodb::Client;
+use futures::future::join_all;
+
+/// A named change to the item layout. `apply` returns the rewritten item, or `None`
+/// when the migration does not touch the item or it is already migrated, so running a
+/// migration again never rewrites an item twice.
+pub struct Migration {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub apply: fn(&Item) -> Option<Item>,
+}
+
+/// Every migration, in the order they are applied. Applied migrations are recorded
+/// by name, so a migration must never be renamed once it has run.
+pub static MIGRATIONS: [Migration; 2] = [
+    Migration {
+        name: "001-username-index",
+        description: "Add the GSI3 username search keys to users created before GSI3",
+        apply: username_index,
+    },
+    Migration {
+        name: "002-rfc3339-timestamps",
+        description: "Rewrite user dates stored as chrono Display strings as RFC 3339",
+        apply: rfc3339_timestamps,
+    },
+];
+
+pub fn find_migration(name: &str) -> Option<&'static Migration> {
+    MIGRATIONS.iter().find(|migration| migration.name == name)
+}
+
+fn username_index(item: &Item) -> Option<Item> {
+    if !dynamo::is_user_item(item) {
+        return None;
+    }
+
+    let username = item.get_opt_s("Username")?;
+    let partition = dynamo::username_index_partition(&username);
+    let sort_key = dynamo::username_index_sort_key(&username);
+    if item.get_opt_s("GSI3PK") == Some(partition.clone())
+        && item.get_opt_s("GSI3SK") == Some(sort_key.clone())
+    {
+        return None;
+    }
+
+    let mut item = item.clone();
+    item.insert("GSI3PK".to_owned(), AttributeValue::S(partition));
+    item.insert("GSI3SK".to_owned(), AttributeValue::S(sort_key));
+
+    Some(item)
+}
+
+fn rfc3339_timestamps(item: &Item) -> Option<Item> {
+    if !dynamo::is_user_item(item) {
+        return None;
+    }
+
+    let expression = dynamo::timestamp_backfill_expression(item)?;
+    let mut item = item.clone();
+    expression.apply(&mut item);
+
+    Some(item)
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct MigrationOptions {
+    /// Segments of the parallel Scan. Ignored when resuming a run, which keeps the
+    /// count it started with.
+    pub total_segments: i32,
+    /// Counts the items that would be rewritten, without writing anything, including
+    /// checkpoints.
+    pub dry_run: bool,
+}
+
+impl Default for MigrationOptions {
+    fn default() -> Self {
+        Self {
+            total_segments: 4,
+            dry_run: false,
+        }
+    }
+}
+
+/// Reported after every page a segment finishes. Counts are totals for the segment.
+pub type MigrationProgress = MigrationCheckpoint;
+
+/// Runs one migration over the whole table with a parallel Scan, rewriting each item
+/// `apply` changes with a Put conditional on the item being unchanged since it was
+/// read.
+///
+/// The migration is recorded in a `MIGRATION#<name>` item. Each segment checkpoints
+/// its Scan position after every page, so an interrupted run resumes where it
+/// stopped, and a migration recorded as applied is not run again.
+pub async fn run_migration(
+    client: &Client,
+    table: &str,
+    migration: &Migration,
+    options: MigrationOptions,
+    progress: &(dyn Fn(&MigrationProgress) + Sync),
+) -> Result<MigrationReport, Box<dyn std::error::Error>> {
+    let retry = RetryPolicy::default();
+    let record = retry
+        .run(true, || get_record(client, table, migration.name))
+        .await?;
+
+    let total_segments = match &record {
+        Some(record) if record.status == MigrationStatus::Applied => {
+            return Ok(MigrationReport {
+                name: migration.name.to_owned(),
+                dry_run: options.dry_run,
+                already_applied: true,
+                scanned: record.scanned,
+                migrated: record.migrated,
+                conflicts: record.conflicts,
+            });
+        }
+        Some(record) if !options.dry_run => record.total_segments,
+        _ => options.total_segments,
+    };
+    if total_segments < 1 {
+        return Err(Box::new(Error::ClientError(
+            "Migration needs at least one segment",
+        )));
+    }
+
+    let started_at = record
+        .map(|record| record.started_at)
+        .unwrap_or_else(timestamp::now);
+    let mut record = MigrationRecord {
+        name: migration.name.to_owned(),
+        status: MigrationStatus::Running,
+        total_segments,
+        started_at,
+        applied_at: None,
+        scanned: 0,
+        migrated: 0,
+        conflicts: 0,
+    };
+    if !options.dry_run {
+        retry
+            .run(false, || put_item(client, table, record.to_item()))
+            .await?;
+    }
+
+    let segments = join_all((0..total_segments).map(|segment| {
+        run_segment(
+            client,
+            table,
+            migration,
+            segment,
+            total_segments,
+            options.dry_run,
+            progress,
+        )
+    }))
+    .await;
+
+    for segment in segments {
+        let segment = segment?;
+        record.scanned += segment.scanned;
+        record.migrated += segment.migrated;
+        record.conflicts += segment.conflicts;
+    }
+
+    if !options.dry_run {
+        record.status = MigrationStatus::Applied;
+        record.applied_at = Some(timestamp::now());
+        retry
+            .run(false, || put_item(client, table, record.to_item()))
+            .await?;
+    }
+
+    Ok(MigrationReport {
+        name: record.name,
+        dry_run: options.dry_run,
+        already_applied: false,
+        scanned: record.scanned,
+        migrated: record.migrated,
+        conflicts: record.conflicts,
+    })
+}
+
+async fn run_segment(
+    client: &Client,
+    table: &str,
+    migration: &Migration,
+    segment: i32,
+    total_segments: i32,
+    dry_run: bool,
+    progress: &(dyn Fn(&MigrationProgress) + Sync),
+) -> Result<MigrationCheckpoint, Box<dyn std::error::Error>> {
+    let retry = RetryPolicy::default();
+    let checkpoint_key = MigrationCheckpoint::new(migration.name, segment).to_item();
+
+    let mut checkpoint = match dry_run {
+        true => None,
+        false => {
+            retry
+                .run(true, || get_item(client, table, &checkpoint_key))
+                .await?
+        }
+    }
+    .map(MigrationCheckpoint::try_from)
+    .transpose()?
+    .unwrap_or_else(|| MigrationCheckpoint::new(migration.name, segment));
+
+    while !checkpoint.done {
+        let resp = retry
+            .run(true, || async {
+                client
+                    .scan()
+                    .table_name(table)
+                    .segment(segment)
+                    .total_segments(total_segments)
+                    .set_exclusive_start_key(checkpoint.last_key.clone())
+                    .send()
+                    .await
+                    .map_err(|err| RepositoryError::from(err).into())
+            })
+            .await?;
+
+        for item in resp.items.unwrap_or_default() {
+            checkpoint.scanned += 1;
+
+            let Some(migrated) = (migration.apply)(&item) else {
+                continue;
+            };
+            if dry_run {
+                checkpoint.migrated += 1;
+                continue;
+            }
+
+            let result = retry
+                .run(false, || put_if_unchanged(client, table, &item, &migrated))
+                .await;
+            match result {
+                Ok(()) => checkpoint.migrated += 1,
+                Err(err)
+                    if matches!(
+                        err.downcast_ref::<RepositoryError>(),
+                        Some(RepositoryError::ConditionFailed(_))
+                    ) =>
+                {
+                    checkpoint.conflicts += 1
+                }
+                Err(err) => return Err(err),
+            }
+        }
+
+        checkpoint.last_key = resp.last_evaluated_key;
+        checkpoint.done = checkpoint.last_key.is_none();
+
+        if !dry_run {
+            retry
+                .run(false, || put_item(client, table, checkpoint.to_item()))
+                .await?;
+        }
+        progress(&checkpoint);
+    }
+
+    Ok(checkpoint)
+}
+
+/// Conditions a write on every scalar attribute still holding the value it was read
+/// with. Items without any are only required to exist.
+pub fn unchanged_condition(item: &Item) -> UpdateExpression {
+    let mut names: Vec<&String> = item.keys().collect();
+    names.sort();
+
+    names
+        .into_iter()
+        .filter(|name| {
+            matches!(
+                item[*name],
+                AttributeValue::S(_) | AttributeValue::N(_) | AttributeValue::Bool(_)
+            )
+        })
+        .fold(
+            UpdateExpression::new().condition_attribute_exists("PK"),
+            |expression, name| expression.condition_equals(name, item[name].clone()),
+        )
+}
+
+async fn put_if_unchanged(
+    client: &Client,
+    table: &str,
+    current: &Item,
+    migrated: &Item,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let condition = unchanged_condition(current);
+
+    client
+        .put_item()
+        .table_name(table)
+        .set_item(Some(migrated.clone()))
+        .set_condition_expression(condition.condition_expression())
+        .set_expression_attribute_names(condition.names())
+        .set_expression_attribute_values(condition.values())
+        .send()
+        .await
+        .map_err(RepositoryError::from)?;
+
+    Ok(())
+}
+
+async fn put_item(
+    client: &Client,
+    table: &str,
+    item: Item,
+) -> Result<(), Box<dyn std::error::Error>> {
+    client
+        .put_item()
+        .table_name(table)
+        .set_item(Some(item))
+        .send()
+        .await
+        .map_err(RepositoryError::from)?;
+
+    Ok(())
+}
+
+async fn get_item(
+    client: &Client,
+    table: &str,
+    key: &Item,
+) -> Result<Option<Item>, Box<dyn std::error::Error>> {
+    let resp = client
+        .get_item()
+        .table_name(table)
+        .key("PK", AttributeValue::S(key.get_s("PK")))
+        .key("SK", AttributeValue::S(key.get_s("SK")))
+        .send()
+        .await
+        .map_err(RepositoryError::from)?;
+
+    Ok(resp.item)
+}
+
+async fn get_record(
+    client: &Client,
+    table: &str,
+    name: &str,
+) -> Result<Option<MigrationRecord>, Box<dyn std::error::Error>> {
+    let key = String::from("MIGRATION#") + name;
+    let item = get_item(
+        client,
+        table,
+        &Item::from([
+            ("PK".to_owned(), AttributeValue::S(key.clone())),
+            ("SK".to_owned(), AttributeValue::S(key)),
+        ]),
+    )
+    .await?;
+
+    Ok(item.map(MigrationRecord::try_from).transpose()?)
+}
+
+#[cfg(test)]
+mod unit_tests {
+    use super::*;
+
+    fn user_item(attributes: &[(&str, &str)]) -> Item {
+        let mut item = Item::from([
+            (
+                "PK".to_string(),
+                AttributeValue::S("USER#abc123".to_string()),
+            ),
+            (
+                "SK".to_string(),
+                AttributeValue::S("USER#abc123".to_string()),
+            ),
+            (
+                "UserId".to_string(),
+                AttributeValue::S("abc123".to_string()),
+            ),
+        ]);
+        for (name, value) in attributes {
+            item.insert(name.to_string(), AttributeValue::S(value.to_string()));
+        }
+        item
+    }
+
+    #[test]
+    fn should_have_unique_migration_names() {
+        let mut names: Vec<&str> = MIGRATIONS.iter().map(|migration| migration.name).collect();
+        names.sort();
+        names.dedup();
+
+        assert_eq!(names.len(), MIGRATIONS.len());
+        assert!(find_migration("001-username-index").is_some());
+        assert!(find_migration("999-unknown").is_none());
+    }
+
+    #[test]
+    fn should_migrate_each_item_only_once() {
+        let items = [
+            user_item(&[
+                ("Username", "Tester"),
+                ("CreatedDate", "2023-07-01 12:00:00 UTC"),
+            ]),
+            user_item(&[
+                ("Username", "Tester"),
+                ("CreatedDate", "2023-07-01T12:00:00.000Z"),
+                ("GSI2SK", "2023-07-01T12:00:00.000Z#abc123"),
+                ("GSI3PK", "USERNAME#t"),
+                ("GSI3SK", "tester"),
+            ]),
+        ];
+
+        for migration in MIGRATIONS.iter() {
+            let migrated = (migration.apply)(&items[0]).expect("Legacy item was not migrated");
+            assert!((migration.apply)(&migrated).is_none(), "{}", migration.name);
+            assert!((migration.apply)(&items[1]).is_none(), "{}", migration.name);
+        }
+    }
+
+    #[test]
+    fn should_ignore_items_other_than_users() {
+        let guard = Item::from([
+            (
+                "PK".to_string(),
+                AttributeValue::S("USERNAME#tester".to_string()),
+            ),
+            (
+                "SK".to_string(),
+                AttributeValue::S("USERNAME#tester".to_string()),
+            ),
+            (
+                "UserId".to_string(),
+                AttributeValue::S("abc123".to_string()),
+            ),
+        ]);
+
+        for migration in MIGRATIONS.iter() {
+            assert!((migration.apply)(&guard).is_none(), "{}", migration.name);
+        }
+    }
+
+    #[test]
+    fn should_require_item_to_be_unchanged() {
+        let mut item = user_item(&[("Username", "tester")]);
+        item.insert("Version".to_string(), AttributeValue::N("3".to_string()));
+        item.insert("Changes".to_string(), AttributeValue::M(Item::new()));
+
+        let condition = unchanged_condition(&item);
+        assert!(condition.matches(Some(&item)));
+
+        let mut changed = item.clone();
+        changed.insert("Version".to_string(), AttributeValue::N("4".to_string()));
+        assert!(!condition.matches(Some(&changed)));
+        assert!(!condition.matches(None));
+    }
+
+    #[test]
+    fn should_convert_checkpoint_to_item_and_back() {
+        let checkpoint = MigrationCheckpoint {
+            last_key: Some(user_item(&[])),
+            scanned: 10,
+            migrated: 4,
+            ..MigrationCheckpoint::new("001-username-index", 3)
+        };
+
+        let item = checkpoint.to_item();
+        assert_eq!(item.get_s("PK"), "MIGRATION#001-username-index");
+        assert_eq!(item.get_s("SK"), "SEGMENT#3");
+
+        assert_eq!(
+            MigrationCheckpoint::try_from(item).expect("Invalid item"),
+            checkpoint
+        );
+    }
+}
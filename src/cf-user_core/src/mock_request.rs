@@ -0,0 +1,61 @@
+use super::models::permissions::Permission;
+use lambda_http::aws_lambda_events::apigw::ApiGatewayProxyRequestContext;
+use lambda_http::request::RequestContext;
+use lambda_http::{http, Body, Context, Request, RequestExt};
+use serde_json::Value;
+use std::collections::HashMap;
+
+static MOCK_FUNCTION_NAME: &str = "cf-user-dev-app-MockFunction";
+pub static MOCK_PRINCIPAL_ID: &str = "mock-principal";
+
+/// Builds an API Gateway request carrying the Lambda and authorizer context that
+/// `fn_handler::handle_request` expects, for exercising handlers locally.
+pub fn create_mock_request(
+    method: http::Method,
+    uri: &str,
+    body: Body,
+    permissions: &[Permission],
+) -> Request {
+    let mut lambda_context = Context::default();
+    lambda_context.env_config.function_name = MOCK_FUNCTION_NAME.to_string();
+
+    let permission_values: Vec<String> = permissions.iter().map(|p| p.value()).collect();
+
+    let request_context = ApiGatewayProxyRequestContext::<Value> {
+        request_id: Some("mock-request-id".to_string()),
+        http_method: method.clone(),
+        authorizer: HashMap::from([
+            (
+                "principalId".to_string(),
+                Value::String(MOCK_PRINCIPAL_ID.to_string()),
+            ),
+            (
+                "permissions".to_string(),
+                Value::String(serde_json::to_string(&permission_values).unwrap_or_default()),
+            ),
+        ]),
+        ..Default::default()
+    };
+
+    let request = http::Request::builder()
+        .method(method)
+        .uri(uri)
+        .body(body)
+        .expect("Error building mock request");
+
+    request
+        .with_lambda_context(lambda_context)
+        .with_request_context(RequestContext::ApiGatewayV1(request_context))
+}
+
+/// Sets the API Gateway resource a mock request came in on, e.g. `/v1/users/{userId}`,
+/// for exercising the router.
+pub fn with_resource_path(request: Request, resource_path: &str) -> Request {
+    let mut request_context = match request.request_context() {
+        RequestContext::ApiGatewayV1(ctx) => ctx,
+        _ => panic!("Mock requests are API Gateway V1 requests"),
+    };
+    request_context.resource_path = Some(resource_path.to_string());
+
+    request.with_request_context(RequestContext::ApiGatewayV1(request_context))
+}
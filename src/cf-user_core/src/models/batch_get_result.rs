@@ -0,0 +1,9 @@
+use serde::{Deserialize, Serialize};
+
+/// Items found by a batch get, in the order their IDs were requested, along with the
+/// requested IDs that matched nothing.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct BatchGetResult<T> {
+    pub data: Vec<T>,
+    pub missing: Vec<String>,
+}
@@ -1,4 +1,4 @@
-use chrono::{NaiveDateTime, TimeZone, Utc};
+use chrono::NaiveDateTime;
 use serde::{self, Deserialize, Deserializer, Serialize, Serializer};
 
 #[derive(Clone, PartialEq, Deserialize, Serialize, Debug)]
@@ -19,7 +19,7 @@ pub struct CachedCredentials {
 mod option_date_format {
     use super::*;
 
-    const FORMAT: &'static str = "%Y-%m-%dT%H:%M:%S%fZ";
+    const FORMAT: &str = "%Y-%m-%dT%H:%M:%S%fZ";
 
     pub fn serialize<S>(date: &Option<NaiveDateTime>, serializer: S) -> Result<S::Ok, S::Error>
     where
@@ -28,7 +28,7 @@ mod option_date_format {
         match date {
             Some(date) => {
                 let s = format!("{}", date.format(FORMAT));
-                return serializer.serialize_str(&s);
+                serializer.serialize_str(&s)
             }
             _ => unreachable!(),
         }
@@ -0,0 +1,23 @@
+/// Who made a change, and the request it was made in. The principal is recorded as
+/// the actor in the user's history, and both are carried by the change's event.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ChangeContext {
+    pub principal_id: String,
+    /// The API Gateway request ID, absent for changes made outside the API, such as
+    /// imports run from `cf-user_admin`.
+    pub request_id: Option<String>,
+}
+
+impl ChangeContext {
+    pub fn new(principal_id: &str) -> Self {
+        Self {
+            principal_id: principal_id.to_owned(),
+            request_id: None,
+        }
+    }
+
+    pub fn with_request_id(mut self, request_id: &str) -> Self {
+        self.request_id = Some(request_id.to_owned());
+        self
+    }
+}
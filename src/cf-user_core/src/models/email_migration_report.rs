@@ -0,0 +1,40 @@
+use serde::{Deserialize, Serialize};
+
+/// Users whose emails normalize to the same key. None of them are re-keyed, since
+/// only one can own the key; they need merging or a changed email first.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct EmailCollision {
+    #[serde(rename = "EmailKey")]
+    pub email_key: String,
+    #[serde(rename = "UserIds")]
+    pub user_ids: Vec<String>,
+    #[serde(rename = "Emails")]
+    pub emails: Vec<String>,
+}
+
+/// A user whose re-keying transaction failed, usually because it changed mid-run.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct EmailMigrationFailure {
+    #[serde(rename = "UserId")]
+    pub user_id: String,
+    #[serde(rename = "Reason")]
+    pub reason: String,
+}
+
+/// Outcome of moving users onto normalized email keys. On a dry run `rekeyed` counts
+/// the users that would have been re-keyed.
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq)]
+pub struct EmailMigrationReport {
+    #[serde(rename = "DryRun")]
+    pub dry_run: bool,
+    #[serde(rename = "Scanned")]
+    pub scanned: usize,
+    #[serde(rename = "Rekeyed")]
+    pub rekeyed: usize,
+    #[serde(rename = "Unchanged")]
+    pub unchanged: usize,
+    #[serde(rename = "Collisions")]
+    pub collisions: Vec<EmailCollision>,
+    #[serde(rename = "Failures")]
+    pub failures: Vec<EmailMigrationFailure>,
+}
@@ -1,54 +1,186 @@
-use lambda_http::http::StatusCode;
+use super::problem::Problem;
+use lambda_http::http::header::{
+    HeaderName, HeaderValue, CACHE_CONTROL, ETAG, LOCATION, SET_COOKIE,
+};
+use lambda_http::http::{HeaderMap, StatusCode};
+use serde::Serialize;
+use serde_json::value::RawValue;
 
+/// What a handler answers with. The body is serialized as JSON by
+/// `fn_handler::handle_request`, so handlers return it as is.
 #[derive(Clone, Debug)]
-pub struct HandleResponse {
-	pub body: Option<String>,
-	pub err_message: Option<String>,
-	pub status_code: Option<StatusCode>,
+pub struct HandleResponse<T = ()> {
+    pub body: Option<T>,
+    pub problem: Option<Problem>,
+    pub status_code: Option<StatusCode>,
+    pub headers: HeaderMap,
 }
 
-impl HandleResponse {
-	pub fn success(body: Option<&str>) -> Self {
-		Self {
-			body: match body {
-				Some(body) => Some(body.to_string()),
-				None => None	
-			},
-			err_message: None,
-			status_code: None,
-		}
-	}
-
-	pub fn set_success(body: Option<&str>, status_code: StatusCode) -> Self {
-		Self {
-			body: match body {
-				Some(body) => Some(body.to_string()),
-				None => None	
-			},
-			err_message: None,
-			status_code: Some(status_code),
-		}
-	}
-
-	pub fn error(err_message: Option<&str>) -> Self {
-		Self {
-			body: None,
-			err_message:  match err_message {
-				Some(err_message) => Some(err_message.to_string()),
-				None => None	
-			},
-			status_code: None,
-		}
-	}
-
-	pub fn set_error(err_message: Option<&str>, status_code: StatusCode) -> Self {
-		Self {
-			body: None,
-			err_message:  match err_message {
-				Some(err_message) => Some(err_message.to_string()),
-				None => None	
-			},
-			status_code: Some(status_code),
-		}
-	}
-}
\ No newline at end of file
+impl<T> HandleResponse<T> {
+    /// Answers 200 with `body`, unless `with_status` says otherwise.
+    pub fn success(body: T) -> Self {
+        Self {
+            body: Some(body),
+            problem: None,
+            status_code: None,
+            headers: HeaderMap::new(),
+        }
+    }
+
+    /// Answers 204 with no body.
+    pub fn no_content() -> Self {
+        Self {
+            body: None,
+            problem: None,
+            status_code: None,
+            headers: HeaderMap::new(),
+        }
+    }
+
+    pub fn error(err_message: Option<&str>) -> Self {
+        Self::set_error(err_message, StatusCode::BAD_REQUEST)
+    }
+
+    pub fn set_error(err_message: Option<&str>, status_code: StatusCode) -> Self {
+        let problem = Problem::new(status_code);
+
+        Self::problem(match err_message {
+            Some(err_message) => problem.with_detail(err_message),
+            None => problem,
+        })
+    }
+
+    /// An error answered with `problem`, for errors with a more specific code or
+    /// field errors.
+    pub fn problem(problem: Problem) -> Self {
+        Self {
+            body: None,
+            status_code: Some(problem.status_code()),
+            problem: Some(problem),
+            headers: HeaderMap::new(),
+        }
+    }
+
+    /// The status answered with: the one set, or else 200 with a body and 204
+    /// without.
+    pub fn status(&self) -> StatusCode {
+        match (self.status_code, &self.body) {
+            (Some(status_code), _) => status_code,
+            (None, Some(_)) => StatusCode::OK,
+            (None, None) => StatusCode::NO_CONTENT,
+        }
+    }
+
+    pub fn with_status(mut self, status_code: StatusCode) -> Self {
+        self.status_code = Some(status_code);
+        self
+    }
+
+    /// Sets `name`, replacing any value it had.
+    pub fn with_header(mut self, name: HeaderName, value: HeaderValue) -> Self {
+        self.headers.insert(name, value);
+        self
+    }
+
+    /// Sets the `ETag` header returned with a successful response.
+    pub fn with_etag(self, etag: &str) -> Self {
+        self.with_text_header(ETAG, etag)
+    }
+
+    /// Sets the `Location` of a created resource, e.g. `/v1/users/{userId}`.
+    pub fn with_location(self, location: &str) -> Self {
+        self.with_text_header(LOCATION, location)
+    }
+
+    pub fn with_cache_control(self, directives: &str) -> Self {
+        self.with_text_header(CACHE_CONTROL, directives)
+    }
+
+    /// Adds a `Set-Cookie` header, keeping any cookies already set.
+    pub fn with_cookie(mut self, cookie: &str) -> Self {
+        if let Ok(value) = HeaderValue::from_str(cookie) {
+            self.headers.append(SET_COOKIE, value);
+        }
+        self
+    }
+
+    /// Text that is not a valid header value, e.g. with a line break, is dropped.
+    fn with_text_header(self, name: HeaderName, value: &str) -> Self {
+        match HeaderValue::from_str(value) {
+            Ok(value) => self.with_header(name, value),
+            Err(_) => self,
+        }
+    }
+}
+
+impl<T: Serialize> HandleResponse<T> {
+    /// The response with its body serialized, for handling responses with different
+    /// body types alike.
+    pub fn into_raw(self) -> Result<HandleResponse<Box<RawValue>>, serde_json::Error> {
+        let body = match self.body {
+            Some(body) => Some(serde_json::value::to_raw_value(&body)?),
+            None => None,
+        };
+
+        Ok(HandleResponse {
+            body,
+            problem: self.problem,
+            status_code: self.status_code,
+            headers: self.headers,
+        })
+    }
+}
+
+#[cfg(test)]
+mod unit_tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn should_default_the_status_to_the_body() {
+        assert_eq!(HandleResponse::success(json!({})).status(), StatusCode::OK);
+        assert_eq!(
+            HandleResponse::<()>::no_content().status(),
+            StatusCode::NO_CONTENT
+        );
+        assert_eq!(
+            HandleResponse::success(json!({}))
+                .with_status(StatusCode::CREATED)
+                .status(),
+            StatusCode::CREATED
+        );
+    }
+
+    #[test]
+    fn should_keep_every_cookie() {
+        let response = HandleResponse::<()>::no_content()
+            .with_cookie("first=1")
+            .with_cookie("second=2")
+            .with_etag("\"1\"")
+            .with_etag("\"2\"");
+
+        assert_eq!(response.headers.get_all(SET_COOKIE).iter().count(), 2);
+        assert_eq!(response.headers[ETAG], "\"2\"");
+    }
+
+    #[test]
+    fn should_drop_invalid_header_text() {
+        let response = HandleResponse::<()>::no_content().with_location("/v1/users/\n");
+
+        assert!(response.headers.is_empty());
+    }
+
+    #[test]
+    fn should_serialize_the_body_once() {
+        let response = HandleResponse::success(json!({ "UserId": "abc123" }))
+            .with_location("/v1/users/abc123")
+            .into_raw()
+            .expect("Failed to serialize");
+
+        assert_eq!(
+            response.body.expect("Missing body").get(),
+            r#"{"UserId":"abc123"}"#
+        );
+        assert_eq!(response.headers[LOCATION], "/v1/users/abc123");
+    }
+}
@@ -0,0 +1,53 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq)]
+pub enum ImportOutcome {
+    /// The user was written.
+    #[serde(rename = "created")]
+    Created,
+    /// The row was valid but its email or username was already taken.
+    #[serde(rename = "skipped")]
+    Skipped,
+    /// The row could not be parsed, failed validation or could not be written.
+    #[serde(rename = "rejected")]
+    Rejected,
+}
+
+/// What happened to one row of an import. `line` is where the row starts in the file.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct ImportRowResult {
+    #[serde(rename = "Line")]
+    pub line: usize,
+    #[serde(rename = "Outcome")]
+    pub outcome: ImportOutcome,
+    #[serde(rename = "UserId", skip_serializing_if = "Option::is_none")]
+    pub user_id: Option<String>,
+    #[serde(rename = "Email", skip_serializing_if = "Option::is_none")]
+    pub email: Option<String>,
+    #[serde(rename = "Reason", skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq)]
+pub struct ImportReport {
+    #[serde(rename = "Created")]
+    pub created: usize,
+    #[serde(rename = "Skipped")]
+    pub skipped: usize,
+    #[serde(rename = "Rejected")]
+    pub rejected: usize,
+    #[serde(rename = "Rows")]
+    pub rows: Vec<ImportRowResult>,
+}
+
+impl ImportReport {
+    pub fn push(&mut self, row: ImportRowResult) {
+        match row.outcome {
+            ImportOutcome::Created => self.created += 1,
+            ImportOutcome::Skipped => self.skipped += 1,
+            ImportOutcome::Rejected => self.rejected += 1,
+        }
+
+        self.rows.push(row);
+    }
+}
@@ -0,0 +1,92 @@
+use super::super::item::{AttributeField, AttributeValue, DynamoItem, Item};
+use chrono::{DateTime, Utc};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MigrationStatus {
+    Running,
+    Applied,
+}
+
+impl MigrationStatus {
+    pub fn value(&self) -> &'static str {
+        match *self {
+            MigrationStatus::Running => "running",
+            MigrationStatus::Applied => "applied",
+        }
+    }
+}
+
+impl AttributeField for MigrationStatus {
+    fn to_attribute(&self) -> Option<AttributeValue> {
+        Some(AttributeValue::S(self.value().to_owned()))
+    }
+
+    fn from_attribute(value: Option<AttributeValue>) -> Option<Self> {
+        match value?.as_s().ok()?.as_str() {
+            "running" => Some(MigrationStatus::Running),
+            "applied" => Some(MigrationStatus::Applied),
+            _ => None,
+        }
+    }
+}
+
+/// The `MIGRATION#<name>` item recording a migration that was started, and once every
+/// segment is done, applied.
+#[derive(Clone, Debug, PartialEq, DynamoItem)]
+#[dynamo(
+    rename_all = "PascalCase",
+    key(names = ["PK", "SK"], field = "name", prefix = "MIGRATION#")
+)]
+pub struct MigrationRecord {
+    pub name: String,
+    pub status: MigrationStatus,
+    /// Segments of the Scan. A resumed run keeps the count it started with, since the
+    /// checkpoints only make sense for it.
+    pub total_segments: i32,
+    pub started_at: DateTime<Utc>,
+    pub applied_at: Option<DateTime<Utc>>,
+    #[dynamo(default)]
+    pub scanned: u64,
+    #[dynamo(default)]
+    pub migrated: u64,
+    #[dynamo(default)]
+    pub conflicts: u64,
+}
+
+/// Progress of one Scan segment, stored under the migration's partition as
+/// `MIGRATION#<name>` / `SEGMENT#<n>` after every page so a run can resume.
+#[derive(Clone, Debug, PartialEq, DynamoItem)]
+#[dynamo(
+    rename_all = "PascalCase",
+    key(name = "PK", field = "name", prefix = "MIGRATION#"),
+    key(name = "SK", field = "segment", prefix = "SEGMENT#")
+)]
+pub struct MigrationCheckpoint {
+    pub name: String,
+    pub segment: i32,
+    /// Where the segment's Scan continues. `None` before the first page, and once
+    /// `done`.
+    pub last_key: Option<Item>,
+    #[dynamo(default)]
+    pub done: bool,
+    #[dynamo(default)]
+    pub scanned: u64,
+    #[dynamo(default)]
+    pub migrated: u64,
+    #[dynamo(default)]
+    pub conflicts: u64,
+}
+
+impl MigrationCheckpoint {
+    pub fn new(name: &str, segment: i32) -> Self {
+        Self {
+            name: name.to_owned(),
+            segment,
+            last_key: None,
+            done: false,
+            scanned: 0,
+            migrated: 0,
+            conflicts: 0,
+        }
+    }
+}
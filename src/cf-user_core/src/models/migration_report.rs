@@ -0,0 +1,22 @@
+use serde::{Deserialize, Serialize};
+
+/// Outcome of running one migration. On a dry run `migrated` counts the items that
+/// would have been rewritten. `conflicts` counts items that changed between being
+/// read and rewritten, which were left as they are.
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq)]
+pub struct MigrationReport {
+    #[serde(rename = "Name")]
+    pub name: String,
+    #[serde(rename = "DryRun")]
+    pub dry_run: bool,
+    /// The migration was already recorded as applied, so nothing was scanned. The
+    /// counts are those of the run that applied it.
+    #[serde(rename = "AlreadyApplied")]
+    pub already_applied: bool,
+    #[serde(rename = "Scanned")]
+    pub scanned: u64,
+    #[serde(rename = "Migrated")]
+    pub migrated: u64,
+    #[serde(rename = "Conflicts")]
+    pub conflicts: u64,
+}
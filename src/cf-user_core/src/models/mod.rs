@@ -1,6 +1,17 @@
+pub mod batch_get_result;
 pub mod cache_credentials;
+pub mod change_context;
+pub mod email_migration_report;
 pub mod handler_response;
+pub mod import_report;
+pub mod migration_record;
+pub mod migration_report;
+pub mod outbox_entry;
 pub mod paginated_result;
 pub mod paginated_users;
+pub mod partial_user;
 pub mod permissions;
+pub mod problem;
 pub mod user;
+pub mod user_event;
+pub mod user_history;
@@ -0,0 +1,61 @@
+use super::super::item::{AttributeField, AttributeValue, DynamoItem};
+use super::user_event::CloudEvent;
+use chrono::{DateTime, Utc};
+
+/// The envelope is stored as a JSON string, exactly as it is published.
+impl AttributeField for CloudEvent {
+    fn to_attribute(&self) -> Option<AttributeValue> {
+        Some(AttributeValue::S(serde_json::to_string(self).ok()?))
+    }
+
+    fn from_attribute(value: Option<AttributeValue>) -> Option<Self> {
+        serde_json::from_str(value?.as_s().ok()?).ok()
+    }
+}
+
+/// An event waiting to be published, written in the same transaction as the change it
+/// describes. Entries share the `OUTBOX` partition, sorted by their ULID, so the relay
+/// reads them in the order the changes were made. `attempts` counts the relays that
+/// failed to publish the entry.
+#[derive(Clone, Debug, PartialEq, DynamoItem)]
+#[dynamo(
+    rename_all = "PascalCase",
+    key(name = "PK", value = "OUTBOX"),
+    key(name = "SK", field = "entry_id", prefix = "OUTBOX#")
+)]
+pub struct OutboxEntry {
+    pub entry_id: String,
+    pub event: CloudEvent,
+    pub created_date: DateTime<Utc>,
+    #[dynamo(default)]
+    pub attempts: u32,
+}
+
+#[cfg(test)]
+mod unit_tests {
+    use super::*;
+    use crate::item::Item;
+    use crate::models::user_event::{UserDeleted, UserEvent};
+    use chrono::TimeZone;
+
+    #[test]
+    fn should_convert_outbox_entry_to_item_and_back() {
+        let time = Utc.with_ymd_and_hms(2023, 7, 1, 12, 0, 0).unwrap();
+        let event = UserEvent::Deleted(UserDeleted {
+            user_id: "abc123".to_string(),
+            purged: false,
+        });
+        let entry = OutboxEntry {
+            entry_id: "01H4".to_string(),
+            event: CloudEvent::new("01H4", "cf-user", time, &event).expect("Invalid event"),
+            created_date: time,
+            attempts: 2,
+        };
+
+        let item = Item::from(&entry);
+        assert_eq!(item["PK"], AttributeValue::S("OUTBOX".to_string()));
+        assert_eq!(item["SK"], AttributeValue::S("OUTBOX#01H4".to_string()));
+
+        assert_eq!(OutboxEntry::try_from(item).expect("Invalid item"), entry);
+    }
+}
@@ -1,83 +1,154 @@
 use super::super::error::Error;
-use super::super::ext::AttributeValuesExt;
-use aws_sdk_dynamodb::types::AttributeValue;
+use super::super::item::{AttributeValue, DynamoItem};
+use aws_sdk_dynamodb::primitives::Blob;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use hmac::{Hmac, Mac};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use sha2::Sha256;
+use std::collections::{BTreeMap, HashMap};
 
-pub trait EncodedToken {
-    fn encode_token(&mut self) -> String;
-    fn decode_token(token: String) -> PaginationToken;
+type HmacSha256 = Hmac<Sha256>;
+
+static TOKEN_VERSION: u8 = 1;
+static SIGNATURE_LENGTH: usize = 32;
+
+pub trait EncodedToken: Sized {
+    fn encode_token(&self, secret: &[u8]) -> Result<String, Error>;
+    fn decode_token(token: &str, secret: &[u8]) -> Result<Self, Error>;
 }
 
-#[derive(Clone, Deserialize, Serialize, Debug)]
+/// The `LastEvaluatedKey` of a page, valid until `expires_at` (seconds since the
+/// epoch). Any key shape is supported, so tokens from index queries carry the index
+/// keys alongside PK and SK.
+///
+/// Encoded as base64url of `version || JSON body || HMAC-SHA256(version || JSON body)`,
+/// which keeps the raw keys out of URLs and lets tampered tokens be rejected. As an
+/// item, a token is just its key.
+#[derive(Clone, Debug, PartialEq, DynamoItem)]
 pub struct PaginationToken {
-    #[serde(rename = "PK")]
-    pub pk: String,
+    #[dynamo(flatten)]
+    pub key: HashMap<String, AttributeValue>,
+    #[dynamo(skip)]
+    pub expires_at: i64,
+}
 
-    #[serde(rename = "SK")]
-    pub sk: String,
+/// Key attributes can only be strings, numbers or binary.
+#[derive(Deserialize, Serialize)]
+enum KeyValue {
+    S(String),
+    N(String),
+    B(String),
 }
 
-impl EncodedToken for PaginationToken {
-    fn encode_token(&mut self) -> String {
-        let hex_pk = hex::encode(self.pk.to_owned());
-        let hex_sk = hex::encode(self.sk.to_owned());
+#[derive(Deserialize, Serialize)]
+struct TokenBody {
+    #[serde(rename = "k")]
+    key: BTreeMap<String, KeyValue>,
+    #[serde(rename = "e")]
+    expires_at: i64,
+}
 
-        return String::from(format!("{}.{}", hex_pk, hex_sk));
+impl PaginationToken {
+    pub fn new(key: HashMap<String, AttributeValue>, ttl_seconds: i64) -> Self {
+        Self {
+            key,
+            expires_at: chrono::offset::Utc::now().timestamp() + ttl_seconds,
+        }
     }
 
-    fn decode_token(token: String) -> PaginationToken {
-        let key_parts = token.split(".").collect::<Vec<&str>>();
-
-        let pk = String::from_utf8(hex::decode(key_parts[0]).expect("Error decoding primary key"))
-            .expect("Error parsing token from decoded bytes");
-
-        let sk =
-            String::from_utf8(hex::decode(key_parts[1]).expect("Error decoding secondary key"))
-                .expect("Error parsing token from decoded bytes");
-
-        return PaginationToken { pk: pk, sk: sk };
+    pub fn is_expired(&self) -> bool {
+        chrono::offset::Utc::now().timestamp() >= self.expires_at
     }
 }
 
-impl From<PaginationToken> for HashMap<String, AttributeValue> {
-    fn from(token: PaginationToken) -> HashMap<String, AttributeValue> {
-        let mut val = HashMap::new();
-        val.insert(String::from("PK"), AttributeValue::S(token.pk.clone()));
-        val.insert(String::from("SK"), AttributeValue::S(token.sk.clone()));
+impl EncodedToken for PaginationToken {
+    fn encode_token(&self, secret: &[u8]) -> Result<String, Error> {
+        let mut key: BTreeMap<String, KeyValue> = BTreeMap::new();
+        for (name, value) in self.key.iter() {
+            let value = match value {
+                AttributeValue::S(value) => KeyValue::S(value.to_owned()),
+                AttributeValue::N(value) => KeyValue::N(value.to_owned()),
+                AttributeValue::B(value) => KeyValue::B(URL_SAFE_NO_PAD.encode(value.as_ref())),
+                _ => return Err(Error::InternalError("Unsupported key attribute type")),
+            };
+            key.insert(name.to_owned(), value);
+        }
+
+        let body = serde_json::to_vec(&TokenBody {
+            key,
+            expires_at: self.expires_at,
+        })
+        .map_err(|_| Error::InternalError("Error serializing pagination token"))?;
+
+        let mut bytes = vec![TOKEN_VERSION];
+        bytes.extend(body);
+
+        let signature = signer(secret)?.chain_update(&bytes).finalize().into_bytes();
+        bytes.extend(signature);
 
-        val
+        Ok(URL_SAFE_NO_PAD.encode(bytes))
     }
-}
 
-impl TryFrom<HashMap<String, AttributeValue>> for PaginationToken {
-    type Error = Error;
+    fn decode_token(token: &str, secret: &[u8]) -> Result<PaginationToken, Error> {
+        let signer = signer(secret)?;
+        let bytes = URL_SAFE_NO_PAD
+            .decode(token)
+            .map_err(|_| Error::InvalidToken("Pagination token is not valid base64url"))?;
+
+        if bytes.len() <= SIGNATURE_LENGTH + 1 {
+            return Err(Error::InvalidToken("Pagination token is truncated"));
+        }
+
+        let (signed, signature) = bytes.split_at(bytes.len() - SIGNATURE_LENGTH);
+        signer
+            .chain_update(signed)
+            .verify_slice(signature)
+            .map_err(|_| Error::InvalidToken("Pagination token signature does not match"))?;
+
+        if signed[0] != TOKEN_VERSION {
+            return Err(Error::InvalidToken("Unsupported pagination token version"));
+        }
+
+        let body: TokenBody = serde_json::from_slice(&signed[1..])
+            .map_err(|_| Error::InvalidToken("Pagination token body is malformed"))?;
+
+        let mut key: HashMap<String, AttributeValue> = HashMap::new();
+        for (name, value) in body.key {
+            let value = match value {
+                KeyValue::S(value) => AttributeValue::S(value),
+                KeyValue::N(value) => AttributeValue::N(value),
+                KeyValue::B(value) => AttributeValue::B(Blob::new(
+                    URL_SAFE_NO_PAD
+                        .decode(value)
+                        .map_err(|_| Error::InvalidToken("Pagination token body is malformed"))?,
+                )),
+            };
+            key.insert(name, value);
+        }
 
-    fn try_from(value: HashMap<String, AttributeValue>) -> Result<Self, Self::Error> {
-        Ok(PaginationToken {
-            pk: value
-                .get_opt_s("PK")
-                .ok_or(Error::InternalError("Missing PK"))?,
+        let token = PaginationToken {
+            key,
+            expires_at: body.expires_at,
+        };
 
-            sk: value
-                .get_opt_s("SK")
-                .ok_or(Error::InternalError("Missing SK"))?,
-        })
-    }
-}
+        if token.is_expired() {
+            return Err(Error::InvalidToken("Pagination token has expired"));
+        }
 
-impl From<PaginationToken> for String {
-    fn from(token: PaginationToken) -> String {
-        return token.clone().encode_token();
+        Ok(token)
     }
 }
 
-impl TryFrom<String> for PaginationToken {
-    type Error = Error;
-
-    fn try_from(token: String) -> Result<Self, Self::Error> {
-        Ok(PaginationToken::decode_token(token.clone()))
+/// Fails closed without a secret, since anyone could sign tokens with an empty key.
+fn signer(secret: &[u8]) -> Result<HmacSha256, Error> {
+    if secret.is_empty() {
+        return Err(Error::InternalError(
+            "Pagination token secret is not configured",
+        ));
     }
+
+    Ok(HmacSha256::new_from_slice(secret).expect("HMAC accepts keys of any length"))
 }
 
 #[derive(Clone, Debug, Serialize)]
@@ -89,77 +160,147 @@ pub struct PaginatedResult<T> {
 #[cfg(test)]
 mod unit_tests {
     use super::*;
+    use proptest::prelude::*;
+
+    static SECRET: &[u8] = b"test-secret";
+
+    fn create_token() -> PaginationToken {
+        PaginationToken::new(
+            HashMap::from([
+                (
+                    "PK".to_string(),
+                    AttributeValue::S("USER#abc123".to_string()),
+                ),
+                (
+                    "SK".to_string(),
+                    AttributeValue::S("USER#abc123".to_string()),
+                ),
+                ("GSI2PK".to_string(), AttributeValue::S("USERS".to_string())),
+                ("Score".to_string(), AttributeValue::N("42".to_string())),
+                (
+                    "Raw".to_string(),
+                    AttributeValue::B(Blob::new(vec![0, 255])),
+                ),
+            ]),
+            60,
+        )
+    }
+
+    fn assert_invalid(result: Result<PaginationToken, Error>) {
+        assert!(matches!(result, Err(Error::InvalidToken(_))));
+    }
 
     #[test]
-    fn should_encode_token_using_same_pk_and_sk() {
-        let token = PaginationToken {
-            pk: "USER#123".to_string(),
-            sk: "USER#123".to_string(),
-        };
+    fn should_decode_token_to_original_value() {
+        let token = create_token();
 
-        let encoded_token = token.clone().encode_token();
+        let encoded_token = token.encode_token(SECRET).expect("Failed to encode token");
+        let decoded_token =
+            PaginationToken::decode_token(&encoded_token, SECRET).expect("Failed to decode token");
 
-        let encoded_token_parts = encoded_token.split('.').collect::<Vec<&str>>();
-        assert_eq!(encoded_token_parts.len(), 2);
-        assert_eq!(encoded_token_parts.get(0), encoded_token_parts.get(1));
+        assert_eq!(token, decoded_token);
     }
 
     #[test]
-    fn should_encode_token_using_different_pk_and_sk() {
-        let token = PaginationToken {
-            pk: "USER#abc123".to_string(),
-            sk: "USER#def456".to_string(),
-        };
-
-        let encoded_token = token.clone().encode_token();
+    fn should_not_expose_raw_keys() {
+        let encoded_token = create_token()
+            .encode_token(SECRET)
+            .expect("Failed to encode token");
 
-        let encoded_token_parts = encoded_token.split('.').collect::<Vec<&str>>();
-        assert_eq!(encoded_token_parts.len(), 2);
-        assert_ne!(encoded_token_parts.get(0), encoded_token_parts.get(1));
+        assert!(!encoded_token.contains("USER"));
+        assert!(!encoded_token.contains(['+', '/', '=', '.']));
     }
 
     #[test]
-    fn should_decode_token_to_original_value() {
-        let token = PaginationToken {
-            pk: "USER#asu47thaskdf".to_string(),
-            sk: "USER#badkehf847au".to_string(),
-        };
+    fn should_reject_tampered_token() {
+        let encoded_token = create_token()
+            .encode_token(SECRET)
+            .expect("Failed to encode token");
+
+        let mut bytes = URL_SAFE_NO_PAD
+            .decode(&encoded_token)
+            .expect("Failed to decode base64");
+        bytes[5] ^= 1;
+
+        assert_invalid(PaginationToken::decode_token(
+            &URL_SAFE_NO_PAD.encode(bytes),
+            SECRET,
+        ));
+        assert_invalid(PaginationToken::decode_token(
+            &encoded_token,
+            b"other-secret",
+        ));
+    }
 
-        let encoded_token = token.clone().encode_token();
+    #[test]
+    fn should_refuse_tokens_without_a_secret() {
+        let encoded_token = create_token()
+            .encode_token(SECRET)
+            .expect("Failed to encode token");
+
+        assert!(matches!(
+            create_token().encode_token(b""),
+            Err(Error::InternalError(_))
+        ));
+        assert!(matches!(
+            PaginationToken::decode_token(&encoded_token, b""),
+            Err(Error::InternalError(_))
+        ));
+    }
 
-        let decoded_token = PaginationToken::decode_token(encoded_token.clone());
-        assert_eq!(token.pk, decoded_token.pk);
-        assert_eq!(token.sk, decoded_token.sk);
+    #[test]
+    fn should_reject_malformed_token_without_panicking() {
+        for token in ["", "not a token", "AAAA", "USER#1.USER#1", "%%%"] {
+            assert_invalid(PaginationToken::decode_token(token, SECRET));
+        }
     }
 
     #[test]
-    fn should_parse_encoded_token_from_valid_string() {
-        let token = PaginationToken {
-            pk: "USER#asu47thaskdf".to_string(),
-            sk: "USER#badkehf847au".to_string(),
-        };
-        let encoded_token = token.clone().encode_token();
+    fn should_reject_unknown_version() {
+        let mut bytes = vec![TOKEN_VERSION + 1];
+        bytes.extend(br#"{"k":{},"e":4102444800}"#);
+        let signature = signer(SECRET)
+            .expect("Failed to create signer")
+            .chain_update(&bytes)
+            .finalize()
+            .into_bytes();
+        bytes.extend(signature);
+
+        assert_invalid(PaginationToken::decode_token(
+            &URL_SAFE_NO_PAD.encode(bytes),
+            SECRET,
+        ));
+    }
 
-        let decoded_token_result = PaginationToken::try_from(encoded_token);
-        let decoded_token = decoded_token_result.expect("Failed to parse token from string.");
+    fn key_value_strategy() -> impl Strategy<Value = AttributeValue> {
+        prop_oneof![
+            any::<String>().prop_map(AttributeValue::S),
+            any::<i64>().prop_map(|n| AttributeValue::N(n.to_string())),
+            any::<Vec<u8>>().prop_map(|bytes| AttributeValue::B(Blob::new(bytes))),
+        ]
+    }
 
-        assert_eq!(token.pk, decoded_token.pk);
-        assert_eq!(token.sk, decoded_token.sk);
+    proptest! {
+        #[test]
+        fn should_decode_any_token_to_original_value(
+            key in proptest::collection::hash_map(any::<String>(), key_value_strategy(), 0..4)
+        ) {
+            let token = PaginationToken::new(key, 60);
+
+            let encoded_token = token.encode_token(SECRET).expect("Failed to encode token");
+            let decoded_token = PaginationToken::decode_token(&encoded_token, SECRET)
+                .expect("Failed to decode token");
+
+            prop_assert_eq!(token, decoded_token);
+        }
     }
 
     #[test]
-    fn should_return_encoded_string_from_pagination_token() {
-        let token = PaginationToken {
-            pk: "USER#asu47thaskdf".to_string(),
-            sk: "USER#badkehf847au".to_string(),
-        };
-
-        let manually_encoded_token = token.clone().encode_token();
+    fn should_reject_expired_token() {
+        let token = PaginationToken::new(HashMap::new(), -1);
 
-        let encoded_token_result = String::try_from(token);
-        let encoded_token =
-            encoded_token_result.expect("Failed to encode token through string overload.");
+        let encoded_token = token.encode_token(SECRET).expect("Failed to encode token");
 
-        assert_eq!(manually_encoded_token, encoded_token);
+        assert_invalid(PaginationToken::decode_token(&encoded_token, SECRET));
     }
 }
@@ -21,9 +21,6 @@ impl From<PaginatedResult<User>> for Option<String> {
             },
         );
 
-        return match serde_json::to_string(&response) {
-            Ok(value) => Some(value),
-            Err(_err) => None,
-        };
+        serde_json::to_string(&response).ok()
     }
 }
@@ -0,0 +1,31 @@
+use super::user::{User, UserStatus};
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+/// The fields of a user a `Fieldset` selected, rendered as they are in the `User`
+/// JSON. The version and status are kept whether or not they were selected, for the
+/// ETag and for telling deleted users apart.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct PartialUser {
+    #[serde(flatten)]
+    pub fields: Map<String, Value>,
+    #[serde(skip)]
+    pub version: u64,
+    #[serde(skip)]
+    pub status: UserStatus,
+}
+
+impl PartialUser {
+    pub fn is_deleted(&self) -> bool {
+        self.status == UserStatus::Deleted
+    }
+}
+
+/// A whole user, or the fields of one a `Fieldset` selected when `?fields=` was
+/// sent.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(untagged)]
+pub enum UserView {
+    Whole(Box<User>),
+    Partial(PartialUser),
+}
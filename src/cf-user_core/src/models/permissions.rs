@@ -1,9 +1,14 @@
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Permission {
     UserGet,
     UserList,
     UserCreate,
     UserUpdate,
     UserDelete,
+    UserRestore,
+    UserHistory,
+    UserImport,
+    UserAdmin,
 }
 
 impl Permission {
@@ -14,6 +19,10 @@ impl Permission {
             Permission::UserCreate => "user:create".to_string(),
             Permission::UserUpdate => "user:update".to_string(),
             Permission::UserDelete => "user:delete".to_string(),
+            Permission::UserRestore => "user:restore".to_string(),
+            Permission::UserHistory => "user:history".to_string(),
+            Permission::UserImport => "user:import".to_string(),
+            Permission::UserAdmin => "user:admin".to_string(),
         }
     }
 }
@@ -0,0 +1,238 @@
+use super::super::error::UniqueField;
+use lambda_http::http::StatusCode;
+use serde::{Deserialize, Serialize};
+
+/// Media type of a `Problem` body.
+pub static PROBLEM_CONTENT_TYPE: &str = "application/problem+json";
+
+/// Machine-readable class of a `Problem`. Codes are stable, so clients can branch on
+/// them rather than on the wording of `detail`.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ProblemCode {
+    BadRequest,
+    /// The request body failed validation. `errors` names the fields at fault.
+    ValidationFailed,
+    InvalidPageToken,
+    Unauthorized,
+    Forbidden,
+    NotFound,
+    MethodNotAllowed,
+    Conflict,
+    EmailTaken,
+    UsernameTaken,
+    Gone,
+    PreconditionFailed,
+    PreconditionRequired,
+    Throttled,
+    InternalError,
+    ServiceUnavailable,
+}
+
+impl ProblemCode {
+    /// The general code for an error status, used unless a handler names a more
+    /// specific one.
+    pub fn from_status(status: StatusCode) -> Self {
+        match status {
+            StatusCode::UNAUTHORIZED => ProblemCode::Unauthorized,
+            StatusCode::FORBIDDEN => ProblemCode::Forbidden,
+            StatusCode::NOT_FOUND => ProblemCode::NotFound,
+            StatusCode::METHOD_NOT_ALLOWED => ProblemCode::MethodNotAllowed,
+            StatusCode::CONFLICT => ProblemCode::Conflict,
+            StatusCode::GONE => ProblemCode::Gone,
+            StatusCode::PRECONDITION_FAILED => ProblemCode::PreconditionFailed,
+            StatusCode::PRECONDITION_REQUIRED => ProblemCode::PreconditionRequired,
+            StatusCode::TOO_MANY_REQUESTS => ProblemCode::Throttled,
+            StatusCode::SERVICE_UNAVAILABLE => ProblemCode::ServiceUnavailable,
+            status if status.is_server_error() => ProblemCode::InternalError,
+            _ => ProblemCode::BadRequest,
+        }
+    }
+
+    pub fn title(&self) -> &'static str {
+        match self {
+            ProblemCode::BadRequest => "Bad request",
+            ProblemCode::ValidationFailed => "Validation failed",
+            ProblemCode::InvalidPageToken => "Invalid page token",
+            ProblemCode::Unauthorized => "Unauthorized",
+            ProblemCode::Forbidden => "Forbidden",
+            ProblemCode::NotFound => "Not found",
+            ProblemCode::MethodNotAllowed => "Method not allowed",
+            ProblemCode::Conflict => "Conflict",
+            ProblemCode::EmailTaken => "Email address taken",
+            ProblemCode::UsernameTaken => "Username taken",
+            ProblemCode::Gone => "Gone",
+            ProblemCode::PreconditionFailed => "Precondition failed",
+            ProblemCode::PreconditionRequired => "Precondition required",
+            ProblemCode::Throttled => "Too many requests",
+            ProblemCode::InternalError => "Internal error",
+            ProblemCode::ServiceUnavailable => "Service unavailable",
+        }
+    }
+
+    /// The `type` URI of problems with this code, e.g.
+    /// `urn:cf-user:problem:email-taken`.
+    pub fn type_uri(&self) -> String {
+        let code = serde_json::to_value(self)
+            .ok()
+            .and_then(|code| code.as_str().map(str::to_owned))
+            .unwrap_or_default();
+
+        format!(
+            "urn:cf-user:problem:{}",
+            code.to_lowercase().replace('_', "-")
+        )
+    }
+}
+
+impl From<UniqueField> for ProblemCode {
+    fn from(field: UniqueField) -> Self {
+        match field {
+            UniqueField::Email => ProblemCode::EmailTaken,
+            UniqueField::Username => ProblemCode::UsernameTaken,
+        }
+    }
+}
+
+/// A request field that failed validation, e.g. `Email`, and why.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+pub struct FieldError {
+    pub field: String,
+    pub detail: String,
+}
+
+impl FieldError {
+    pub fn new(field: &str, detail: &str) -> Self {
+        Self {
+            field: field.to_string(),
+            detail: detail.to_string(),
+        }
+    }
+}
+
+/// An RFC 7807 problem detail, the body of every error response. `instance` is the
+/// API Gateway request ID, for finding the request in the logs.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+pub struct Problem {
+    #[serde(rename = "type")]
+    pub problem_type: String,
+    pub title: String,
+    pub status: u16,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub instance: Option<String>,
+    pub code: ProblemCode,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<FieldError>,
+}
+
+impl Problem {
+    /// A problem answered with `status`, with the general code for it.
+    pub fn new(status: StatusCode) -> Self {
+        Self::with_status(ProblemCode::from_status(status), status)
+    }
+
+    pub fn with_status(code: ProblemCode, status: StatusCode) -> Self {
+        Self {
+            problem_type: code.type_uri(),
+            title: code.title().to_string(),
+            status: status.as_u16(),
+            detail: None,
+            instance: None,
+            code,
+            errors: Vec::new(),
+        }
+    }
+
+    pub fn status_code(&self) -> StatusCode {
+        StatusCode::from_u16(self.status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR)
+    }
+
+    /// Replaces the general code, along with the `type` and `title` it sets.
+    pub fn with_code(mut self, code: ProblemCode) -> Self {
+        self.problem_type = code.type_uri();
+        self.title = code.title().to_string();
+        self.code = code;
+        self
+    }
+
+    pub fn with_detail(mut self, detail: &str) -> Self {
+        self.detail = Some(detail.to_string());
+        self
+    }
+
+    pub fn with_instance(mut self, instance: &str) -> Self {
+        self.instance = Some(instance.to_string());
+        self
+    }
+
+    pub fn with_errors(mut self, errors: Vec<FieldError>) -> Self {
+        self.errors = errors;
+        self
+    }
+}
+
+#[cfg(test)]
+mod unit_tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn should_serialize_as_a_problem_detail() {
+        let problem = Problem::new(StatusCode::BAD_REQUEST)
+            .with_code(ProblemCode::ValidationFailed)
+            .with_detail("Invalid user")
+            .with_instance("request-id")
+            .with_errors(vec![FieldError::new("Email", "Invalid email address")]);
+
+        assert_eq!(
+            serde_json::to_value(&problem).expect("Failed to serialize"),
+            json!({
+                "type": "urn:cf-user:problem:validation-failed",
+                "title": "Validation failed",
+                "status": 400,
+                "detail": "Invalid user",
+                "instance": "request-id",
+                "code": "VALIDATION_FAILED",
+                "errors": [{ "field": "Email", "detail": "Invalid email address" }]
+            })
+        );
+    }
+
+    #[test]
+    fn should_leave_out_missing_members() {
+        let problem = Problem::new(StatusCode::NOT_FOUND);
+
+        assert_eq!(
+            serde_json::to_value(&problem).expect("Failed to serialize"),
+            json!({
+                "type": "urn:cf-user:problem:not-found",
+                "title": "Not found",
+                "status": 404,
+                "code": "NOT_FOUND"
+            })
+        );
+    }
+
+    #[test]
+    fn should_keep_the_status_when_the_code_changes() {
+        let problem = Problem::new(StatusCode::CONFLICT).with_code(UniqueField::Email.into());
+
+        assert_eq!(problem.status_code(), StatusCode::CONFLICT);
+        assert_eq!(problem.code, ProblemCode::EmailTaken);
+        assert_eq!(problem.title, "Email address taken");
+    }
+
+    #[test]
+    fn should_treat_unknown_errors_by_class() {
+        assert_eq!(
+            ProblemCode::from_status(StatusCode::IM_A_TEAPOT),
+            ProblemCode::BadRequest
+        );
+        assert_eq!(
+            ProblemCode::from_status(StatusCode::BAD_GATEWAY),
+            ProblemCode::InternalError
+        );
+    }
+}
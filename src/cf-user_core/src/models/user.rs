@@ -1,15 +1,76 @@
-use super::super::error::Error;
-use super::super::ext::AttributeValuesExt;
-use aws_sdk_dynamodb::types::AttributeValue;
+use super::super::item::{AttributeField, AttributeValue, DynamoItem};
+use super::super::timestamp;
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::fmt;
 
-#[derive(Clone, Deserialize, Serialize, Debug)]
+/// Lifecycle of a user record. Deleted users are kept until their `ExpiresAt` TTL
+/// purges them, and can be restored until then.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+pub enum UserStatus {
+    #[default]
+    #[serde(rename = "active")]
+    Active,
+    #[serde(rename = "deleted")]
+    Deleted,
+}
+
+impl UserStatus {
+    pub fn value(&self) -> &'static str {
+        match *self {
+            UserStatus::Active => "active",
+            UserStatus::Deleted => "deleted",
+        }
+    }
+}
+
+impl AttributeField for UserStatus {
+    fn to_attribute(&self) -> Option<AttributeValue> {
+        Some(AttributeValue::S(self.value().to_owned()))
+    }
+
+    fn from_attribute(value: Option<AttributeValue>) -> Option<Self> {
+        match value?.as_s().ok()?.as_str() {
+            "deleted" => Some(UserStatus::Deleted),
+            _ => Some(UserStatus::Active),
+        }
+    }
+}
+
+impl fmt::Display for UserStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.value())
+    }
+}
+
+/// `User` fields as named in its JSON, in their default order. These are the fields
+/// callers may select, in exports and `?fields=`.
+pub static USER_FIELDS: [&str; 13] = [
+    "UserId",
+    "Username",
+    "FirstName",
+    "LastName",
+    "Email",
+    "ProfilePhoto",
+    "Summary",
+    "PhoneNumber",
+    "CreatedDate",
+    "UpdatedDate",
+    "Version",
+    "Status",
+    "DeletedAt",
+];
+
+/// A user item. `PK`, `SK` and `GSI1SK` are derived from the ID and username when
+/// the item is written. `GSI1PK` is read back as stored, so the email migration can
+/// find keys written before emails were normalized.
+#[derive(Clone, Deserialize, Serialize, Debug, PartialEq, DynamoItem)]
+#[dynamo(
+    rename_all = "PascalCase",
+    key(names = ["PK", "SK"], field = "user_id", prefix = "USER#"),
+    key(name = "GSI1SK", field = "username", prefix = "USERNAME#")
+)]
 pub struct User {
-    #[serde(rename = "PK", skip)]
-    pub pk: String,
-    #[serde(rename = "SK", skip)]
-    pub sk: String,
     #[serde(rename = "UserId")]
     pub user_id: String,
     #[serde(rename = "Username")]
@@ -27,111 +88,165 @@ pub struct User {
     #[serde(rename = "PhoneNumber")]
     pub phone_number: Option<String>,
     #[serde(rename = "GSI1PK", skip)]
+    #[dynamo(rename = "GSI1PK")]
     pub gsi1pk: String,
-    #[serde(rename = "GSI1SK", skip)]
-    pub gsi1sk: String,
-    #[serde(rename = "CreatedDate")]
-    pub created_date: String,
-    #[serde(rename = "UpdatedDate")]
-    pub updated_date: String,
+    #[serde(rename = "CreatedDate", with = "timestamp")]
+    pub created_date: DateTime<Utc>,
+    #[serde(rename = "UpdatedDate", with = "timestamp")]
+    pub updated_date: DateTime<Utc>,
+    /// Items written before versioning was introduced are treated as version 0.
+    #[serde(rename = "Version", default)]
+    #[dynamo(default)]
+    pub version: u64,
+    #[serde(rename = "Status", default)]
+    #[dynamo(default)]
+    pub status: UserStatus,
+    #[serde(rename = "DeletedAt", with = "timestamp::option", default)]
+    pub deleted_at: Option<DateTime<Utc>>,
+    #[serde(rename = "ExpiresAt", skip)]
+    pub expires_at: Option<i64>,
 }
 
-impl From<&User> for HashMap<String, AttributeValue> {
-    fn from(user: &User) -> HashMap<String, AttributeValue> {
-        let mut val = HashMap::new();
-        val.insert("PK".to_owned(), AttributeValue::S(user.pk.clone()));
-        val.insert("SK".to_owned(), AttributeValue::S(user.sk.clone()));
-        val.insert("UserId".to_owned(), AttributeValue::S(user.user_id.clone()));
-        val.insert(
-            "Username".to_owned(),
-            AttributeValue::S(user.username.clone()),
-        );
-        val.insert(
-            "FirstName".to_owned(),
-            match user.first_name.clone() {
-                Some(first_name) => AttributeValue::S(first_name),
-                None => AttributeValue::Null(true),
-            },
-        );
-        val.insert(
-            "LastName".to_owned(),
-            match user.last_name.clone() {
-                Some(last_name) => AttributeValue::S(last_name),
-                None => AttributeValue::Null(true),
-            },
-        );
-        val.insert("Email".to_owned(), AttributeValue::S(user.email.clone()));
-        val.insert(
-            "ProfilePhoto".to_owned(),
-            match user.profile_photo.clone() {
-                Some(profile_photo) => AttributeValue::S(profile_photo),
-                None => AttributeValue::Null(true),
-            },
+impl User {
+    pub fn is_deleted(&self) -> bool {
+        self.status == UserStatus::Deleted
+    }
+}
+
+#[cfg(test)]
+mod unit_tests {
+    use super::*;
+    use crate::item::Item;
+    use chrono::TimeZone;
+    use proptest::prelude::*;
+
+    fn create_item(attributes: &[(&str, AttributeValue)]) -> Item {
+        attributes
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.clone()))
+            .collect()
+    }
+
+    /// Millisecond timestamps between 1970 and the end of year 9999.
+    fn timestamp_strategy() -> impl Strategy<Value = DateTime<Utc>> {
+        (0..253_402_300_800_000i64).prop_map(|millis| Utc.timestamp_millis_opt(millis).unwrap())
+    }
+
+    fn user_strategy() -> impl Strategy<Value = User> {
+        let profile = (
+            any::<String>(),
+            any::<String>(),
+            any::<String>(),
+            any::<String>(),
+            proptest::option::of(any::<String>()),
+            proptest::option::of(any::<String>()),
+            proptest::option::of(any::<String>()),
+            proptest::option::of(any::<String>()),
+            proptest::option::of(any::<String>()),
         );
-        val.insert(
-            "Summary".to_owned(),
-            match user.summary.clone() {
-                Some(summary) => AttributeValue::S(summary),
-                None => AttributeValue::Null(true),
-            },
+        let record = (
+            timestamp_strategy(),
+            timestamp_strategy(),
+            any::<u64>(),
+            prop_oneof![Just(UserStatus::Active), Just(UserStatus::Deleted)],
+            proptest::option::of(timestamp_strategy()),
+            proptest::option::of(any::<i64>()),
         );
-        val.insert(
-            "PhoneNumber".to_owned(),
-            match user.phone_number.clone() {
-                Some(phone_number) => AttributeValue::S(phone_number),
-                None => AttributeValue::Null(true),
+
+        (profile, record).prop_map(
+            |(
+                (
+                    user_id,
+                    username,
+                    email,
+                    gsi1pk,
+                    first_name,
+                    last_name,
+                    profile_photo,
+                    summary,
+                    phone_number,
+                ),
+                (created_date, updated_date, version, status, deleted_at, expires_at),
+            )| User {
+                user_id,
+                username,
+                first_name,
+                last_name,
+                email,
+                profile_photo,
+                summary,
+                phone_number,
+                gsi1pk,
+                created_date,
+                updated_date,
+                version,
+                status,
+                deleted_at,
+                expires_at,
             },
-        );
-        val.insert("GSI1PK".to_owned(), AttributeValue::S(user.gsi1pk.clone()));
-        val.insert("GSI1SK".to_owned(), AttributeValue::S(user.gsi1sk.clone()));
-        val.insert(
-            "CreatedDate".to_owned(),
-            AttributeValue::S(user.created_date.clone()),
-        );
-        val.insert(
-            "UpdatedDate".to_owned(),
-            AttributeValue::S(user.updated_date.clone()),
-        );
+        )
+    }
+
+    #[test]
+    fn should_read_absent_and_null_optional_fields_as_none() {
+        let item = create_item(&[
+            ("UserId", AttributeValue::S("abc123".to_string())),
+            ("Username", AttributeValue::S("tester".to_string())),
+            ("Email", AttributeValue::S("tester@example.com".to_string())),
+            (
+                "GSI1PK",
+                AttributeValue::S("EMAIL#tester@example.com".to_string()),
+            ),
+            ("CreatedDate", AttributeValue::S("2023-07-01".to_string())),
+            ("UpdatedDate", AttributeValue::S("2023-07-01".to_string())),
+            ("FirstName", AttributeValue::Null(true)),
+            ("Summary", AttributeValue::Null(true)),
+        ]);
+
+        let user = User::try_from(item).expect("Invalid item");
+
+        assert_eq!(user.first_name, None);
+        assert_eq!(user.last_name, None);
+        assert_eq!(user.summary, None);
+        assert_eq!(user.version, 0);
+        assert_eq!(user.status, UserStatus::Active);
 
-        val
+        let item = Item::from(&user);
+        for name in [
+            "FirstName",
+            "LastName",
+            "ProfilePhoto",
+            "Summary",
+            "PhoneNumber",
+        ] {
+            assert!(!item.contains_key(name), "{} was written", name);
+        }
     }
-}
 
-impl TryFrom<HashMap<String, AttributeValue>> for User {
-    type Error = Error;
-
-    fn try_from(value: HashMap<String, AttributeValue>) -> Result<Self, Self::Error> {
-        Ok(User {
-            pk: value
-                .get_opt_s("PK")
-                .ok_or(Error::InternalError("Missing PK"))?,
-            sk: value
-                .get_opt_s("SK")
-                .ok_or(Error::InternalError("Missing SK"))?,
-            user_id: value
-                .get_opt_s("UserId")
-                .ok_or(Error::InternalError("Missing User ID"))?,
-            username: value.get_s("Username"),
-            first_name: Some(value.get_s("FirstName")),
-            last_name: Some(value.get_s("LastName")),
-            email: value
-                .get_opt_s("Email")
-                .ok_or(Error::InternalError("Missing Email"))?,
-            profile_photo: Some(value.get_s("ProfilePhoto")),
-            summary: Some(value.get_s("Summary")),
-            phone_number: Some(value.get_s("PhoneNumber")),
-            gsi1pk: value
-                .get_opt_s("GSI1PK")
-                .ok_or(Error::InternalError("Missing GSI1PK"))?,
-            gsi1sk: value
-                .get_opt_s("GSI1SK")
-                .ok_or(Error::InternalError("Missing GSI1SK"))?,
-            created_date: value
-                .get_opt_s("CreatedDate")
-                .ok_or(Error::InternalError("Missing Created Date"))?,
-            updated_date: value
-                .get_opt_s("UpdatedDate")
-                .ok_or(Error::InternalError("Missing Updated Date"))?,
-        })
+    #[test]
+    fn should_reject_optional_field_of_wrong_type() {
+        let item = create_item(&[
+            ("UserId", AttributeValue::S("abc123".to_string())),
+            ("Username", AttributeValue::S("tester".to_string())),
+            ("Email", AttributeValue::S("tester@example.com".to_string())),
+            (
+                "GSI1PK",
+                AttributeValue::S("EMAIL#tester@example.com".to_string()),
+            ),
+            ("CreatedDate", AttributeValue::S("2023-07-01".to_string())),
+            ("UpdatedDate", AttributeValue::S("2023-07-01".to_string())),
+            ("PhoneNumber", AttributeValue::N("5551234".to_string())),
+        ]);
+
+        assert!(User::try_from(item).is_err());
+    }
+
+    proptest! {
+        #[test]
+        fn should_convert_user_to_item_and_back(user in user_strategy()) {
+            let item = Item::from(&user);
+
+            prop_assert_eq!(User::try_from(item).expect("Invalid item"), user);
+        }
     }
 }
@@ -0,0 +1,196 @@
+use super::super::{item::DynamoItem, timestamp};
+use super::{change_context::ChangeContext, user::User};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+static SPEC_VERSION: &str = "1.0";
+static DATA_CONTENT_TYPE: &str = "application/json";
+
+/// Attributes that change on every write without being news to anyone.
+static BOOKKEEPING_ATTRIBUTES: [&str; 3] = ["UpdatedDate", "Version", "GSI1PK"];
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct UserCreated {
+    #[serde(rename = "User")]
+    pub user: User,
+}
+
+/// `changed_fields` names the `User` attributes whose values differ from before the
+/// update, e.g. `["Email", "Summary"]`.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct UserUpdated {
+    #[serde(rename = "User")]
+    pub user: User,
+    #[serde(rename = "ChangedFields")]
+    pub changed_fields: Vec<String>,
+}
+
+/// A user was soft deleted, or `purged` from the table for good once its retention
+/// ran out. A soft deleted user is later purged, so both are published for it.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct UserDeleted {
+    #[serde(rename = "UserId")]
+    pub user_id: String,
+    #[serde(rename = "Purged")]
+    pub purged: bool,
+}
+
+/// A change to a user that downstream services are notified of.
+#[derive(Clone, Debug, PartialEq)]
+pub enum UserEvent {
+    Created(UserCreated),
+    Updated(UserUpdated),
+    Deleted(UserDeleted),
+}
+
+impl UserEvent {
+    /// Works out what a change from `old` to `new` means for a user, or `None` when it
+    /// changes nothing but bookkeeping.
+    pub fn from_change(old: Option<User>, new: Option<User>) -> Option<Self> {
+        match (old, new) {
+            (None, Some(user)) => Some(UserEvent::Created(UserCreated { user })),
+            (Some(old), Some(user)) if user.is_deleted() && !old.is_deleted() => {
+                Some(UserEvent::Deleted(UserDeleted {
+                    user_id: user.user_id,
+                    purged: false,
+                }))
+            }
+            (Some(old), Some(user)) => {
+                let changed_fields = changed_fields(&old, &user);
+                (!changed_fields.is_empty()).then_some(UserEvent::Updated(UserUpdated {
+                    user,
+                    changed_fields,
+                }))
+            }
+            (Some(old), None) => Some(UserEvent::Deleted(UserDeleted {
+                user_id: old.user_id,
+                purged: true,
+            })),
+            (None, None) => None,
+        }
+    }
+
+    pub fn event_type(&self) -> &'static str {
+        match self {
+            UserEvent::Created(_) => "com.cf.user.created",
+            UserEvent::Updated(_) => "com.cf.user.updated",
+            UserEvent::Deleted(_) => "com.cf.user.deleted",
+        }
+    }
+
+    pub fn user_id(&self) -> &str {
+        match self {
+            UserEvent::Created(event) => &event.user.user_id,
+            UserEvent::Updated(event) => &event.user.user_id,
+            UserEvent::Deleted(event) => &event.user_id,
+        }
+    }
+
+    fn data(&self) -> Result<serde_json::Value, serde_json::Error> {
+        match self {
+            UserEvent::Created(event) => serde_json::to_value(event),
+            UserEvent::Updated(event) => serde_json::to_value(event),
+            UserEvent::Deleted(event) => serde_json::to_value(event),
+        }
+    }
+}
+
+/// Names the `User` attributes that differ between two versions of a user.
+fn changed_fields(old: &User, new: &User) -> Vec<String> {
+    let (old, new) = (old.to_item(), new.to_item());
+
+    User::ATTRIBUTES
+        .iter()
+        .filter(|name| !BOOKKEEPING_ATTRIBUTES.contains(name))
+        .filter(|name| old.get(**name) != new.get(**name))
+        .map(|name| name.to_string())
+        .collect()
+}
+
+/// The CloudEvents 1.0 JSON envelope events are published in. `subject` is the user
+/// ID, and `id` is unique per change, so consumers can drop redelivered events.
+///
+/// Events written by the API carry the `principalid` that made the change and the
+/// request ID as `correlationid`, as CloudEvents extension attributes.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct CloudEvent {
+    pub specversion: String,
+    pub id: String,
+    pub source: String,
+    #[serde(rename = "type")]
+    pub event_type: String,
+    pub subject: String,
+    #[serde(with = "timestamp")]
+    pub time: DateTime<Utc>,
+    pub datacontenttype: String,
+    pub data: serde_json::Value,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub principalid: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub correlationid: Option<String>,
+}
+
+impl CloudEvent {
+    pub fn new(
+        id: &str,
+        source: &str,
+        time: DateTime<Utc>,
+        event: &UserEvent,
+    ) -> Result<Self, serde_json::Error> {
+        Ok(Self {
+            specversion: SPEC_VERSION.to_owned(),
+            id: id.to_owned(),
+            source: source.to_owned(),
+            event_type: event.event_type().to_owned(),
+            subject: event.user_id().to_owned(),
+            time,
+            datacontenttype: DATA_CONTENT_TYPE.to_owned(),
+            data: event.data()?,
+            principalid: None,
+            correlationid: None,
+        })
+    }
+
+    pub fn with_context(mut self, context: &ChangeContext) -> Self {
+        self.principalid = Some(context.principal_id.clone());
+        self.correlationid = context.request_id.clone();
+        self
+    }
+}
+
+#[cfg(test)]
+mod unit_tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn should_wrap_event_in_envelope() {
+        let event = UserEvent::Deleted(UserDeleted {
+            user_id: "abc123".to_string(),
+            purged: false,
+        });
+        let time = Utc.with_ymd_and_hms(2023, 7, 1, 12, 0, 0).unwrap();
+
+        let context = ChangeContext::new("auth0|tester").with_request_id("request-1");
+
+        let envelope = CloudEvent::new("event-1", "cf-user", time, &event)
+            .expect("Invalid event")
+            .with_context(&context);
+
+        assert_eq!(
+            serde_json::to_value(&envelope).expect("Failed to serialize"),
+            serde_json::json!({
+                "specversion": "1.0",
+                "id": "event-1",
+                "source": "cf-user",
+                "type": "com.cf.user.deleted",
+                "subject": "abc123",
+                "time": "2023-07-01T12:00:00.000Z",
+                "datacontenttype": "application/json",
+                "data": { "UserId": "abc123", "Purged": false },
+                "principalid": "auth0|tester",
+                "correlationid": "request-1"
+            })
+        );
+    }
+}
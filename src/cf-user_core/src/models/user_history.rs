@@ -0,0 +1,229 @@
+use super::super::error::Error;
+use super::super::ext::AttributeValuesExt;
+use super::super::item::{AttributeField, AttributeValue, DynamoItem, Item};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::Mutex;
+use ulid::{Generator, Ulid};
+
+/// History IDs must sort in the order changes were made, even within a millisecond.
+static HISTORY_IDS: Mutex<Option<Generator>> = Mutex::new(None);
+
+/// Profile attributes whose changes are recorded in a user's history.
+static TRACKED_ATTRIBUTES: [&str; 9] = [
+    "Username",
+    "Email",
+    "FirstName",
+    "LastName",
+    "ProfilePhoto",
+    "Summary",
+    "PhoneNumber",
+    "Status",
+    "DeletedAt",
+];
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq)]
+pub enum HistoryOperation {
+    #[serde(rename = "create")]
+    Create,
+    #[serde(rename = "update")]
+    Update,
+    #[serde(rename = "delete")]
+    Delete,
+    #[serde(rename = "restore")]
+    Restore,
+}
+
+impl HistoryOperation {
+    pub fn value(&self) -> &'static str {
+        match *self {
+            HistoryOperation::Create => "create",
+            HistoryOperation::Update => "update",
+            HistoryOperation::Delete => "delete",
+            HistoryOperation::Restore => "restore",
+        }
+    }
+}
+
+impl fmt::Display for HistoryOperation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.value())
+    }
+}
+
+impl AttributeField for HistoryOperation {
+    fn to_attribute(&self) -> Option<AttributeValue> {
+        Some(AttributeValue::S(self.value().to_owned()))
+    }
+
+    fn from_attribute(value: Option<AttributeValue>) -> Option<Self> {
+        HistoryOperation::try_from(value?.as_s().ok()?.as_str()).ok()
+    }
+}
+
+impl TryFrom<&str> for HistoryOperation {
+    type Error = Error;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "create" => Ok(HistoryOperation::Create),
+            "update" => Ok(HistoryOperation::Update),
+            "delete" => Ok(HistoryOperation::Delete),
+            "restore" => Ok(HistoryOperation::Restore),
+            _ => Err(Error::InternalError("Unknown history operation")),
+        }
+    }
+}
+
+/// Value of an attribute before and after a change. `None` means the attribute was
+/// absent.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq, DynamoItem)]
+#[dynamo(rename_all = "PascalCase")]
+pub struct FieldChange {
+    #[serde(rename = "Before")]
+    pub before: Option<String>,
+    #[serde(rename = "After")]
+    pub after: Option<String>,
+}
+
+/// One mutation of a user, stored under the user's partition as
+/// `USER#<id>` / `HISTORY#<ulid>` so history sorts chronologically.
+#[derive(Clone, Deserialize, Serialize, Debug, PartialEq, DynamoItem)]
+#[dynamo(
+    rename_all = "PascalCase",
+    key(name = "PK", field = "user_id", prefix = "USER#"),
+    key(name = "SK", field = "history_id", prefix = "HISTORY#")
+)]
+pub struct UserHistory {
+    #[serde(rename = "UserId")]
+    pub user_id: String,
+    #[serde(rename = "HistoryId")]
+    pub history_id: String,
+    #[serde(rename = "Actor")]
+    #[dynamo(default)]
+    pub actor: String,
+    #[serde(rename = "Operation")]
+    pub operation: HistoryOperation,
+    #[serde(rename = "Changes")]
+    #[dynamo(default)]
+    pub changes: BTreeMap<String, FieldChange>,
+    #[serde(rename = "CreatedDate")]
+    pub created_date: String,
+}
+
+impl UserHistory {
+    pub fn new(
+        user_id: &str,
+        actor: &str,
+        operation: HistoryOperation,
+        changes: BTreeMap<String, FieldChange>,
+        created_date: &str,
+    ) -> Self {
+        Self {
+            user_id: user_id.to_owned(),
+            history_id: next_history_id(),
+            actor: actor.to_owned(),
+            operation,
+            changes,
+            created_date: created_date.to_owned(),
+        }
+    }
+
+    /// Compares the tracked attributes of a user item before and after a mutation.
+    /// `NULL` attributes, which older items used for missing fields, count as absent.
+    pub fn diff(before: Option<&Item>, after: &Item) -> BTreeMap<String, FieldChange> {
+        TRACKED_ATTRIBUTES
+            .iter()
+            .filter_map(|name| {
+                let before = before.and_then(|item| item.get_opt_s(name));
+                let after = after.get_opt_s(name);
+
+                (before != after).then(|| (name.to_string(), FieldChange { before, after }))
+            })
+            .collect()
+    }
+}
+
+fn next_history_id() -> String {
+    let mut generator = HISTORY_IDS.lock().unwrap();
+
+    generator
+        .get_or_insert_with(Generator::new)
+        .generate()
+        .unwrap_or_else(|_| Ulid::new())
+        .to_string()
+}
+
+#[cfg(test)]
+mod unit_tests {
+    use super::*;
+
+    fn item(attributes: &[(&str, AttributeValue)]) -> Item {
+        attributes
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn should_diff_only_changed_tracked_attributes() {
+        let before = item(&[
+            ("Email", AttributeValue::S("old@example.com".to_string())),
+            ("Username", AttributeValue::S("tester".to_string())),
+            ("FirstName", AttributeValue::Null(true)),
+            ("UpdatedDate", AttributeValue::S("2023-01-01".to_string())),
+        ]);
+        let after = item(&[
+            ("Email", AttributeValue::S("new@example.com".to_string())),
+            ("Username", AttributeValue::S("tester".to_string())),
+            ("UpdatedDate", AttributeValue::S("2023-01-02".to_string())),
+            ("Summary", AttributeValue::S("Hello".to_string())),
+        ]);
+
+        let changes = UserHistory::diff(Some(&before), &after);
+
+        assert_eq!(
+            changes,
+            BTreeMap::from([
+                (
+                    "Email".to_string(),
+                    FieldChange {
+                        before: Some("old@example.com".to_string()),
+                        after: Some("new@example.com".to_string()),
+                    }
+                ),
+                (
+                    "Summary".to_string(),
+                    FieldChange {
+                        before: None,
+                        after: Some("Hello".to_string()),
+                    }
+                ),
+            ])
+        );
+    }
+
+    #[test]
+    fn should_convert_history_to_item_and_back() {
+        let history = UserHistory::new(
+            "01H5FS6FKMB0YY0VDJ015741BJ",
+            "auth0|tester",
+            HistoryOperation::Update,
+            BTreeMap::from([(
+                "Email".to_string(),
+                FieldChange {
+                    before: Some("old@example.com".to_string()),
+                    after: None,
+                },
+            )]),
+            "2023-07-18 12:00:00 UTC",
+        );
+
+        let item: Item = (&history).into();
+        assert_eq!(item.get_s("PK"), "USER#01H5FS6FKMB0YY0VDJ015741BJ");
+        assert_eq!(item.get_s("SK"), format!("HISTORY#{}", history.history_id));
+
+        assert_eq!(UserHistory::try_from(item).expect("Invalid item"), history);
+    }
+}
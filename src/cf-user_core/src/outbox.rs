@@ -0,0 +1,283 @@
+use super::{event_sink::EventSink, repository::UserRepository};
+use serde::Serialize;
+use std::collections::HashSet;
+use tracing::warn;
+
+/// Outbox entries read per page while relaying.
+pub static RELAY_BATCH_SIZE: i32 = 25;
+
+/// Failed relays after which an entry is moved to the dead-letter partition.
+pub static MAX_RELAY_ATTEMPTS: u32 = 5;
+
+/// What one relay did with the outbox entries it read.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct RelayReport {
+    /// Entries published and removed.
+    #[serde(rename = "Published")]
+    pub published: usize,
+    /// Entries the sink rejected, left for a later relay.
+    #[serde(rename = "Failed")]
+    pub failed: usize,
+    /// Entries left unpublished because an earlier event about the same user failed.
+    #[serde(rename = "HeldBack")]
+    pub held_back: usize,
+    /// Entries given up on after `MAX_RELAY_ATTEMPTS` failures.
+    #[serde(rename = "DeadLettered")]
+    pub dead_lettered: usize,
+}
+
+/// Publishes every pending outbox entry to `sink`, oldest first, deleting each entry
+/// once the sink has accepted its event.
+///
+/// An entry the sink rejects does not stop the relay. Its failure is counted on the
+/// entry and it stays for the next relay, along with every later entry about the same
+/// user, so that user's events keep their order. After `MAX_RELAY_ATTEMPTS` failures
+/// the entry is moved to the dead-letter partition and later events about the user
+/// go out without it.
+///
+/// Delivery is at least once: an event is published again when deleting its entry
+/// fails, or when two relays drain the outbox at the same time.
+pub async fn relay_outbox(
+    repository: &dyn UserRepository,
+    sink: &dyn EventSink,
+) -> Result<RelayReport, Box<dyn std::error::Error>> {
+    let mut report = RelayReport::default();
+    let mut failed_subjects: HashSet<String> = HashSet::new();
+    let mut after: Option<String> = None;
+
+    loop {
+        let entries = repository
+            .list_outbox_entries(RELAY_BATCH_SIZE, after.as_deref())
+            .await?;
+        let Some(last) = entries.last() else {
+            return Ok(report);
+        };
+        after = Some(last.entry_id.clone());
+
+        for entry in entries {
+            if failed_subjects.contains(&entry.event.subject) {
+                report.held_back += 1;
+                continue;
+            }
+
+            if let Err(err) = sink.publish(&entry.event).await {
+                warn!("Failed to publish outbox entry {}: {}", entry.entry_id, err);
+
+                if entry.attempts + 1 >= MAX_RELAY_ATTEMPTS {
+                    repository.dead_letter_outbox_entry(&entry).await?;
+                    report.dead_lettered += 1;
+                } else {
+                    repository.record_outbox_attempt(&entry.entry_id).await?;
+                    failed_subjects.insert(entry.event.subject.clone());
+                    report.failed += 1;
+                }
+                continue;
+            }
+
+            repository.delete_outbox_entry(&entry.entry_id).await?;
+            report.published += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod unit_tests {
+    use super::*;
+    use crate::{
+        args::{create_user_args::CreateUserArgs, update_user_args::UpdateUserArgs},
+        event_sink::InMemoryEventSink,
+        models::change_context::ChangeContext,
+        repository::InMemoryUserRepository,
+    };
+
+    fn create_args(username: &str) -> CreateUserArgs {
+        CreateUserArgs {
+            username: username.to_string(),
+            first_name: None,
+            last_name: None,
+            email: format!("{}@example.com", username),
+            profile_photo: None,
+            summary: None,
+            phone_number: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn should_publish_changes_in_order_with_their_context() {
+        let repository = InMemoryUserRepository::new();
+        let sink = InMemoryEventSink::new();
+        let context = ChangeContext::new("auth0|tester").with_request_id("request-1");
+
+        let user_id = repository
+            .create_user(create_args("tester"), &context)
+            .await
+            .expect("Failed to create user");
+        repository
+            .update_user(
+                &user_id,
+                UpdateUserArgs {
+                    username: "tester".to_string(),
+                    first_name: None,
+                    last_name: None,
+                    email: "tester@example.com".to_string(),
+                    profile_photo: None,
+                    summary: Some("Hello".to_string()),
+                    phone_number: None,
+                },
+                None,
+                &ChangeContext::new("auth0|editor").with_request_id("request-2"),
+            )
+            .await
+            .expect("Failed to update user");
+        repository
+            .delete_user(&user_id, None, &ChangeContext::new("admin"))
+            .await
+            .expect("Failed to delete user");
+
+        let report = relay_outbox(&repository, &sink)
+            .await
+            .expect("Relay failed");
+
+        assert_eq!(report.published, 3);
+        let events = sink.events();
+        let summary: Vec<(&str, &str, Option<&str>, Option<&str>)> = events
+            .iter()
+            .map(|event| {
+                (
+                    event.event_type.as_str(),
+                    event.subject.as_str(),
+                    event.principalid.as_deref(),
+                    event.correlationid.as_deref(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (
+                    "com.cf.user.created",
+                    user_id.as_str(),
+                    Some("auth0|tester"),
+                    Some("request-1")
+                ),
+                (
+                    "com.cf.user.updated",
+                    user_id.as_str(),
+                    Some("auth0|editor"),
+                    Some("request-2")
+                ),
+                ("com.cf.user.deleted", user_id.as_str(), Some("admin"), None),
+            ]
+        );
+        assert_eq!(
+            events[1].data["ChangedFields"],
+            serde_json::json!(["Summary"])
+        );
+        assert!(repository
+            .list_outbox_entries(RELAY_BATCH_SIZE, None)
+            .await
+            .expect("Failed to list outbox")
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn should_relay_past_entries_the_sink_rejects() {
+        let repository = InMemoryUserRepository::new();
+        let sink = InMemoryEventSink::new();
+        let context = ChangeContext::new("admin");
+
+        let first = repository
+            .create_user(create_args("first"), &context)
+            .await
+            .expect("Failed to create user");
+        let second = repository
+            .create_user(create_args("second"), &context)
+            .await
+            .expect("Failed to create user");
+        repository
+            .delete_user(&first, None, &context)
+            .await
+            .expect("Failed to delete user");
+        sink.fail_subject(&first);
+
+        let report = relay_outbox(&repository, &sink)
+            .await
+            .expect("Relay failed");
+
+        assert_eq!(
+            report,
+            RelayReport {
+                published: 1,
+                failed: 1,
+                held_back: 1,
+                dead_lettered: 0,
+            }
+        );
+        assert_eq!(sink.events()[0].subject, second);
+        let pending = repository
+            .list_outbox_entries(RELAY_BATCH_SIZE, None)
+            .await
+            .expect("Failed to list outbox");
+        let pending: Vec<(&str, &str, u32)> = pending
+            .iter()
+            .map(|entry| {
+                (
+                    entry.event.subject.as_str(),
+                    entry.event.event_type.as_str(),
+                    entry.attempts,
+                )
+            })
+            .collect();
+        assert_eq!(
+            pending,
+            vec![
+                (first.as_str(), "com.cf.user.created", 1),
+                (first.as_str(), "com.cf.user.deleted", 0),
+            ]
+        );
+
+        sink.recover_subject(&first);
+        let report = relay_outbox(&repository, &sink)
+            .await
+            .expect("Relay failed");
+
+        assert_eq!(report.published, 2);
+        let types: Vec<String> = sink
+            .events()
+            .into_iter()
+            .skip(1)
+            .map(|event| event.event_type)
+            .collect();
+        assert_eq!(types, vec!["com.cf.user.created", "com.cf.user.deleted"]);
+    }
+
+    #[tokio::test]
+    async fn should_dead_letter_entries_after_max_attempts() {
+        let repository = InMemoryUserRepository::new();
+        let sink = InMemoryEventSink::new();
+
+        let user_id = repository
+            .create_user(create_args("tester"), &ChangeContext::new("admin"))
+            .await
+            .expect("Failed to create user");
+        sink.fail_subject(&user_id);
+
+        for _ in 1..MAX_RELAY_ATTEMPTS {
+            let report = relay_outbox(&repository, &sink)
+                .await
+                .expect("Relay failed");
+            assert_eq!(report.failed, 1);
+        }
+        let report = relay_outbox(&repository, &sink)
+            .await
+            .expect("Relay failed");
+
+        assert_eq!(report.dead_lettered, 1);
+        assert!(repository
+            .list_outbox_entries(RELAY_BATCH_SIZE, None)
+            .await
+            .expect("Failed to list outbox")
+            .is_empty());
+        assert_eq!(repository.len(), 5);
+    }
+}
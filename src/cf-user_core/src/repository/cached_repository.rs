@@ -0,0 +1,464 @@
+use super::super::{
+    args::{
+        create_user_args::CreateUserArgs, list_user_history_args::ListUserHistoryArgs,
+        list_users_args::ListUsersArgs, scan_users_args::ScanUsersArgs,
+        update_user_args::UpdateUserArgs,
+    },
+    cache::UserCache,
+    error::UniqueField,
+    fieldset::Fieldset,
+    models::{
+        batch_get_result::BatchGetResult, change_context::ChangeContext, outbox_entry::OutboxEntry,
+        paginated_result::PaginatedResult, partial_user::PartialUser, user::User,
+        user_history::UserHistory,
+    },
+};
+use super::UserRepository;
+
+use async_trait::async_trait;
+use std::sync::Arc;
+
+/// `UserRepository` that answers lookups by ID and by email from a `UserCache`,
+/// reading through to `inner` on a miss. Every write drops the entries it touches
+/// once `inner` has run it, whether or not it succeeded, since a write that timed
+/// out may still have been applied.
+///
+/// Writes made through another process are not seen until their entries expire.
+#[derive(Clone)]
+pub struct CachedUserRepository {
+    inner: Arc<dyn UserRepository>,
+    cache: Arc<UserCache>,
+    bypass: bool,
+}
+
+impl CachedUserRepository {
+    pub fn new(inner: Arc<dyn UserRepository>, cache: Arc<UserCache>) -> Self {
+        Self {
+            inner,
+            cache,
+            bypass: false,
+        }
+    }
+
+    /// Reads every lookup from `inner` while still caching what it returns, for
+    /// requests sent with `Cache-Control: no-cache`.
+    pub fn with_bypass(mut self, bypass: bool) -> Self {
+        self.bypass = bypass;
+        self
+    }
+}
+
+#[async_trait]
+impl UserRepository for CachedUserRepository {
+    async fn get_user_by_id(
+        &self,
+        user_id: &str,
+        include_deleted: bool,
+    ) -> Result<Option<User>, Box<dyn std::error::Error>> {
+        let cached = match self.bypass {
+            true => None,
+            false => self.cache.get_user(user_id),
+        };
+        let user = match cached {
+            Some(user) => user,
+            None => {
+                let user = self.inner.get_user_by_id(user_id, true).await?;
+                self.cache.put_user(user_id, user.clone());
+                user
+            }
+        };
+
+        Ok(user.filter(|user| include_deleted || !user.is_deleted()))
+    }
+
+    /// Answered from a cached lookup when there is one. Otherwise the projected read
+    /// goes to `inner` and is not cached, since it does not hold the whole user.
+    async fn get_user_fields_by_id(
+        &self,
+        user_id: &str,
+        include_deleted: bool,
+        fields: &Fieldset,
+    ) -> Result<Option<PartialUser>, Box<dyn std::error::Error>> {
+        let cached = match self.bypass {
+            true => None,
+            false => self.cache.get_user(user_id),
+        };
+
+        match cached {
+            Some(user) => Ok(user
+                .filter(|user| include_deleted || !user.is_deleted())
+                .map(|user| fields.select_user(&user))),
+            None => {
+                self.inner
+                    .get_user_fields_by_id(user_id, include_deleted, fields)
+                    .await
+            }
+        }
+    }
+
+    async fn get_user_by_email(
+        &self,
+        email: &str,
+        include_deleted: bool,
+    ) -> Result<Option<Vec<User>>, Box<dyn std::error::Error>> {
+        let cached = match self.bypass {
+            true => None,
+            false => self.cache.get_email(email),
+        };
+        let users = match cached {
+            Some(users) => users,
+            None => {
+                let users = self.inner.get_user_by_email(email, true).await?;
+                self.cache.put_email(email, users.clone());
+                users
+            }
+        };
+
+        Ok(users
+            .map(|users| {
+                users
+                    .into_iter()
+                    .filter(|user| include_deleted || !user.is_deleted())
+                    .collect::<Vec<User>>()
+            })
+            .filter(|users| !users.is_empty()))
+    }
+
+    async fn get_users_by_ids(
+        &self,
+        user_ids: &[String],
+        include_deleted: bool,
+    ) -> Result<BatchGetResult<User>, Box<dyn std::error::Error>> {
+        self.inner.get_users_by_ids(user_ids, include_deleted).await
+    }
+
+    async fn create_user(
+        &self,
+        input: CreateUserArgs,
+        context: &ChangeContext,
+    ) -> Result<String, Box<dyn std::error::Error>> {
+        let email = input.email.clone();
+        let result = self.inner.create_user(input, context).await;
+        self.cache.invalidate_email(&email);
+
+        result
+    }
+
+    async fn get_unique_value_owner(
+        &self,
+        field: UniqueField,
+        value: &str,
+    ) -> Result<Option<String>, Box<dyn std::error::Error>> {
+        self.inner.get_unique_value_owner(field, value).await
+    }
+
+    async fn update_user(
+        &self,
+        user_id: &str,
+        input: UpdateUserArgs,
+        expected_version: Option<u64>,
+        context: &ChangeContext,
+    ) -> Result<bool, Box<dyn std::error::Error>> {
+        let email = input.email.clone();
+        let result = self
+            .inner
+            .update_user(user_id, input, expected_version, context)
+            .await;
+        self.cache.invalidate_user(user_id);
+        self.cache.invalidate_email(&email);
+
+        result
+    }
+
+    async fn delete_user(
+        &self,
+        user_id: &str,
+        expected_version: Option<u64>,
+        context: &ChangeContext,
+    ) -> Result<bool, Box<dyn std::error::Error>> {
+        let result = self
+            .inner
+            .delete_user(user_id, expected_version, context)
+            .await;
+        self.cache.invalidate_user(user_id);
+
+        result
+    }
+
+    async fn restore_user(
+        &self,
+        user_id: &str,
+        expected_version: Option<u64>,
+        context: &ChangeContext,
+    ) -> Result<bool, Box<dyn std::error::Error>> {
+        let result = self
+            .inner
+            .restore_user(user_id, expected_version, context)
+            .await;
+        self.cache.invalidate_user(user_id);
+
+        result
+    }
+
+    async fn list_user_history(
+        &self,
+        user_id: &str,
+        args: ListUserHistoryArgs,
+    ) -> Result<PaginatedResult<UserHistory>, Box<dyn std::error::Error>> {
+        self.inner.list_user_history(user_id, args).await
+    }
+
+    async fn scan_users(
+        &self,
+        args: ScanUsersArgs,
+    ) -> Result<PaginatedResult<User>, Box<dyn std::error::Error>> {
+        self.inner.scan_users(args).await
+    }
+
+    async fn list_users(
+        &self,
+        args: ListUsersArgs,
+    ) -> Result<PaginatedResult<User>, Box<dyn std::error::Error>> {
+        self.inner.list_users(args).await
+    }
+
+    async fn list_user_fields(
+        &self,
+        args: ListUsersArgs,
+        fields: &Fieldset,
+    ) -> Result<PaginatedResult<PartialUser>, Box<dyn std::error::Error>> {
+        self.inner.list_user_fields(args, fields).await
+    }
+
+    async fn list_outbox_entries(
+        &self,
+        limit: i32,
+        after: Option<&str>,
+    ) -> Result<Vec<OutboxEntry>, Box<dyn std::error::Error>> {
+        self.inner.list_outbox_entries(limit, after).await
+    }
+
+    async fn delete_outbox_entry(&self, entry_id: &str) -> Result<(), Box<dyn std::error::Error>> {
+        self.inner.delete_outbox_entry(entry_id).await
+    }
+
+    async fn record_outbox_attempt(
+        &self,
+        entry_id: &str,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.inner.record_outbox_attempt(entry_id).await
+    }
+
+    async fn dead_letter_outbox_entry(
+        &self,
+        entry: &OutboxEntry,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.inner.dead_letter_outbox_entry(entry).await
+    }
+}
+
+#[cfg(test)]
+mod unit_tests {
+    use super::*;
+    use crate::{cache::CacheStats, repository::InMemoryUserRepository};
+    use aws_sdk_dynamodb::types::AttributeValue;
+    use std::num::NonZeroUsize;
+    use tokio::time::Duration;
+
+    fn repositories() -> (
+        Arc<InMemoryUserRepository>,
+        Arc<UserCache>,
+        CachedUserRepository,
+    ) {
+        let inner = Arc::new(InMemoryUserRepository::new());
+        let cache = Arc::new(UserCache::new(
+            NonZeroUsize::new(10).unwrap(),
+            Duration::from_secs(60),
+        ));
+        let repository = CachedUserRepository::new(inner.clone(), cache.clone());
+
+        (inner, cache, repository)
+    }
+
+    fn create_args(username: &str) -> CreateUserArgs {
+        CreateUserArgs {
+            username: username.to_string(),
+            first_name: None,
+            last_name: None,
+            email: format!("{}@example.com", username),
+            profile_photo: None,
+            summary: None,
+            phone_number: None,
+        }
+    }
+
+    /// Changes the stored user behind the cache's back, as another Lambda would.
+    fn set_summary(inner: &InMemoryUserRepository, user_id: &str, summary: &str) {
+        let key = format!("USER#{}", user_id);
+        let mut item = inner.get_item(&key, &key).expect("Missing user");
+        item.insert(
+            "Summary".to_string(),
+            AttributeValue::S(summary.to_string()),
+        );
+        inner.put_item(item);
+    }
+
+    #[tokio::test]
+    async fn should_answer_repeated_lookups_from_the_cache() {
+        let (inner, cache, repository) = repositories();
+        let user_id = repository
+            .create_user(create_args("tester"), &ChangeContext::new("admin"))
+            .await
+            .expect("Failed to create user");
+
+        repository
+            .get_user_by_id(&user_id, false)
+            .await
+            .expect("Lookup failed");
+        repository
+            .get_user_by_email("TESTER@example.com", false)
+            .await
+            .expect("Lookup failed");
+        set_summary(&inner, &user_id, "Changed elsewhere");
+
+        let user = repository
+            .get_user_by_id(&user_id, false)
+            .await
+            .expect("Lookup failed")
+            .expect("Missing user");
+        assert_eq!(user.summary, None);
+        let users = repository
+            .get_user_by_email("tester@example.com", false)
+            .await
+            .expect("Lookup failed")
+            .expect("Missing user");
+        assert_eq!(users[0].summary, None);
+
+        assert_eq!(cache.stats(), CacheStats { hits: 2, misses: 2 });
+    }
+
+    #[tokio::test]
+    async fn should_cache_lookups_that_found_nothing() {
+        let (_inner, cache, repository) = repositories();
+
+        for _ in 0..2 {
+            assert!(repository
+                .get_user_by_id("01H4MISSING", false)
+                .await
+                .expect("Lookup failed")
+                .is_none());
+        }
+        assert!(repository
+            .get_user_by_email("tester@example.com", false)
+            .await
+            .expect("Lookup failed")
+            .is_none());
+        assert_eq!(cache.stats(), CacheStats { hits: 1, misses: 2 });
+
+        repository
+            .create_user(create_args("tester"), &ChangeContext::new("admin"))
+            .await
+            .expect("Failed to create user");
+
+        assert!(repository
+            .get_user_by_email("tester@example.com", false)
+            .await
+            .expect("Lookup failed")
+            .is_some());
+    }
+
+    #[tokio::test]
+    async fn should_drop_entries_its_writes_touch() {
+        let (_inner, _cache, repository) = repositories();
+        let context = ChangeContext::new("admin");
+        let user_id = repository
+            .create_user(create_args("tester"), &context)
+            .await
+            .expect("Failed to create user");
+        repository
+            .get_user_by_id(&user_id, false)
+            .await
+            .expect("Lookup failed");
+        repository
+            .get_user_by_email("tester@example.com", false)
+            .await
+            .expect("Lookup failed");
+
+        repository
+            .update_user(
+                &user_id,
+                UpdateUserArgs {
+                    username: "tester".to_string(),
+                    first_name: None,
+                    last_name: None,
+                    email: "renamed@example.com".to_string(),
+                    profile_photo: None,
+                    summary: None,
+                    phone_number: None,
+                },
+                None,
+                &context,
+            )
+            .await
+            .expect("Failed to update user");
+
+        let user = repository
+            .get_user_by_id(&user_id, false)
+            .await
+            .expect("Lookup failed")
+            .expect("Missing user");
+        assert_eq!(user.email, "renamed@example.com");
+        assert!(repository
+            .get_user_by_email("tester@example.com", false)
+            .await
+            .expect("Lookup failed")
+            .is_none());
+
+        repository
+            .delete_user(&user_id, None, &context)
+            .await
+            .expect("Failed to delete user");
+
+        assert!(repository
+            .get_user_by_id(&user_id, false)
+            .await
+            .expect("Lookup failed")
+            .is_none());
+        let user = repository
+            .get_user_by_id(&user_id, true)
+            .await
+            .expect("Lookup failed")
+            .expect("Missing deleted user");
+        assert!(user.is_deleted());
+    }
+
+    #[tokio::test]
+    async fn should_read_through_when_bypassed() {
+        let (inner, cache, repository) = repositories();
+        let user_id = repository
+            .create_user(create_args("tester"), &ChangeContext::new("admin"))
+            .await
+            .expect("Failed to create user");
+        repository
+            .get_user_by_id(&user_id, false)
+            .await
+            .expect("Lookup failed");
+        set_summary(&inner, &user_id, "Changed elsewhere");
+
+        let bypassing = repository.clone().with_bypass(true);
+        let user = bypassing
+            .get_user_by_id(&user_id, false)
+            .await
+            .expect("Lookup failed")
+            .expect("Missing user");
+        assert_eq!(user.summary.as_deref(), Some("Changed elsewhere"));
+
+        let user = repository
+            .get_user_by_id(&user_id, false)
+            .await
+            .expect("Lookup failed")
+            .expect("Missing user");
+        assert_eq!(user.summary.as_deref(), Some("Changed elsewhere"));
+        assert_eq!(cache.stats(), CacheStats { hits: 1, misses: 1 });
+    }
+}
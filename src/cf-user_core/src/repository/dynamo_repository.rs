@@ -0,0 +1,311 @@
+use super::super::{
+    args::{
+        create_user_args::CreateUserArgs, list_user_history_args::ListUserHistoryArgs,
+        list_users_args::ListUsersArgs, scan_users_args::ScanUsersArgs,
+        update_user_args::UpdateUserArgs,
+    },
+    dynamo,
+    error::UniqueField,
+    fieldset::Fieldset,
+    models::{
+        batch_get_result::BatchGetResult, change_context::ChangeContext, outbox_entry::OutboxEntry,
+        paginated_result::PaginatedResult, partial_user::PartialUser, user::User,
+        user_history::UserHistory,
+    },
+    retry::RetryPolicy,
+};
+use super::UserRepository;
+
+use async_trait::async_trait;
+use aws_sdk_dynamodb::Client;
+use std::collections::HashMap;
+use std::time::SystemTime;
+
+/// `UserRepository` backed by a DynamoDB table. Calls that fail with a retryable
+/// `RepositoryError` are repeated under its `RetryPolicy`. Writes are only repeated
+/// when DynamoDB rejected them outright, and batch creates not at all, since a
+/// partly written batch cannot safely be written again.
+#[derive(Clone, Debug)]
+pub struct DynamoUserRepository {
+    client: Client,
+    table: String,
+    retry: RetryPolicy,
+}
+
+impl DynamoUserRepository {
+    pub fn new(client: Client, table: &str) -> Self {
+        Self {
+            client,
+            table: table.to_string(),
+            retry: RetryPolicy::default(),
+        }
+    }
+
+    /// Stops retrying in time for a Lambda invocation to answer before `deadline`.
+    pub fn with_deadline(mut self, deadline: SystemTime) -> Self {
+        self.retry = self.retry.with_deadline(deadline);
+        self
+    }
+
+    pub fn client(&self) -> &Client {
+        &self.client
+    }
+
+    pub fn table(&self) -> &str {
+        &self.table
+    }
+}
+
+#[async_trait]
+impl UserRepository for DynamoUserRepository {
+    async fn get_user_by_id(
+        &self,
+        user_id: &str,
+        include_deleted: bool,
+    ) -> Result<Option<User>, Box<dyn std::error::Error>> {
+        let user = self
+            .retry
+            .run(true, || {
+                dynamo::get_user_by_id(&self.client, &self.table, user_id)
+            })
+            .await?;
+
+        Ok(user.filter(|user| include_deleted || !user.is_deleted()))
+    }
+
+    async fn get_user_fields_by_id(
+        &self,
+        user_id: &str,
+        include_deleted: bool,
+        fields: &Fieldset,
+    ) -> Result<Option<PartialUser>, Box<dyn std::error::Error>> {
+        let user = self
+            .retry
+            .run(true, || {
+                dynamo::get_user_fields_by_id(&self.client, &self.table, user_id, fields)
+            })
+            .await?;
+
+        Ok(user.filter(|user| include_deleted || !user.is_deleted()))
+    }
+
+    async fn get_user_by_email(
+        &self,
+        email: &str,
+        include_deleted: bool,
+    ) -> Result<Option<Vec<User>>, Box<dyn std::error::Error>> {
+        let users = self
+            .retry
+            .run(true, || {
+                dynamo::get_user_by_email(&self.client, &self.table, email)
+            })
+            .await?;
+
+        Ok(users
+            .map(|users| {
+                users
+                    .into_iter()
+                    .filter(|user| include_deleted || !user.is_deleted())
+                    .collect::<Vec<User>>()
+            })
+            .filter(|users| !users.is_empty()))
+    }
+
+    async fn get_users_by_ids(
+        &self,
+        user_ids: &[String],
+        include_deleted: bool,
+    ) -> Result<BatchGetResult<User>, Box<dyn std::error::Error>> {
+        let result = self
+            .retry
+            .run(true, || {
+                dynamo::get_users_by_ids(&self.client, &self.table, user_ids)
+            })
+            .await?;
+
+        if include_deleted {
+            return Ok(result);
+        }
+
+        let found: HashMap<String, User> = result
+            .data
+            .into_iter()
+            .filter(|user| !user.is_deleted())
+            .map(|user| (user.user_id.clone(), user))
+            .collect();
+
+        Ok(dynamo::order_batch_result(user_ids, found))
+    }
+
+    async fn create_user(
+        &self,
+        input: CreateUserArgs,
+        context: &ChangeContext,
+    ) -> Result<String, Box<dyn std::error::Error>> {
+        self.retry
+            .run(false, || {
+                dynamo::create_user(&self.client, &self.table, input.clone(), context)
+            })
+            .await
+    }
+
+    async fn get_unique_value_owner(
+        &self,
+        field: UniqueField,
+        value: &str,
+    ) -> Result<Option<String>, Box<dyn std::error::Error>> {
+        self.retry
+            .run(true, || {
+                dynamo::get_unique_value_owner(&self.client, &self.table, field, value)
+            })
+            .await
+    }
+
+    async fn update_user(
+        &self,
+        user_id: &str,
+        input: UpdateUserArgs,
+        expected_version: Option<u64>,
+        context: &ChangeContext,
+    ) -> Result<bool, Box<dyn std::error::Error>> {
+        self.retry
+            .run(false, || {
+                dynamo::update_user(
+                    &self.client,
+                    &self.table,
+                    user_id,
+                    input.clone(),
+                    expected_version,
+                    context,
+                )
+            })
+            .await
+    }
+
+    async fn delete_user(
+        &self,
+        user_id: &str,
+        expected_version: Option<u64>,
+        context: &ChangeContext,
+    ) -> Result<bool, Box<dyn std::error::Error>> {
+        self.retry
+            .run(false, || {
+                dynamo::delete_user(
+                    &self.client,
+                    &self.table,
+                    user_id,
+                    expected_version,
+                    context,
+                )
+            })
+            .await
+    }
+
+    async fn restore_user(
+        &self,
+        user_id: &str,
+        expected_version: Option<u64>,
+        context: &ChangeContext,
+    ) -> Result<bool, Box<dyn std::error::Error>> {
+        self.retry
+            .run(false, || {
+                dynamo::restore_user(
+                    &self.client,
+                    &self.table,
+                    user_id,
+                    expected_version,
+                    context,
+                )
+            })
+            .await
+    }
+
+    async fn list_user_history(
+        &self,
+        user_id: &str,
+        args: ListUserHistoryArgs,
+    ) -> Result<PaginatedResult<UserHistory>, Box<dyn std::error::Error>> {
+        self.retry
+            .run(true, || {
+                dynamo::list_user_history(&self.client, &self.table, user_id, args.clone())
+            })
+            .await
+    }
+
+    async fn scan_users(
+        &self,
+        args: ScanUsersArgs,
+    ) -> Result<PaginatedResult<User>, Box<dyn std::error::Error>> {
+        self.retry
+            .run(true, || {
+                dynamo::scan_users(&self.client, &self.table, args.clone())
+            })
+            .await
+    }
+
+    async fn list_users(
+        &self,
+        args: ListUsersArgs,
+    ) -> Result<PaginatedResult<User>, Box<dyn std::error::Error>> {
+        self.retry
+            .run(true, || {
+                dynamo::list_users(&self.client, &self.table, args.clone())
+            })
+            .await
+    }
+
+    async fn list_user_fields(
+        &self,
+        args: ListUsersArgs,
+        fields: &Fieldset,
+    ) -> Result<PaginatedResult<PartialUser>, Box<dyn std::error::Error>> {
+        self.retry
+            .run(true, || {
+                dynamo::list_user_fields(&self.client, &self.table, args.clone(), fields)
+            })
+            .await
+    }
+
+    async fn list_outbox_entries(
+        &self,
+        limit: i32,
+        after: Option<&str>,
+    ) -> Result<Vec<OutboxEntry>, Box<dyn std::error::Error>> {
+        self.retry
+            .run(true, || {
+                dynamo::list_outbox_entries(&self.client, &self.table, limit, after)
+            })
+            .await
+    }
+
+    async fn delete_outbox_entry(&self, entry_id: &str) -> Result<(), Box<dyn std::error::Error>> {
+        self.retry
+            .run(true, || {
+                dynamo::delete_outbox_entry(&self.client, &self.table, entry_id)
+            })
+            .await
+    }
+
+    async fn record_outbox_attempt(
+        &self,
+        entry_id: &str,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        // Not idempotent, but an extra count only dead-letters an entry an attempt early
+        self.retry
+            .run(false, || {
+                dynamo::record_outbox_attempt(&self.client, &self.table, entry_id)
+            })
+            .await
+    }
+
+    async fn dead_letter_outbox_entry(
+        &self,
+        entry: &OutboxEntry,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.retry
+            .run(false, || {
+                dynamo::dead_letter_outbox_entry(&self.client, &self.table, entry)
+            })
+            .await
+    }
+}
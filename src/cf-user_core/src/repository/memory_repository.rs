@@ -0,0 +1,1628 @@
+use super::super::{
+    args::{
+        create_user_args::CreateUserArgs,
+        list_user_history_args::ListUserHistoryArgs,
+        list_users_args::{ListUsersArgs, SortOrder},
+        scan_users_args::ScanUsersArgs,
+        update_user_args::UpdateUserArgs,
+    },
+    config::{self, Config},
+    dynamo,
+    error::{Error, UniqueField},
+    ext::AttributeValuesExt,
+    models::{
+        batch_get_result::BatchGetResult,
+        change_context::ChangeContext,
+        outbox_entry::OutboxEntry,
+        paginated_result::PaginatedResult,
+        user::User,
+        user_history::{HistoryOperation, UserHistory},
+    },
+    timestamp,
+    update_expression::UpdateExpression,
+};
+use super::UserRepository;
+
+use async_trait::async_trait;
+use aws_sdk_dynamodb::types::AttributeValue;
+use chrono::{DateTime, Utc};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+use ulid::Ulid;
+
+type Item = HashMap<String, AttributeValue>;
+
+/// Items read per `scan_users` page, standing in for Scan's 1MB page limit.
+static SCAN_PAGE_SIZE: usize = 100;
+
+/// `UserRepository` that keeps items in process memory. Items are stored exactly as
+/// `dynamo` writes them, keyed and ordered by (PK, SK), so handlers can be exercised
+/// without AWS credentials.
+#[derive(Debug, Default)]
+pub struct InMemoryUserRepository {
+    items: Mutex<BTreeMap<(String, String), Item>>,
+}
+
+impl InMemoryUserRepository {
+    /// Pagination tokens of the repository are signed with a random key, unless one
+    /// is configured, since they cannot outlive the process anyway.
+    pub fn new() -> Self {
+        config::use_process_pagination_token_secret();
+
+        Self::default()
+    }
+
+    /// Inserts a raw item, replacing any existing item with the same PK and SK.
+    pub fn put_item(&self, item: Item) {
+        let key = (item.get_s("PK"), item.get_s("SK"));
+        self.items.lock().unwrap().insert(key, item);
+    }
+
+    /// Returns the raw item stored under the given PK and SK.
+    pub fn get_item(&self, pk: &str, sk: &str) -> Option<Item> {
+        self.items
+            .lock()
+            .unwrap()
+            .get(&(pk.to_string(), sk.to_string()))
+            .cloned()
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Applies `update_user` as `dynamo` does: from `current_item`, the user as it was
+    /// read before the write, conditioned on the item still being at that version.
+    fn update_user_from(
+        &self,
+        user_id: &str,
+        input: UpdateUserArgs,
+        current_item: Option<Item>,
+        expected_version: Option<u64>,
+        context: &ChangeContext,
+    ) -> Result<bool, Box<dyn std::error::Error>> {
+        let now = timestamp::now();
+        let current_date = timestamp::format(&now);
+        let key = String::from("USER#") + user_id;
+
+        let current_item = match current_item {
+            Some(item) if !item.contains_key("DeletedAt") => item,
+            _ => {
+                return Err(Box::new(dynamo::condition_error(
+                    expected_version,
+                    "User does not exist or is deleted",
+                )))
+            }
+        };
+
+        let mut expression = dynamo::update_user_expression(&current_date, input.clone());
+        if let Some(created_date) = timestamp::parse(&current_item.get_s("CreatedDate")) {
+            expression = dynamo::with_list_index_keys(expression, user_id, &created_date);
+        }
+        let read_version = current_item.get_n("Version").unwrap_or_default() as u64;
+        let expression = dynamo::version_condition(
+            expression.condition_attribute_not_exists("DeletedAt"),
+            expected_version.unwrap_or(read_version),
+        );
+
+        let mut items = self.items.lock().unwrap();
+
+        if !expression.matches(items.get(&(key.clone(), key.clone()))) {
+            return Err(dynamo::stale_read_error(
+                Box::new(Error::PreconditionError("Version mismatch")),
+                expected_version,
+            ));
+        }
+
+        let mut guard_puts: Vec<String> = Vec::new();
+        let mut guard_deletes: Vec<String> = Vec::new();
+
+        for (field, attribute, new_value) in [
+            (UniqueField::Email, "Email", input.email.as_str()),
+            (UniqueField::Username, "Username", input.username.as_str()),
+        ] {
+            let current_guard = dynamo::guard_key(field, &current_item.get_s(attribute));
+            let new_guard = dynamo::guard_key(field, new_value);
+            if current_guard == new_guard {
+                continue;
+            }
+
+            if items.contains_key(&(new_guard.clone(), new_guard.clone())) {
+                return Err(Box::new(Error::ConflictError(field)));
+            }
+            guard_puts.push(new_guard);
+            guard_deletes.push(current_guard);
+        }
+
+        for guard in guard_deletes {
+            items.remove(&(guard.clone(), guard));
+        }
+        for guard in guard_puts {
+            let item = dynamo::create_guard_item(&guard, user_id);
+            items.insert((guard.clone(), guard), item);
+        }
+
+        // The condition above holds only for an existing item
+        let item = items
+            .get_mut(&(key.clone(), key))
+            .expect("Missing user item");
+
+        expression.apply(item);
+
+        let history_item = dynamo::create_history_item(
+            user_id,
+            &context.principal_id,
+            HistoryOperation::Update,
+            Some(&current_item),
+            item,
+            &current_date,
+        );
+        let outbox_item = dynamo::outbox_item(context, Some(&current_item), Some(item), &now)?;
+
+        for item in std::iter::once(history_item).chain(outbox_item) {
+            items.insert((item.get_s("PK"), item.get_s("SK")), item);
+        }
+
+        Ok(true)
+    }
+
+    /// Applies `delete_user` as `dynamo` does: from `current_item`, the user as it was
+    /// read before the write, conditioned on the item still being at that version.
+    fn delete_user_from(
+        &self,
+        user_id: &str,
+        current_item: Option<Item>,
+        expected_version: Option<u64>,
+        context: &ChangeContext,
+    ) -> Result<bool, Box<dyn std::error::Error>> {
+        let config = Config::from_env();
+        let now = timestamp::now();
+        let expires_at = now.timestamp() + config.deleted_user_retention_days * 24 * 60 * 60;
+
+        let current_item = match current_item {
+            Some(item) if !item.contains_key("DeletedAt") => item,
+            _ => {
+                return Err(Box::new(dynamo::condition_error(
+                    expected_version,
+                    "User does not exist or is already deleted",
+                )))
+            }
+        };
+
+        let read_version = current_item.get_n("Version").unwrap_or_default() as u64;
+        let expression = dynamo::version_condition(
+            dynamo::soft_delete_user_expression(&timestamp::format(&now), expires_at),
+            expected_version.unwrap_or(read_version),
+        );
+
+        apply_deletion_state(
+            &mut self.items.lock().unwrap(),
+            user_id,
+            &current_item,
+            expression,
+            Some(expires_at),
+            context,
+            HistoryOperation::Delete,
+            &now,
+            Error::PreconditionError("Version mismatch"),
+        )
+        .map_err(|err| dynamo::stale_read_error(err, expected_version))?;
+
+        Ok(true)
+    }
+}
+
+/// Applies a soft delete or restore to the user item, sets the TTL of its guards and
+/// records the change from `current_item`, the user as it was read before the write,
+/// as the `dynamo` transaction does. Fails with `error` when the condition on the user
+/// or one of its guards does not hold.
+#[allow(clippy::too_many_arguments)]
+fn apply_deletion_state(
+    items: &mut BTreeMap<(String, String), Item>,
+    user_id: &str,
+    current_item: &Item,
+    expression: UpdateExpression,
+    expires_at: Option<i64>,
+    context: &ChangeContext,
+    operation: HistoryOperation,
+    now: &DateTime<Utc>,
+    error: Error,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let key = (
+        String::from("USER#") + user_id,
+        String::from("USER#") + user_id,
+    );
+    let guards = [
+        dynamo::email_guard_key(&current_item.get_s("Email")),
+        dynamo::username_guard_key(&current_item.get_s("Username")),
+    ];
+    let guard_expression = dynamo::guard_expiry_expression(user_id, expires_at);
+
+    if !expression.matches(items.get(&key))
+        || guards
+            .iter()
+            .any(|guard| !guard_expression.matches(items.get(&(guard.clone(), guard.clone()))))
+    {
+        return Err(Box::new(error));
+    }
+
+    let history_item = dynamo::updated_history_item(
+        user_id,
+        &context.principal_id,
+        operation,
+        Some(current_item),
+        &expression,
+        &timestamp::format(now),
+    );
+    let mut updated_item = current_item.clone();
+    expression.apply(&mut updated_item);
+    let outbox_item = dynamo::outbox_item(context, Some(current_item), Some(&updated_item), now)?;
+
+    expression.apply(items.get_mut(&key).expect("Missing user item"));
+    for guard in guards {
+        let guard_item = items
+            .entry((guard.clone(), guard.clone()))
+            .or_insert_with(|| {
+                HashMap::from([
+                    ("PK".to_string(), AttributeValue::S(guard.clone())),
+                    ("SK".to_string(), AttributeValue::S(guard.clone())),
+                ])
+            });
+        guard_expression.apply(guard_item);
+    }
+
+    for item in std::iter::once(history_item).chain(outbox_item) {
+        items.insert((item.get_s("PK"), item.get_s("SK")), item);
+    }
+
+    Ok(())
+}
+
+#[async_trait]
+impl UserRepository for InMemoryUserRepository {
+    async fn get_user_by_id(
+        &self,
+        user_id: &str,
+        include_deleted: bool,
+    ) -> Result<Option<User>, Box<dyn std::error::Error>> {
+        let key = String::from("USER#") + user_id;
+
+        match self.get_item(&key, &key) {
+            Some(item) => {
+                let user = User::try_from(item)?;
+                Ok(Some(user).filter(|user| include_deleted || !user.is_deleted()))
+            }
+            None => Ok(None),
+        }
+    }
+
+    async fn get_user_by_email(
+        &self,
+        email: &str,
+        include_deleted: bool,
+    ) -> Result<Option<Vec<User>>, Box<dyn std::error::Error>> {
+        let gsi1pk = dynamo::email_key(email);
+
+        let mut items: Vec<Item> = self
+            .items
+            .lock()
+            .unwrap()
+            .values()
+            .filter(|item| {
+                item.get_opt_s("GSI1PK").as_deref() == Some(gsi1pk.as_str())
+                    && item.get_s("GSI1SK").starts_with("USERNAME#")
+            })
+            .cloned()
+            .collect();
+
+        if items.is_empty() {
+            return Ok(None);
+        }
+
+        items.sort_by_key(|item| item.get_s("GSI1SK"));
+
+        let mut users: Vec<User> = Vec::new();
+        for item in items {
+            let user = User::try_from(item)?;
+            if include_deleted || !user.is_deleted() {
+                users.push(user);
+            }
+        }
+
+        if users.is_empty() {
+            return Ok(None);
+        }
+
+        Ok(Some(users))
+    }
+
+    async fn get_users_by_ids(
+        &self,
+        user_ids: &[String],
+        include_deleted: bool,
+    ) -> Result<BatchGetResult<User>, Box<dyn std::error::Error>> {
+        let mut found: HashMap<String, User> = HashMap::new();
+
+        for user_id in user_ids {
+            if let Some(user) = self.get_user_by_id(user_id, include_deleted).await? {
+                found.insert(user_id.clone(), user);
+            }
+        }
+
+        Ok(dynamo::order_batch_result(user_ids, found))
+    }
+
+    async fn create_user(
+        &self,
+        input: CreateUserArgs,
+        context: &ChangeContext,
+    ) -> Result<String, Box<dyn std::error::Error>> {
+        let user_id = Ulid::new().to_string();
+        let now = timestamp::now();
+        let current_date = timestamp::format(&now);
+
+        let email_guard = dynamo::email_guard_key(&input.email);
+        let username_guard = dynamo::username_guard_key(&input.username);
+
+        let mut items = self.items.lock().unwrap();
+
+        for (guard, field) in [
+            (&email_guard, UniqueField::Email),
+            (&username_guard, UniqueField::Username),
+        ] {
+            if items.contains_key(&(guard.clone(), guard.clone())) {
+                return Err(Box::new(Error::ConflictError(field)));
+            }
+        }
+
+        let user_item = dynamo::create_user_item(&user_id, &now, input);
+        let history_item = dynamo::create_history_item(
+            &user_id,
+            &context.principal_id,
+            HistoryOperation::Create,
+            None,
+            &user_item,
+            &current_date,
+        );
+        let outbox_item = dynamo::outbox_item(context, None, Some(&user_item), &now)?;
+
+        for item in [
+            user_item,
+            history_item,
+            dynamo::create_guard_item(&email_guard, &user_id),
+            dynamo::create_guard_item(&username_guard, &user_id),
+        ]
+        .into_iter()
+        .chain(outbox_item)
+        {
+            items.insert((item.get_s("PK"), item.get_s("SK")), item);
+        }
+
+        Ok(user_id)
+    }
+
+    async fn get_unique_value_owner(
+        &self,
+        field: UniqueField,
+        value: &str,
+    ) -> Result<Option<String>, Box<dyn std::error::Error>> {
+        let guard = dynamo::guard_key(field, value);
+
+        Ok(self
+            .get_item(&guard, &guard)
+            .and_then(|item| item.get_opt_s("UserId")))
+    }
+
+    async fn update_user(
+        &self,
+        user_id: &str,
+        input: UpdateUserArgs,
+        expected_version: Option<u64>,
+        context: &ChangeContext,
+    ) -> Result<bool, Box<dyn std::error::Error>> {
+        let key = String::from("USER#") + user_id;
+        let current_item = self.get_item(&key, &key);
+
+        self.update_user_from(user_id, input, current_item, expected_version, context)
+    }
+
+    async fn delete_user(
+        &self,
+        user_id: &str,
+        expected_version: Option<u64>,
+        context: &ChangeContext,
+    ) -> Result<bool, Box<dyn std::error::Error>> {
+        let key = String::from("USER#") + user_id;
+        let current_item = self.get_item(&key, &key);
+
+        self.delete_user_from(user_id, current_item, expected_version, context)
+    }
+
+    async fn restore_user(
+        &self,
+        user_id: &str,
+        expected_version: Option<u64>,
+        context: &ChangeContext,
+    ) -> Result<bool, Box<dyn std::error::Error>> {
+        let now = timestamp::now();
+        let key = String::from("USER#") + user_id;
+
+        let mut expression =
+            dynamo::restore_user_expression(&timestamp::format(&now), now.timestamp());
+        if let Some(version) = expected_version {
+            expression = dynamo::version_condition(expression, version);
+        }
+        let error = dynamo::condition_error(
+            expected_version,
+            "User is not deleted or can no longer be restored",
+        );
+
+        let mut items = self.items.lock().unwrap();
+        let Some(current_item) = items.get(&(key.clone(), key)).cloned() else {
+            return Err(Box::new(error));
+        };
+
+        apply_deletion_state(
+            &mut items,
+            user_id,
+            &current_item,
+            expression,
+            None,
+            context,
+            HistoryOperation::Restore,
+            &now,
+            error,
+        )?;
+
+        Ok(true)
+    }
+
+    async fn list_user_history(
+        &self,
+        user_id: &str,
+        args: ListUserHistoryArgs,
+    ) -> Result<PaginatedResult<UserHistory>, Box<dyn std::error::Error>> {
+        let pk = String::from("USER#") + user_id;
+        let start_key: Option<String> =
+            dynamo::decode_start_key(args.pagination_token.as_deref())?.map(|key| key.get_s("SK"));
+
+        let limit = usize::try_from(args.limit).unwrap_or_default();
+
+        let mut entries: Vec<(String, Item)> = self
+            .items
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|((item_pk, sk), _)| *item_pk == pk && sk.starts_with("HISTORY#"))
+            .map(|((_, sk), item)| (sk.clone(), item.clone()))
+            .collect();
+
+        if args.order == SortOrder::Descending {
+            entries.reverse();
+        }
+
+        let items: Vec<Item> = entries
+            .into_iter()
+            .filter(|(sk, _)| match (&start_key, args.order) {
+                (Some(start_key), SortOrder::Ascending) => sk > start_key,
+                (Some(start_key), SortOrder::Descending) => sk < start_key,
+                (None, _) => true,
+            })
+            .take(limit)
+            .map(|(_, item)| item)
+            .collect();
+
+        let last_key = match items.last() {
+            Some(item) if items.len() == limit => Some(
+                ["PK", "SK"]
+                    .iter()
+                    .filter_map(|name| Some((name.to_string(), item.get(*name)?.clone())))
+                    .collect(),
+            ),
+            _ => None,
+        };
+
+        let mut history: Vec<UserHistory> = Vec::new();
+        for item in items {
+            history.push(UserHistory::try_from(item)?);
+        }
+
+        Ok(PaginatedResult {
+            data: history,
+            token: dynamo::encode_last_key(last_key)?,
+        })
+    }
+
+    async fn scan_users(
+        &self,
+        args: ScanUsersArgs,
+    ) -> Result<PaginatedResult<User>, Box<dyn std::error::Error>> {
+        let start_key: Option<String> =
+            dynamo::decode_start_key(args.pagination_token.as_deref())?.map(|key| key.get_s("PK"));
+        let total_segments = usize::try_from(args.total_segments).unwrap_or(1).max(1);
+        let segment = usize::try_from(args.segment).unwrap_or_default();
+
+        // Segments split the user items round robin, then each is read in key order
+        let page: Vec<Item> = self
+            .items
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|((pk, sk), _)| pk.starts_with("USER#") && sk.starts_with("USER#"))
+            .enumerate()
+            .filter(|(index, _)| index % total_segments == segment)
+            .map(|(_, ((pk, _), item))| (pk, item))
+            .filter(|(pk, _)| start_key.as_ref().is_none_or(|start_key| *pk > start_key))
+            .take(SCAN_PAGE_SIZE)
+            .map(|(_, item)| item.clone())
+            .collect();
+
+        let last_key = match page.last() {
+            Some(item) if page.len() == SCAN_PAGE_SIZE => Some(
+                ["PK", "SK"]
+                    .iter()
+                    .filter_map(|name| Some((name.to_string(), item.get(*name)?.clone())))
+                    .collect(),
+            ),
+            _ => None,
+        };
+
+        let mut users: Vec<User> = Vec::new();
+        for item in page {
+            let created_date = item.get_s("CreatedDate");
+            if args
+                .created_from
+                .as_ref()
+                .is_some_and(|created_from| created_date < *created_from)
+                || args
+                    .created_to
+                    .as_ref()
+                    .is_some_and(|created_to| created_date >= *created_to)
+                || (!args.include_deleted && item.contains_key("DeletedAt"))
+            {
+                continue;
+            }
+
+            users.push(User::try_from(item)?);
+        }
+
+        Ok(PaginatedResult {
+            data: users,
+            token: dynamo::encode_last_key(last_key)?,
+        })
+    }
+
+    async fn list_users(
+        &self,
+        args: ListUsersArgs,
+    ) -> Result<PaginatedResult<User>, Box<dyn std::error::Error>> {
+        // A prefix search reads the username index rather than the listing index
+        let (index_pk, index_sk, partition, prefix) = match args.username_prefix.as_deref() {
+            Some(prefix) => (
+                "GSI3PK",
+                "GSI3SK",
+                dynamo::username_index_partition(prefix),
+                dynamo::username_index_sort_key(prefix),
+            ),
+            None => ("GSI2PK", "GSI2SK", String::from("USERS"), String::new()),
+        };
+
+        // Index entries are ordered by their index sort key, then by the table key
+        let start_key: Option<(String, String)> = dynamo::index_start_key(
+            dynamo::decode_start_key(args.pagination_token.as_deref())?,
+            index_pk,
+            &partition,
+        )?
+        .map(|key| (key.get_s(index_sk), key.get_s("PK")));
+
+        let limit = usize::try_from(args.limit).unwrap_or_default();
+
+        let mut entries: Vec<((String, String), Item)> = self
+            .items
+            .lock()
+            .unwrap()
+            .values()
+            .filter(|item| item.get_opt_s(index_pk).as_deref() == Some(partition.as_str()))
+            .filter(|item| {
+                item.get_opt_s(index_sk)
+                    .is_some_and(|sort_key| sort_key.starts_with(&prefix))
+            })
+            .filter(|item| args.include_deleted || !item.contains_key("DeletedAt"))
+            .map(|item| ((item.get_s(index_sk), item.get_s("PK")), item.clone()))
+            .collect();
+
+        entries.sort_by(|(a, _), (b, _)| match args.order {
+            SortOrder::Ascending => a.cmp(b),
+            SortOrder::Descending => b.cmp(a),
+        });
+
+        let items: Vec<Item> = entries
+            .into_iter()
+            .filter(|(key, _)| match (&start_key, args.order) {
+                (Some(start_key), SortOrder::Ascending) => key > start_key,
+                (Some(start_key), SortOrder::Descending) => key < start_key,
+                (None, _) => true,
+            })
+            .take(limit)
+            .map(|(_, item)| item)
+            .collect();
+
+        // Like Query with a Limit, a full page always reports its last key, even when
+        // nothing remains after it
+        let last_key = match items.last() {
+            Some(item) if items.len() == limit => Some(
+                ["PK", "SK", index_pk, index_sk]
+                    .iter()
+                    .filter_map(|name| Some((name.to_string(), item.get(*name)?.clone())))
+                    .collect(),
+            ),
+            _ => None,
+        };
+
+        let mut users: Vec<User> = Vec::new();
+        for item in items {
+            users.push(User::try_from(item)?);
+        }
+
+        Ok(PaginatedResult {
+            data: users,
+            token: dynamo::encode_last_key(last_key)?,
+        })
+    }
+
+    async fn list_outbox_entries(
+        &self,
+        limit: i32,
+        after: Option<&str>,
+    ) -> Result<Vec<OutboxEntry>, Box<dyn std::error::Error>> {
+        let limit = usize::try_from(limit).unwrap_or_default();
+        let after = after.map(|after| String::from("OUTBOX#") + after);
+
+        let items: Vec<Item> = self
+            .items
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|((pk, sk), _)| pk == "OUTBOX" && after.as_ref().is_none_or(|after| sk > after))
+            .take(limit)
+            .map(|(_, item)| item.clone())
+            .collect();
+
+        let mut entries: Vec<OutboxEntry> = Vec::new();
+        for item in items {
+            entries.push(OutboxEntry::try_from(item)?);
+        }
+
+        Ok(entries)
+    }
+
+    async fn delete_outbox_entry(&self, entry_id: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let key = ("OUTBOX".to_string(), String::from("OUTBOX#") + entry_id);
+        self.items.lock().unwrap().remove(&key);
+
+        Ok(())
+    }
+
+    async fn record_outbox_attempt(
+        &self,
+        entry_id: &str,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let key = ("OUTBOX".to_string(), String::from("OUTBOX#") + entry_id);
+        if let Some(item) = self.items.lock().unwrap().get_mut(&key) {
+            UpdateExpression::new()
+                .add("Attempts", AttributeValue::N("1".to_string()))
+                .apply(item);
+        }
+
+        Ok(())
+    }
+
+    async fn dead_letter_outbox_entry(
+        &self,
+        entry: &OutboxEntry,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let sort_key = String::from("OUTBOX#") + &entry.entry_id;
+        let mut item = Item::from(entry);
+        item.insert(
+            "PK".to_string(),
+            AttributeValue::S("OUTBOX_DEAD_LETTER".to_string()),
+        );
+
+        let mut items = self.items.lock().unwrap();
+        items.remove(&("OUTBOX".to_string(), sort_key.clone()));
+        items.insert(("OUTBOX_DEAD_LETTER".to_string(), sort_key), item);
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod unit_tests {
+    use super::*;
+    use crate::error::RepositoryError;
+
+    fn create_args(username: &str, email: &str) -> CreateUserArgs {
+        CreateUserArgs {
+            username: username.to_string(),
+            first_name: Some("Test".to_string()),
+            last_name: None,
+            email: email.to_string(),
+            profile_photo: None,
+            summary: None,
+            phone_number: None,
+        }
+    }
+
+    fn update_args(username: &str, email: &str) -> UpdateUserArgs {
+        UpdateUserArgs {
+            username: username.to_string(),
+            first_name: None,
+            last_name: Some("User".to_string()),
+            email: email.to_string(),
+            profile_photo: None,
+            summary: None,
+            phone_number: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn should_store_user_using_dynamo_key_layout() {
+        let repository = InMemoryUserRepository::new();
+
+        let user_id = repository
+            .create_user(
+                create_args("tester", "tester@example.com"),
+                &ChangeContext::new("tester"),
+            )
+            .await
+            .expect("Failed to create user");
+
+        let key = format!("USER#{}", user_id);
+        let item = repository.get_item(&key, &key).expect("Missing user item");
+
+        assert_eq!(item.get_s("GSI1PK"), "EMAIL#tester@example.com");
+        assert_eq!(item.get_s("GSI1SK"), "USERNAME#tester");
+        assert_eq!(item.get_s("GSI2PK"), "USERS");
+        assert!(item.get_s("GSI2SK").ends_with(&format!("#{}", user_id)));
+        assert!(!item.contains_key("LastName"));
+
+        let email_guard = repository
+            .get_item("EMAIL#tester@example.com", "EMAIL#tester@example.com")
+            .expect("Missing email guard");
+        assert_eq!(email_guard.get_s("UserId"), user_id);
+
+        let username_guard = repository
+            .get_item("USERNAME#tester", "USERNAME#tester")
+            .expect("Missing username guard");
+        assert_eq!(username_guard.get_s("UserId"), user_id);
+    }
+
+    #[tokio::test]
+    async fn should_reject_duplicate_email_and_username() {
+        let repository = InMemoryUserRepository::new();
+
+        repository
+            .create_user(
+                create_args("tester", "tester@example.com"),
+                &ChangeContext::new("tester"),
+            )
+            .await
+            .expect("Failed to create user");
+
+        let err = repository
+            .create_user(
+                create_args("other", "tester@example.com"),
+                &ChangeContext::new("tester"),
+            )
+            .await
+            .expect_err("Duplicate email was accepted");
+        assert!(matches!(
+            err.downcast_ref::<Error>(),
+            Some(Error::ConflictError(UniqueField::Email))
+        ));
+
+        let err = repository
+            .create_user(
+                create_args("tester", "other@example.com"),
+                &ChangeContext::new("tester"),
+            )
+            .await
+            .expect_err("Duplicate username was accepted");
+        assert!(matches!(
+            err.downcast_ref::<Error>(),
+            Some(Error::ConflictError(UniqueField::Username))
+        ));
+
+        assert_eq!(repository.len(), 5);
+    }
+
+    #[tokio::test]
+    async fn should_swap_guards_when_email_and_username_change() {
+        let repository = InMemoryUserRepository::new();
+
+        let user_id = repository
+            .create_user(
+                create_args("tester", "tester@example.com"),
+                &ChangeContext::new("tester"),
+            )
+            .await
+            .expect("Failed to create user");
+        repository
+            .create_user(
+                create_args("taken", "taken@example.com"),
+                &ChangeContext::new("tester"),
+            )
+            .await
+            .expect("Failed to create user");
+
+        let err = repository
+            .update_user(
+                &user_id,
+                update_args("tester", "taken@example.com"),
+                None,
+                &ChangeContext::new("tester"),
+            )
+            .await
+            .expect_err("Update to a taken email was accepted");
+        assert!(matches!(
+            err.downcast_ref::<Error>(),
+            Some(Error::ConflictError(UniqueField::Email))
+        ));
+
+        repository
+            .update_user(
+                &user_id,
+                update_args("renamed", "renamed@example.com"),
+                None,
+                &ChangeContext::new("tester"),
+            )
+            .await
+            .expect("Failed to update user");
+
+        assert!(repository
+            .get_item("EMAIL#tester@example.com", "EMAIL#tester@example.com")
+            .is_none());
+        assert!(repository
+            .get_item("USERNAME#tester", "USERNAME#tester")
+            .is_none());
+        assert!(repository
+            .get_item("EMAIL#renamed@example.com", "EMAIL#renamed@example.com")
+            .is_some());
+        assert!(repository
+            .get_item("USERNAME#renamed", "USERNAME#renamed")
+            .is_some());
+
+        repository
+            .create_user(
+                create_args("tester", "tester@example.com"),
+                &ChangeContext::new("tester"),
+            )
+            .await
+            .expect("Released email and username could not be reused");
+    }
+
+    #[tokio::test]
+    async fn should_reject_concurrent_update_from_a_stale_read() {
+        let repository = InMemoryUserRepository::new();
+
+        let user_id = repository
+            .create_user(
+                create_args("tester", "tester@example.com"),
+                &ChangeContext::new("tester"),
+            )
+            .await
+            .expect("Failed to create user");
+
+        // Both updates read the user before either one writes
+        let key = format!("USER#{}", user_id);
+        let read = repository.get_item(&key, &key);
+
+        repository
+            .update_user_from(
+                &user_id,
+                update_args("first", "first@example.com"),
+                read.clone(),
+                None,
+                &ChangeContext::new("tester"),
+            )
+            .expect("Failed to update user");
+
+        let err = repository
+            .update_user_from(
+                &user_id,
+                update_args("second", "second@example.com"),
+                read,
+                None,
+                &ChangeContext::new("tester"),
+            )
+            .expect_err("Update from a stale read should fail");
+
+        assert!(matches!(
+            err.downcast_ref::<RepositoryError>(),
+            Some(RepositoryError::TransactionConflict(_))
+        ));
+        assert!(repository
+            .get_item("EMAIL#first@example.com", "EMAIL#first@example.com")
+            .is_some());
+        assert!(repository
+            .get_item("USERNAME#first", "USERNAME#first")
+            .is_some());
+        for guard in [
+            "EMAIL#tester@example.com",
+            "USERNAME#tester",
+            "EMAIL#second@example.com",
+            "USERNAME#second",
+        ] {
+            assert!(repository.get_item(guard, guard).is_none());
+        }
+    }
+
+    #[tokio::test]
+    async fn should_not_update_a_missing_or_deleted_user() {
+        let repository = InMemoryUserRepository::new();
+
+        let err = repository
+            .update_user(
+                "01H2PFJW211F4A7D98DS551H2M",
+                update_args("tester", "tester@example.com"),
+                None,
+                &ChangeContext::new("tester"),
+            )
+            .await
+            .expect_err("Update of a missing user should fail");
+
+        assert!(matches!(
+            err.downcast_ref::<Error>(),
+            Some(Error::ClientError(_))
+        ));
+        assert!(repository.is_empty());
+
+        let user_id = repository
+            .create_user(
+                create_args("tester", "tester@example.com"),
+                &ChangeContext::new("tester"),
+            )
+            .await
+            .expect("Failed to create user");
+
+        // The user is deleted after the update read it
+        let key = format!("USER#{}", user_id);
+        let read = repository.get_item(&key, &key);
+        repository
+            .delete_user(&user_id, None, &ChangeContext::new("tester"))
+            .await
+            .expect("Failed to delete user");
+        let deleted = repository.get_item(&key, &key);
+        let len = repository.len();
+
+        assert!(repository
+            .update_user_from(
+                &user_id,
+                update_args("renamed", "renamed@example.com"),
+                read,
+                None,
+                &ChangeContext::new("tester"),
+            )
+            .is_err());
+        assert_eq!(repository.get_item(&key, &key), deleted);
+        assert_eq!(repository.len(), len);
+    }
+
+    #[tokio::test]
+    async fn should_get_user_by_id_and_email() {
+        let repository = InMemoryUserRepository::new();
+
+        let user_id = repository
+            .create_user(
+                create_args("tester", "tester@example.com"),
+                &ChangeContext::new("tester"),
+            )
+            .await
+            .expect("Failed to create user");
+
+        let user = repository
+            .get_user_by_id(&user_id, false)
+            .await
+            .expect("Failed to get user")
+            .expect("User not found");
+        assert_eq!(user.email, "tester@example.com");
+
+        let users = repository
+            .get_user_by_email("tester@example.com", false)
+            .await
+            .expect("Failed to get user by email")
+            .expect("User not found");
+        assert_eq!(users.len(), 1);
+        assert_eq!(users[0].user_id, user_id);
+
+        let missing = repository
+            .get_user_by_email("missing@example.com", false)
+            .await
+            .expect("Failed to get user by email");
+        assert!(missing.is_none());
+    }
+
+    #[tokio::test]
+    async fn should_match_emails_regardless_of_case() {
+        let repository = InMemoryUserRepository::new();
+
+        let user_id = repository
+            .create_user(
+                create_args("tester", " Tester@Example.com"),
+                &ChangeContext::new("tester"),
+            )
+            .await
+            .expect("Failed to create user");
+
+        let err = repository
+            .create_user(
+                create_args("other", "tester@EXAMPLE.com"),
+                &ChangeContext::new("tester"),
+            )
+            .await
+            .expect_err("Duplicate email was accepted");
+        assert!(matches!(
+            err.downcast_ref::<Error>(),
+            Some(Error::ConflictError(UniqueField::Email))
+        ));
+
+        let users = repository
+            .get_user_by_email("TESTER@example.com", false)
+            .await
+            .expect("Failed to get user by email")
+            .expect("User not found");
+        assert_eq!(users[0].user_id, user_id);
+        assert_eq!(users[0].email, " Tester@Example.com");
+
+        // Changing only how the email is written keeps the same guard
+        repository
+            .update_user(
+                &user_id,
+                update_args("tester", "tester@example.com"),
+                None,
+                &ChangeContext::new("tester"),
+            )
+            .await
+            .expect("Failed to update user");
+        assert_eq!(
+            repository
+                .get_unique_value_owner(UniqueField::Email, "Tester@Example.com")
+                .await
+                .expect("Failed to get email owner"),
+            Some(user_id)
+        );
+    }
+
+    #[tokio::test]
+    async fn should_update_user_and_remove_omitted_fields() {
+        let repository = InMemoryUserRepository::new();
+
+        let user_id = repository
+            .create_user(
+                create_args("tester", "tester@example.com"),
+                &ChangeContext::new("tester"),
+            )
+            .await
+            .expect("Failed to create user");
+
+        repository
+            .update_user(
+                &user_id,
+                update_args("renamed", "renamed@example.com"),
+                None,
+                &ChangeContext::new("tester"),
+            )
+            .await
+            .expect("Failed to update user");
+
+        let key = format!("USER#{}", user_id);
+        let item = repository.get_item(&key, &key).expect("Missing user item");
+
+        assert_eq!(item.get_s("Username"), "renamed");
+        assert_eq!(item.get_s("LastName"), "User");
+        assert_eq!(item.get_s("GSI1PK"), "EMAIL#renamed@example.com");
+        assert!(!item.contains_key("FirstName"));
+    }
+
+    #[tokio::test]
+    async fn should_delete_user() {
+        let repository = InMemoryUserRepository::new();
+
+        let user_id = repository
+            .create_user(
+                create_args("tester", "tester@example.com"),
+                &ChangeContext::new("tester"),
+            )
+            .await
+            .expect("Failed to create user");
+
+        repository
+            .delete_user(&user_id, None, &ChangeContext::new("tester"))
+            .await
+            .expect("Failed to delete user");
+
+        assert!(repository
+            .get_user_by_id(&user_id, false)
+            .await
+            .expect("Failed to get user")
+            .is_none());
+        assert!(repository
+            .get_user_by_email("tester@example.com", false)
+            .await
+            .expect("Failed to get user by email")
+            .is_none());
+
+        let user = repository
+            .get_user_by_id(&user_id, true)
+            .await
+            .expect("Failed to get user")
+            .expect("Deleted user was purged");
+        assert!(user.is_deleted());
+        assert!(user.deleted_at.is_some());
+
+        // Guards stay reserved until the TTL purges them with the user
+        let email_guard = repository
+            .get_item("EMAIL#tester@example.com", "EMAIL#tester@example.com")
+            .expect("Missing email guard");
+        assert_eq!(
+            email_guard.get_n("ExpiresAt"),
+            user.expires_at.map(|t| t as f64)
+        );
+
+        let err = repository
+            .delete_user(&user_id, None, &ChangeContext::new("tester"))
+            .await
+            .expect_err("Deleted user was deleted again");
+        assert!(matches!(
+            err.downcast_ref::<Error>(),
+            Some(Error::ClientError(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn should_reject_delete_from_a_stale_read() {
+        let repository = InMemoryUserRepository::new();
+
+        let user_id = repository
+            .create_user(
+                create_args("tester", "tester@example.com"),
+                &ChangeContext::new("tester"),
+            )
+            .await
+            .expect("Failed to create user");
+
+        // The email changes after the delete read the user
+        let key = format!("USER#{}", user_id);
+        let read = repository.get_item(&key, &key);
+        repository
+            .update_user(
+                &user_id,
+                update_args("tester", "renamed@example.com"),
+                None,
+                &ChangeContext::new("tester"),
+            )
+            .await
+            .expect("Failed to update user");
+
+        let err = repository
+            .delete_user_from(&user_id, read, None, &ChangeContext::new("tester"))
+            .expect_err("Delete from a stale read should fail");
+
+        assert!(matches!(
+            err.downcast_ref::<RepositoryError>(),
+            Some(RepositoryError::TransactionConflict(_))
+        ));
+        assert!(repository
+            .get_item("EMAIL#tester@example.com", "EMAIL#tester@example.com")
+            .is_none());
+        let email_guard = repository
+            .get_item("EMAIL#renamed@example.com", "EMAIL#renamed@example.com")
+            .expect("Missing email guard");
+        assert!(!email_guard.contains_key("ExpiresAt"));
+
+        repository
+            .delete_user(&user_id, None, &ChangeContext::new("tester"))
+            .await
+            .expect("Failed to delete user");
+
+        let email_guard = repository
+            .get_item("EMAIL#renamed@example.com", "EMAIL#renamed@example.com")
+            .expect("Missing email guard");
+        assert!(email_guard.contains_key("ExpiresAt"));
+        assert!(repository
+            .get_item("EMAIL#tester@example.com", "EMAIL#tester@example.com")
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn should_restore_deleted_user() {
+        let repository = InMemoryUserRepository::new();
+
+        let user_id = repository
+            .create_user(
+                create_args("tester", "tester@example.com"),
+                &ChangeContext::new("tester"),
+            )
+            .await
+            .expect("Failed to create user");
+
+        repository
+            .restore_user(&user_id, None, &ChangeContext::new("tester"))
+            .await
+            .expect_err("Active user was restored");
+
+        repository
+            .delete_user(&user_id, Some(1), &ChangeContext::new("tester"))
+            .await
+            .expect("Failed to delete user");
+        repository
+            .restore_user(&user_id, Some(2), &ChangeContext::new("tester"))
+            .await
+            .expect("Failed to restore user");
+
+        let user = repository
+            .get_user_by_id(&user_id, false)
+            .await
+            .expect("Failed to get user")
+            .expect("Restored user not found");
+        assert!(!user.is_deleted());
+        assert_eq!(user.deleted_at, None);
+        assert_eq!(user.expires_at, None);
+        assert_eq!(user.version, 3);
+
+        let email_guard = repository
+            .get_item("EMAIL#tester@example.com", "EMAIL#tester@example.com")
+            .expect("Missing email guard");
+        assert!(!email_guard.contains_key("ExpiresAt"));
+    }
+
+    #[tokio::test]
+    async fn should_not_restore_user_after_retention_period() {
+        let repository = InMemoryUserRepository::new();
+
+        let user_id = repository
+            .create_user(
+                create_args("tester", "tester@example.com"),
+                &ChangeContext::new("tester"),
+            )
+            .await
+            .expect("Failed to create user");
+        repository
+            .delete_user(&user_id, None, &ChangeContext::new("tester"))
+            .await
+            .expect("Failed to delete user");
+
+        // Expired but not yet purged by the TTL
+        let key = format!("USER#{}", user_id);
+        let mut item = repository.get_item(&key, &key).expect("Missing user item");
+        item.insert("ExpiresAt".to_string(), AttributeValue::N("1".to_string()));
+        repository.put_item(item);
+
+        repository
+            .restore_user(&user_id, None, &ChangeContext::new("tester"))
+            .await
+            .expect_err("Expired user was restored");
+    }
+
+    #[tokio::test]
+    async fn should_increment_version_and_reject_stale_writes() {
+        let repository = InMemoryUserRepository::new();
+
+        let user_id = repository
+            .create_user(
+                create_args("tester", "tester@example.com"),
+                &ChangeContext::new("tester"),
+            )
+            .await
+            .expect("Failed to create user");
+
+        repository
+            .update_user(
+                &user_id,
+                update_args("tester", "tester@example.com"),
+                Some(1),
+                &ChangeContext::new("tester"),
+            )
+            .await
+            .expect("Failed to update user at current version");
+
+        let user = repository
+            .get_user_by_id(&user_id, false)
+            .await
+            .expect("Failed to get user")
+            .expect("User not found");
+        assert_eq!(user.version, 2);
+
+        let err = repository
+            .update_user(
+                &user_id,
+                update_args("tester", "tester@example.com"),
+                Some(1),
+                &ChangeContext::new("tester"),
+            )
+            .await
+            .expect_err("Stale update was accepted");
+        assert!(matches!(
+            err.downcast_ref::<Error>(),
+            Some(Error::PreconditionError(_))
+        ));
+
+        let err = repository
+            .delete_user(&user_id, Some(1), &ChangeContext::new("tester"))
+            .await
+            .expect_err("Stale delete was accepted");
+        assert!(matches!(
+            err.downcast_ref::<Error>(),
+            Some(Error::PreconditionError(_))
+        ));
+
+        repository
+            .delete_user(&user_id, Some(2), &ChangeContext::new("tester"))
+            .await
+            .expect("Failed to delete user at current version");
+    }
+
+    #[tokio::test]
+    async fn should_paginate_through_all_users() {
+        let repository = InMemoryUserRepository::new();
+
+        for i in 0..5 {
+            repository
+                .create_user(
+                    create_args(&format!("tester{}", i), &format!("tester{}@example.com", i)),
+                    &ChangeContext::new("tester"),
+                )
+                .await
+                .expect("Failed to create user");
+        }
+
+        for order in [SortOrder::Ascending, SortOrder::Descending] {
+            let mut users: Vec<User> = Vec::new();
+            let mut token: Option<String> = None;
+            let mut pages = 0;
+
+            loop {
+                let page = repository
+                    .list_users(ListUsersArgs {
+                        limit: 2,
+                        pagination_token: token,
+                        order,
+                        ..Default::default()
+                    })
+                    .await
+                    .expect("Failed to list users");
+
+                pages += 1;
+                users.extend(page.data);
+
+                match page.token {
+                    Some(next) => token = Some(next),
+                    None => break,
+                }
+            }
+
+            let keys: Vec<String> = users
+                .iter()
+                .map(|user| dynamo::list_index_sort_key(&user.created_date, &user.user_id))
+                .collect();
+            let mut sorted_keys = keys.clone();
+            sorted_keys.sort();
+            if order == SortOrder::Descending {
+                sorted_keys.reverse();
+            }
+
+            assert_eq!(pages, 3);
+            assert_eq!(users.len(), 5);
+            assert_eq!(keys, sorted_keys);
+        }
+    }
+
+    #[tokio::test]
+    async fn should_search_users_by_username_prefix() {
+        let repository = InMemoryUserRepository::new();
+
+        for username in ["Alice", "alfred", "albert", "bob", "Alan"] {
+            repository
+                .create_user(
+                    create_args(username, &format!("{}@example.com", username)),
+                    &ChangeContext::new("tester"),
+                )
+                .await
+                .expect("Failed to create user");
+        }
+
+        let first_page = repository
+            .list_users(ListUsersArgs {
+                limit: 2,
+                username_prefix: Some("AL".to_string()),
+                ..Default::default()
+            })
+            .await
+            .expect("Failed to search users");
+        let second_page = repository
+            .list_users(ListUsersArgs {
+                limit: 2,
+                pagination_token: first_page.token.clone(),
+                username_prefix: Some("AL".to_string()),
+                ..Default::default()
+            })
+            .await
+            .expect("Failed to search users");
+
+        let usernames: Vec<String> = first_page
+            .data
+            .iter()
+            .chain(second_page.data.iter())
+            .map(|user| user.username.clone())
+            .collect();
+        assert_eq!(usernames, vec!["Alan", "albert", "alfred", "Alice"]);
+
+        let err = repository
+            .list_users(ListUsersArgs {
+                limit: 2,
+                pagination_token: first_page.token,
+                username_prefix: Some("b".to_string()),
+                ..Default::default()
+            })
+            .await
+            .expect_err("Token was accepted by another search");
+        assert!(matches!(
+            err.downcast_ref::<Error>(),
+            Some(Error::InvalidToken(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn should_record_history_for_each_mutation() {
+        let repository = InMemoryUserRepository::new();
+
+        let user_id = repository
+            .create_user(
+                create_args("tester", "tester@example.com"),
+                &ChangeContext::new("creator"),
+            )
+            .await
+            .expect("Failed to create user");
+        repository
+            .update_user(
+                &user_id,
+                update_args("tester", "renamed@example.com"),
+                Some(1),
+                &ChangeContext::new("editor"),
+            )
+            .await
+            .expect("Failed to update user");
+        repository
+            .delete_user(&user_id, None, &ChangeContext::new("admin"))
+            .await
+            .expect("Failed to delete user");
+        repository
+            .restore_user(&user_id, None, &ChangeContext::new("admin"))
+            .await
+            .expect("Failed to restore user");
+
+        let mut token: Option<String> = None;
+        let mut history: Vec<UserHistory> = Vec::new();
+        loop {
+            let page = repository
+                .list_user_history(
+                    &user_id,
+                    ListUserHistoryArgs {
+                        limit: 3,
+                        pagination_token: token,
+                        ..Default::default()
+                    },
+                )
+                .await
+                .expect("Failed to list history");
+            history.extend(page.data);
+
+            match page.token {
+                Some(next) => token = Some(next),
+                None => break,
+            }
+        }
+
+        let operations: Vec<HistoryOperation> = history.iter().map(|h| h.operation).collect();
+        assert_eq!(
+            operations,
+            vec![
+                HistoryOperation::Restore,
+                HistoryOperation::Delete,
+                HistoryOperation::Update,
+                HistoryOperation::Create,
+            ]
+        );
+
+        let update = &history[2];
+        assert_eq!(update.actor, "editor");
+        assert_eq!(
+            update.changes.keys().collect::<Vec<&String>>(),
+            vec!["Email", "FirstName", "LastName"]
+        );
+        assert_eq!(
+            update.changes["Email"].before.as_deref(),
+            Some("tester@example.com")
+        );
+        assert_eq!(
+            update.changes["Email"].after.as_deref(),
+            Some("renamed@example.com")
+        );
+
+        let delete = &history[1];
+        assert_eq!(delete.changes["Status"].after.as_deref(), Some("deleted"));
+        assert_eq!(history[3].changes["Username"].before, None);
+
+        // A failed mutation leaves no history behind
+        repository
+            .update_user(
+                &user_id,
+                update_args("tester", "other@example.com"),
+                Some(1),
+                &ChangeContext::new("editor"),
+            )
+            .await
+            .expect_err("Stale update was accepted");
+        let page = repository
+            .list_user_history(&user_id, ListUserHistoryArgs::default())
+            .await
+            .expect("Failed to list history");
+        assert_eq!(page.data.len(), 4);
+    }
+
+    #[tokio::test]
+    async fn should_get_users_by_ids_in_request_order() {
+        let repository = InMemoryUserRepository::new();
+
+        let mut user_ids: Vec<String> = Vec::new();
+        for i in 0..3 {
+            user_ids.push(
+                repository
+                    .create_user(
+                        create_args(&format!("tester{}", i), &format!("tester{}@example.com", i)),
+                        &ChangeContext::new("tester"),
+                    )
+                    .await
+                    .expect("Failed to create user"),
+            );
+        }
+        repository
+            .delete_user(&user_ids[1], None, &ChangeContext::new("tester"))
+            .await
+            .expect("Failed to delete user");
+
+        let requested = vec![
+            user_ids[2].clone(),
+            "01H5FS6FKMB0YY0VDJ015741BJ".to_string(),
+            user_ids[1].clone(),
+            user_ids[0].clone(),
+            user_ids[2].clone(),
+        ];
+
+        let result = repository
+            .get_users_by_ids(&requested, false)
+            .await
+            .expect("Failed to get users");
+
+        let found: Vec<&str> = result.data.iter().map(|u| u.user_id.as_str()).collect();
+        assert_eq!(found, vec![user_ids[2].as_str(), user_ids[0].as_str()]);
+        assert_eq!(
+            result.missing,
+            vec![
+                "01H5FS6FKMB0YY0VDJ015741BJ".to_string(),
+                user_ids[1].clone()
+            ]
+        );
+
+        let result = repository
+            .get_users_by_ids(&requested, true)
+            .await
+            .expect("Failed to get users");
+        assert_eq!(result.data.len(), 3);
+        assert_eq!(result.missing.len(), 1);
+    }
+}
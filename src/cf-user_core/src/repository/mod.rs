@@ -0,0 +1,170 @@
+pub mod cached_repository;
+pub mod dynamo_repository;
+pub mod memory_repository;
+
+use super::{
+    args::{
+        create_user_args::CreateUserArgs, list_user_history_args::ListUserHistoryArgs,
+        list_users_args::ListUsersArgs, scan_users_args::ScanUsersArgs,
+        update_user_args::UpdateUserArgs,
+    },
+    error::UniqueField,
+    fieldset::Fieldset,
+    models::{
+        batch_get_result::BatchGetResult, change_context::ChangeContext, outbox_entry::OutboxEntry,
+        paginated_result::PaginatedResult, partial_user::PartialUser, user::User,
+        user_history::UserHistory,
+    },
+};
+
+use async_trait::async_trait;
+
+pub use cached_repository::CachedUserRepository;
+pub use dynamo_repository::DynamoUserRepository;
+pub use memory_repository::InMemoryUserRepository;
+
+/// Storage operations the user handlers depend on. Every implementation shares the
+/// single-table layout written by `dynamo` (`USER#<id>` primary keys, the
+/// `EMAIL#<email>` / `USERNAME#<username>` GSI1 keys and the `USERS` GSI2 listing keys).
+///
+/// Every mutation also writes a `HISTORY#<ulid>` item under the user's partition in the
+/// same transaction, attributed to the principal of the `ChangeContext`, and an
+/// `OUTBOX#<ulid>` item with the event announcing the change for `outbox::relay_outbox`
+/// to publish.
+#[async_trait]
+pub trait UserRepository: Send + Sync {
+    /// Soft deleted users are only returned when `include_deleted` is set.
+    async fn get_user_by_id(
+        &self,
+        user_id: &str,
+        include_deleted: bool,
+    ) -> Result<Option<User>, Box<dyn std::error::Error>>;
+
+    /// `get_user_by_id` returning only `fields` of the user. Implementations that can
+    /// read fewer attributes from storage override this.
+    async fn get_user_fields_by_id(
+        &self,
+        user_id: &str,
+        include_deleted: bool,
+        fields: &Fieldset,
+    ) -> Result<Option<PartialUser>, Box<dyn std::error::Error>> {
+        let user = self.get_user_by_id(user_id, include_deleted).await?;
+
+        Ok(user.map(|user| fields.select_user(&user)))
+    }
+
+    async fn get_user_by_email(
+        &self,
+        email: &str,
+        include_deleted: bool,
+    ) -> Result<Option<Vec<User>>, Box<dyn std::error::Error>>;
+
+    /// Fetches many users at once, in the order their IDs were given. Deleted users
+    /// are reported as missing unless `include_deleted` is set.
+    async fn get_users_by_ids(
+        &self,
+        user_ids: &[String],
+        include_deleted: bool,
+    ) -> Result<BatchGetResult<User>, Box<dyn std::error::Error>>;
+
+    async fn create_user(
+        &self,
+        input: CreateUserArgs,
+        context: &ChangeContext,
+    ) -> Result<String, Box<dyn std::error::Error>>;
+
+    /// Returns the ID of the user that has reserved `value` for `field`, if any.
+    async fn get_unique_value_owner(
+        &self,
+        field: UniqueField,
+        value: &str,
+    ) -> Result<Option<String>, Box<dyn std::error::Error>>;
+
+    /// Updates the user, failing with a `PreconditionError` when `expected_version`
+    /// is given and the stored user is missing or at a different version.
+    async fn update_user(
+        &self,
+        user_id: &str,
+        input: UpdateUserArgs,
+        expected_version: Option<u64>,
+        context: &ChangeContext,
+    ) -> Result<bool, Box<dyn std::error::Error>>;
+
+    /// Soft deletes the user, with the same `expected_version` check as `update_user`.
+    async fn delete_user(
+        &self,
+        user_id: &str,
+        expected_version: Option<u64>,
+        context: &ChangeContext,
+    ) -> Result<bool, Box<dyn std::error::Error>>;
+
+    /// Restores a soft deleted user whose retention period has not ended.
+    async fn restore_user(
+        &self,
+        user_id: &str,
+        expected_version: Option<u64>,
+        context: &ChangeContext,
+    ) -> Result<bool, Box<dyn std::error::Error>>;
+
+    /// Lists the history of a user, newest first unless `args.order` says otherwise.
+    async fn list_user_history(
+        &self,
+        user_id: &str,
+        args: ListUserHistoryArgs,
+    ) -> Result<PaginatedResult<UserHistory>, Box<dyn std::error::Error>>;
+
+    /// Reads a page of one segment of a parallel scan over every user, in no
+    /// particular order.
+    async fn scan_users(
+        &self,
+        args: ScanUsersArgs,
+    ) -> Result<PaginatedResult<User>, Box<dyn std::error::Error>>;
+
+    /// Lists users by creation date, or by username when `args.username_prefix` is
+    /// set, a page of `args.limit` at a time.
+    async fn list_users(
+        &self,
+        args: ListUsersArgs,
+    ) -> Result<PaginatedResult<User>, Box<dyn std::error::Error>>;
+
+    /// `list_users` returning only `fields` of each user, overridden like
+    /// `get_user_fields_by_id`.
+    async fn list_user_fields(
+        &self,
+        args: ListUsersArgs,
+        fields: &Fieldset,
+    ) -> Result<PaginatedResult<PartialUser>, Box<dyn std::error::Error>> {
+        let page = self.list_users(args).await?;
+
+        Ok(PaginatedResult {
+            data: page
+                .data
+                .iter()
+                .map(|user| fields.select_user(user))
+                .collect(),
+            token: page.token,
+        })
+    }
+
+    /// Reads up to `limit` outbox entries waiting to be published, oldest first,
+    /// starting after the entry `after` when given.
+    async fn list_outbox_entries(
+        &self,
+        limit: i32,
+        after: Option<&str>,
+    ) -> Result<Vec<OutboxEntry>, Box<dyn std::error::Error>>;
+
+    /// Removes an outbox entry once its event has been published.
+    async fn delete_outbox_entry(&self, entry_id: &str) -> Result<(), Box<dyn std::error::Error>>;
+
+    /// Counts a failed attempt to publish an outbox entry.
+    async fn record_outbox_attempt(&self, entry_id: &str)
+        -> Result<(), Box<dyn std::error::Error>>;
+
+    /// Moves an outbox entry that could not be published to the dead-letter
+    /// partition, so it is no longer relayed.
+    async fn dead_letter_outbox_entry(
+        &self,
+        entry: &OutboxEntry,
+    ) -> Result<(), Box<dyn std::error::Error>>;
+}
@@ -0,0 +1,198 @@
+use super::error::RepositoryError;
+
+use std::future::Future;
+use std::time::{Duration, SystemTime};
+use tracing::warn;
+
+/// Time kept free before the deadline for the handler to answer after a last attempt.
+static DEADLINE_MARGIN: Duration = Duration::from_secs(1);
+
+/// How repository calls that fail with a retryable `RepositoryError` are repeated:
+/// exponential backoff with full jitter, for at most `max_attempts` calls, and never
+/// sleeping past the Lambda's deadline.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RetryPolicy {
+    pub max_attempts: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+    /// When the invocation times out, if the caller has one.
+    pub deadline: Option<SystemTime>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 4,
+            base_delay: Duration::from_millis(50),
+            max_delay: Duration::from_secs(1),
+            deadline: None,
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn with_deadline(mut self, deadline: SystemTime) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    /// Upper bound of the delay before retry `attempt`, doubling from `base_delay`
+    /// up to `max_delay`. The actual delay is picked uniformly below it.
+    pub fn max_backoff(&self, attempt: u32) -> Duration {
+        self.base_delay
+            .saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)))
+            .min(self.max_delay)
+    }
+
+    /// Delay before retrying a call that failed with `err` on attempt `attempt`, or
+    /// `None` when the error should be returned instead.
+    pub fn retry_delay(
+        &self,
+        err: &(dyn std::error::Error + 'static),
+        idempotent: bool,
+        attempt: u32,
+    ) -> Option<Duration> {
+        let retryable = err
+            .downcast_ref::<RepositoryError>()
+            .is_some_and(|err| err.is_retryable(idempotent));
+        if !retryable || attempt >= self.max_attempts {
+            return None;
+        }
+
+        let max_backoff = self.max_backoff(attempt).as_millis() as u64;
+        let delay = Duration::from_millis(fastrand::u64(0..=max_backoff));
+
+        match self.deadline {
+            Some(deadline) => {
+                let remaining = deadline
+                    .duration_since(SystemTime::now())
+                    .unwrap_or_default();
+                (remaining > delay + DEADLINE_MARGIN).then_some(delay)
+            }
+            None => Some(delay),
+        }
+    }
+
+    /// Runs `operation` until it succeeds, fails with an error that is not worth
+    /// retrying, or runs out of attempts or time. Only `idempotent` operations are
+    /// repeated after errors that leave it unknown whether they were applied.
+    pub async fn run<T, F, Fut>(
+        &self,
+        idempotent: bool,
+        mut operation: F,
+    ) -> Result<T, Box<dyn std::error::Error>>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, Box<dyn std::error::Error>>>,
+    {
+        let mut attempt = 1;
+
+        loop {
+            let delay = match operation().await {
+                Ok(value) => return Ok(value),
+                Err(err) => match self.retry_delay(err.as_ref(), idempotent, attempt) {
+                    Some(delay) => {
+                        warn!("Retrying attempt {} in {:?}: {}", attempt, delay, err);
+                        delay
+                    }
+                    None => return Err(err),
+                },
+            };
+
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod unit_tests {
+    use super::*;
+    use crate::error::Error;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    fn policy() -> RetryPolicy {
+        RetryPolicy {
+            base_delay: Duration::from_millis(1),
+            max_delay: Duration::from_millis(2),
+            ..Default::default()
+        }
+    }
+
+    async fn fail_with(
+        calls: &AtomicU32,
+        err: RepositoryError,
+        succeed_on: u32,
+    ) -> Result<u32, Box<dyn std::error::Error>> {
+        let call = calls.fetch_add(1, Ordering::SeqCst) + 1;
+        match call >= succeed_on {
+            true => Ok(call),
+            false => Err(Box::new(err)),
+        }
+    }
+
+    #[tokio::test]
+    async fn should_retry_throttled_calls_until_they_succeed() {
+        let calls = AtomicU32::new(0);
+
+        let result = policy()
+            .run(false, || {
+                fail_with(&calls, RepositoryError::Throttled("slow down".into()), 3)
+            })
+            .await
+            .expect("Call was not retried");
+
+        assert_eq!(result, 3);
+    }
+
+    #[tokio::test]
+    async fn should_not_retry_ambiguous_failures_of_writes() {
+        let calls = AtomicU32::new(0);
+
+        policy()
+            .run(false, || {
+                fail_with(&calls, RepositoryError::Timeout("timed out".into()), 2)
+            })
+            .await
+            .expect_err("Write was retried after a timeout");
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        let calls = AtomicU32::new(0);
+        policy()
+            .run(true, || {
+                fail_with(&calls, RepositoryError::Timeout("timed out".into()), 2)
+            })
+            .await
+            .expect("Read was not retried after a timeout");
+    }
+
+    #[tokio::test]
+    async fn should_stop_at_max_attempts_and_deadline() {
+        let calls = AtomicU32::new(0);
+
+        policy()
+            .run(true, || {
+                fail_with(&calls, RepositoryError::Throttled("slow down".into()), 10)
+            })
+            .await
+            .expect_err("Call succeeded past max attempts");
+        assert_eq!(calls.load(Ordering::SeqCst), 4);
+
+        let calls = AtomicU32::new(0);
+        policy()
+            .with_deadline(SystemTime::now() + Duration::from_millis(500))
+            .run(true, || {
+                fail_with(&calls, RepositoryError::Throttled("slow down".into()), 10)
+            })
+            .await
+            .expect_err("Call was retried past the deadline");
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn should_not_retry_domain_errors() {
+        let err = Error::PreconditionError("Version mismatch");
+
+        assert_eq!(policy().retry_delay(&err, true, 1), None);
+    }
+}
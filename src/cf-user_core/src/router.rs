@@ -0,0 +1,322 @@
+use super::{
+    fn_handler, handlers,
+    models::{handler_response::HandleResponse, permissions::Permission, problem::Problem},
+    repository::UserRepository,
+};
+
+use futures::future::BoxFuture;
+use lambda_http::http::{Method, StatusCode};
+use lambda_http::request::RequestContext;
+use lambda_http::{Request, RequestExt, Response};
+use serde::Serialize;
+use serde_json::value::RawValue;
+use std::future::Future;
+use std::sync::Arc;
+
+/// A handler's response with its body serialized, so routes with different body
+/// types are stored alike.
+type RawResponse = Result<HandleResponse<Box<RawValue>>, Box<dyn std::error::Error>>;
+
+type BoxedHandler =
+    Box<dyn Fn(Request, Arc<dyn UserRepository>) -> BoxFuture<'static, RawResponse> + Send + Sync>;
+
+/// A handler and the permission a caller needs to invoke it, served for one method
+/// on one API Gateway resource.
+pub struct Route {
+    pub method: Method,
+    /// The resource as API Gateway names it, with path parameters as templates, e.g.
+    /// `/v1/users/{userId}`.
+    pub resource: &'static str,
+    pub permission: Permission,
+    handler: BoxedHandler,
+}
+
+/// Maps the method and resource of an API Gateway request to the handler serving
+/// it, so one Lambda can serve every route.
+#[derive(Default)]
+pub struct Router {
+    routes: Vec<Route>,
+}
+
+impl Router {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Serves `method` on `resource` with `handler`, whose response body is serialized
+    /// when it returns.
+    pub fn route<F, Fut, T>(
+        mut self,
+        method: Method,
+        resource: &'static str,
+        permission: Permission,
+        handler: F,
+    ) -> Self
+    where
+        F: Fn(Request, Arc<dyn UserRepository>) -> Fut + Send + Sync + 'static,
+        Fut:
+            Future<Output = Result<HandleResponse<T>, Box<dyn std::error::Error>>> + Send + 'static,
+        T: Serialize,
+    {
+        self.routes.push(Route {
+            method,
+            resource,
+            permission,
+            handler: Box::new(move |event, repository| {
+                let response = handler(event, repository);
+                Box::pin(async move { Ok(response.await?.into_raw()?) })
+            }),
+        });
+        self
+    }
+
+    pub fn routes(&self) -> &[Route] {
+        &self.routes
+    }
+
+    /// The route for `method` on `resource`, or the status to answer with when there
+    /// is none: 405 when the resource is served for other methods, 404 otherwise.
+    pub fn find(&self, method: &Method, resource: &str) -> Result<&Route, StatusCode> {
+        let mut routes = self
+            .routes
+            .iter()
+            .filter(|route| route.resource == resource)
+            .peekable();
+
+        if routes.peek().is_none() {
+            return Err(StatusCode::NOT_FOUND);
+        }
+
+        routes
+            .find(|route| route.method == method)
+            .ok_or(StatusCode::METHOD_NOT_ALLOWED)
+    }
+
+    /// Serves a request with the handler of its route, through
+    /// `fn_handler::handle_request` as a single-route Lambda would.
+    pub async fn handle_request(
+        &self,
+        event: Request,
+        repository: Option<Arc<dyn UserRepository>>,
+    ) -> Result<Response<String>, Box<dyn std::error::Error>> {
+        let resource = match event.request_context_ref() {
+            Some(RequestContext::ApiGatewayV1(ctx)) => ctx.resource_path.clone(),
+            _ => None,
+        }
+        .unwrap_or_default();
+
+        let route = match self.find(event.method(), &resource) {
+            Ok(route) => route,
+            Err(status) => {
+                let problem = Problem::new(status).with_detail(&format!(
+                    "No route for {} {}",
+                    event.method(),
+                    resource
+                ));
+
+                return fn_handler::problem_response(&match fn_handler::request_id(&event) {
+                    Some(request_id) => problem.with_instance(&request_id),
+                    None => problem,
+                });
+            }
+        };
+
+        fn_handler::handle_request(event, &route.handler, repository, route.permission).await
+    }
+}
+
+/// Every route of the user API, as the CDK stack lays out its resources.
+pub fn user_routes() -> Router {
+    Router::new()
+        .route(
+            Method::GET,
+            "/v1/users",
+            Permission::UserList,
+            handlers::list_users::function_handler,
+        )
+        .route(
+            Method::POST,
+            "/v1/users",
+            Permission::UserCreate,
+            handlers::create_user::function_handler,
+        )
+        .route(
+            Method::POST,
+            "/v1/users:batchGet",
+            Permission::UserGet,
+            handlers::batch_get_users::function_handler,
+        )
+        .route(
+            Method::POST,
+            "/v1/users:import",
+            Permission::UserImport,
+            handlers::import_users::function_handler,
+        )
+        .route(
+            Method::GET,
+            "/v1/users/{userId}",
+            Permission::UserGet,
+            handlers::get_user::function_handler,
+        )
+        .route(
+            Method::PUT,
+            "/v1/users/{userId}",
+            Permission::UserUpdate,
+            handlers::update_user::function_handler,
+        )
+        .route(
+            Method::DELETE,
+            "/v1/users/{userId}",
+            Permission::UserDelete,
+            handlers::delete_user::function_handler,
+        )
+        .route(
+            Method::POST,
+            "/v1/users/{userId}/restore",
+            Permission::UserRestore,
+            handlers::restore_user::function_handler,
+        )
+        .route(
+            Method::GET,
+            "/v1/users/{userId}/history",
+            Permission::UserHistory,
+            handlers::get_user_history::function_handler,
+        )
+}
+
+#[cfg(test)]
+mod unit_tests {
+    use super::*;
+    use crate::{
+        mock_request::{create_mock_request, with_resource_path},
+        models::user::User,
+        repository::InMemoryUserRepository,
+    };
+    use lambda_http::Body;
+    use serde_json::json;
+    use std::collections::HashMap;
+
+    fn request(
+        method: Method,
+        resource: &str,
+        path: &str,
+        body: Body,
+        permissions: &[Permission],
+    ) -> Request {
+        with_resource_path(
+            create_mock_request(
+                method,
+                &format!("https://dev-api.classifind.app/user{}", path),
+                body,
+                permissions,
+            ),
+            resource,
+        )
+    }
+
+    #[tokio::test]
+    async fn should_serve_each_route_with_its_handler() {
+        let router = user_routes();
+        let repository = Arc::new(InMemoryUserRepository::new());
+
+        let response = router
+            .handle_request(
+                request(
+                    Method::POST,
+                    "/v1/users",
+                    "/v1/users",
+                    Body::Text(
+                        json!({ "Username": "tester", "Email": "tester@example.com" }).to_string(),
+                    ),
+                    &[Permission::UserCreate],
+                ),
+                Some(repository.clone()),
+            )
+            .await
+            .expect("Router failed");
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let created: User = serde_json::from_str(response.body()).expect("Invalid user body");
+
+        let response = router
+            .handle_request(
+                request(
+                    Method::GET,
+                    "/v1/users/{userId}",
+                    &format!("/v1/users/{}", created.user_id),
+                    Body::Empty,
+                    &[Permission::UserGet],
+                )
+                .with_path_parameters(HashMap::from([(
+                    "userId".to_string(),
+                    created.user_id.clone(),
+                )])),
+                Some(repository),
+            )
+            .await
+            .expect("Router failed");
+        assert_eq!(response.status(), StatusCode::OK);
+        let user: User = serde_json::from_str(response.body()).expect("Invalid user body");
+        assert_eq!(user, created);
+    }
+
+    #[tokio::test]
+    async fn should_require_the_permission_of_the_route() {
+        let response = user_routes()
+            .handle_request(
+                request(
+                    Method::GET,
+                    "/v1/users",
+                    "/v1/users",
+                    Body::Empty,
+                    &[Permission::UserGet],
+                ),
+                Some(Arc::new(InMemoryUserRepository::new())),
+            )
+            .await
+            .expect("Router failed");
+
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    }
+
+    #[tokio::test]
+    async fn should_reject_requests_without_a_route() {
+        let router = user_routes();
+
+        for (method, resource, status) in [
+            (Method::GET, "/v1/accounts", StatusCode::NOT_FOUND),
+            (
+                Method::PATCH,
+                "/v1/users/{userId}",
+                StatusCode::METHOD_NOT_ALLOWED,
+            ),
+        ] {
+            let response = router
+                .handle_request(
+                    request(method, resource, resource, Body::Empty, &[]),
+                    Some(Arc::new(InMemoryUserRepository::new())),
+                )
+                .await
+                .expect("Router failed");
+
+            assert_eq!(response.status(), status);
+        }
+    }
+
+    #[test]
+    fn should_not_serve_a_route_twice() {
+        let router = user_routes();
+
+        for route in router.routes() {
+            let count = router
+                .routes()
+                .iter()
+                .filter(|other| other.method == route.method && other.resource == route.resource)
+                .count();
+            assert_eq!(
+                count, 1,
+                "{} {} is served twice",
+                route.method, route.resource
+            );
+        }
+    }
+}
@@ -0,0 +1,149 @@
+use super::{config, error::Error};
+use hyper::{body, Body, Client, Request};
+use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
+use serde::Deserialize;
+use std::env;
+
+/// Port the AWS Parameters and Secrets Lambda Extension listens on, unless
+/// `PARAMETERS_SECRETS_EXTENSION_HTTP_PORT` says otherwise.
+static DEFAULT_EXTENSION_PORT: &str = "2773";
+
+#[derive(Deserialize)]
+struct GetSecretValueResponse {
+    #[serde(rename = "SecretString")]
+    secret_string: Option<String>,
+}
+
+/// Reads the pagination token secret named by `PAGINATION_TOKEN_SECRET_ARN` from
+/// Secrets Manager and keeps it for the life of the process. Called once at cold
+/// start, before the runtime takes requests, so the secret never appears in the
+/// function configuration. Does nothing when the ARN is unset, e.g. when
+/// `PAGINATION_TOKEN_SECRET` is set directly for local development.
+pub async fn load_pagination_token_secret() -> Result<(), Error> {
+    let Ok(secret_id) = env::var("PAGINATION_TOKEN_SECRET_ARN") else {
+        return Ok(());
+    };
+
+    let port = env::var("PARAMETERS_SECRETS_EXTENSION_HTTP_PORT")
+        .unwrap_or_else(|_| DEFAULT_EXTENSION_PORT.to_owned());
+    let session_token = env::var("AWS_SESSION_TOKEN").unwrap_or_default();
+
+    let secret = get_secret_string(
+        &format!("http://localhost:{}", port),
+        &secret_id,
+        &session_token,
+    )
+    .await?;
+    config::set_pagination_token_secret(secret);
+
+    Ok(())
+}
+
+/// Gets the string value of a secret through the extension at `endpoint`, which
+/// caches it and signs the call to Secrets Manager with the function's role.
+async fn get_secret_string(
+    endpoint: &str,
+    secret_id: &str,
+    session_token: &str,
+) -> Result<String, Error> {
+    let request = Request::get(format!(
+        "{}/secretsmanager/get?secretId={}",
+        endpoint,
+        utf8_percent_encode(secret_id, NON_ALPHANUMERIC)
+    ))
+    .header("X-Aws-Parameters-Secrets-Token", session_token)
+    .body(Body::empty())
+    .map_err(|err| Error::SdkError(format!("Error building secret request: {}", err)))?;
+
+    let response = Client::new()
+        .request(request)
+        .await
+        .map_err(|err| Error::SdkError(format!("Error reading secret: {}", err)))?;
+    if !response.status().is_success() {
+        return Err(Error::SdkError(format!(
+            "Error reading secret: {}",
+            response.status()
+        )));
+    }
+
+    let bytes = body::to_bytes(response.into_body())
+        .await
+        .map_err(|err| Error::SdkError(format!("Error reading secret: {}", err)))?;
+    let response: GetSecretValueResponse = serde_json::from_slice(&bytes)
+        .map_err(|_| Error::InitError("Secret response is malformed"))?;
+
+    response
+        .secret_string
+        .filter(|secret| !secret.is_empty())
+        .ok_or(Error::InitError("Secret has no string value"))
+}
+
+#[cfg(test)]
+mod unit_tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    /// Answers one request with `status` and `body`, returning the request it read.
+    async fn serve_once(
+        status: &'static str,
+        body: &'static str,
+    ) -> (String, tokio::task::JoinHandle<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("Failed to bind");
+        let endpoint = format!("http://{}", listener.local_addr().expect("Missing address"));
+
+        let server = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.expect("Failed to accept");
+            let mut request = vec![0; 4096];
+            let read = stream.read(&mut request).await.expect("Failed to read");
+            let response = format!(
+                "HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                status,
+                body.len(),
+                body
+            );
+            stream
+                .write_all(response.as_bytes())
+                .await
+                .expect("Failed to write");
+
+            String::from_utf8_lossy(&request[..read]).into_owned()
+        });
+
+        (endpoint, server)
+    }
+
+    #[tokio::test]
+    async fn should_read_secret_string_through_extension() {
+        let (endpoint, server) =
+            serve_once("200 OK", r#"{"Name":"pagination","SecretString":"s3cret"}"#).await;
+
+        let secret = get_secret_string(&endpoint, "arn:aws:secretsmanager:x:1:secret:p", "token")
+            .await
+            .expect("Failed to read secret");
+
+        let request = server.await.expect("Server failed").to_lowercase();
+        assert_eq!(secret, "s3cret");
+        assert!(request.starts_with(
+            "get /secretsmanager/get?secretid=arn%3aaws%3asecretsmanager%3ax%3a1%3asecret%3ap "
+        ));
+        assert!(request.contains("x-aws-parameters-secrets-token: token"));
+    }
+
+    #[tokio::test]
+    async fn should_fail_without_a_secret_string() {
+        for (status, body) in [
+            ("200 OK", r#"{"Name":"pagination","SecretString":""}"#),
+            ("200 OK", r#"{"Name":"pagination"}"#),
+            ("400 Bad Request", "not authorized"),
+        ] {
+            let (endpoint, _server) = serve_once(status, body).await;
+
+            assert!(get_secret_string(&endpoint, "pagination", "token")
+                .await
+                .is_err());
+        }
+    }
+}
@@ -0,0 +1,143 @@
+use chrono::{DateTime, NaiveDate, NaiveDateTime, SecondsFormat, SubsecRound, TimeZone, Utc};
+use serde::de::Error;
+use serde::{Deserialize, Deserializer, Serializer};
+
+/// How chrono's `Display` renders a `DateTime<Utc>`, e.g. `2023-07-01 12:00:00.123 UTC`.
+/// Items written before timestamps were typed store dates this way.
+static LEGACY_FORMAT: &str = "%Y-%m-%d %H:%M:%S%.f UTC";
+
+/// The current time, at the millisecond precision timestamps are stored with.
+pub fn now() -> DateTime<Utc> {
+    Utc::now().trunc_subsecs(3)
+}
+
+/// RFC 3339 in UTC with exactly three fractional digits, e.g.
+/// `2023-07-01T12:00:00.123Z`, so stored timestamps sort as strings.
+pub fn format(timestamp: &DateTime<Utc>) -> String {
+    timestamp.to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+/// Reads RFC 3339 with any offset, the legacy `Display` format, or a bare date as
+/// midnight UTC. Precision beyond milliseconds is dropped.
+pub fn parse(value: &str) -> Option<DateTime<Utc>> {
+    let timestamp = DateTime::parse_from_rfc3339(value)
+        .map(|timestamp| timestamp.with_timezone(&Utc))
+        .or_else(|_| {
+            NaiveDateTime::parse_from_str(value, LEGACY_FORMAT)
+                .map(|timestamp| Utc.from_utc_datetime(&timestamp))
+        })
+        .or_else(|_| {
+            NaiveDate::parse_from_str(value, "%Y-%m-%d")
+                .map(|date| Utc.from_utc_datetime(&date.and_hms_opt(0, 0, 0).unwrap_or_default()))
+        })
+        .ok()?;
+
+    Some(timestamp.trunc_subsecs(3))
+}
+
+/// Serializes a timestamp with `format`, for `#[serde(with = "timestamp")]`.
+pub fn serialize<S: Serializer>(
+    timestamp: &DateTime<Utc>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&format(timestamp))
+}
+
+/// Deserializes a timestamp with `parse`, for `#[serde(with = "timestamp")]`.
+pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<DateTime<Utc>, D::Error> {
+    let value = String::deserialize(deserializer)?;
+
+    parse(&value).ok_or_else(|| D::Error::custom(format!("Invalid timestamp: {}", value)))
+}
+
+/// `#[serde(with = "timestamp::option")]` for optional timestamps.
+pub mod option {
+    use chrono::{DateTime, Utc};
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(
+        timestamp: &Option<DateTime<Utc>>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match timestamp {
+            Some(timestamp) => super::serialize(timestamp, serializer),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<DateTime<Utc>>, D::Error> {
+        #[derive(Deserialize)]
+        struct Timestamp(#[serde(with = "super")] DateTime<Utc>);
+
+        Ok(Option::<Timestamp>::deserialize(deserializer)?.map(|timestamp| timestamp.0))
+    }
+}
+
+#[cfg(test)]
+mod unit_tests {
+    use super::*;
+
+    fn timestamp(millis: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2023, 7, 1, 12, 0, 0).unwrap()
+            + chrono::Duration::milliseconds(millis.into())
+    }
+
+    #[test]
+    fn should_format_with_millisecond_precision() {
+        assert_eq!(format(&timestamp(0)), "2023-07-01T12:00:00.000Z");
+        assert_eq!(format(&timestamp(123)), "2023-07-01T12:00:00.123Z");
+        assert_eq!(now().timestamp_subsec_nanos() % 1_000_000, 0);
+    }
+
+    #[test]
+    fn should_parse_current_and_legacy_formats() {
+        for value in [
+            "2023-07-01T12:00:00.123Z",
+            "2023-07-01T14:00:00.123+02:00",
+            "2023-07-01 12:00:00.123 UTC",
+            "2023-07-01 12:00:00.123456789 UTC",
+        ] {
+            assert_eq!(parse(value), Some(timestamp(123)), "{}", value);
+        }
+
+        assert_eq!(parse("2023-07-01 12:00:00 UTC"), Some(timestamp(0)));
+        assert_eq!(
+            parse("2023-07-01"),
+            Some(Utc.with_ymd_and_hms(2023, 7, 1, 0, 0, 0).unwrap())
+        );
+        assert_eq!(parse("yesterday"), None);
+    }
+
+    #[test]
+    fn should_round_trip_through_json() {
+        #[derive(Debug, PartialEq, Deserialize, serde::Serialize)]
+        struct Dates {
+            #[serde(with = "super")]
+            created: DateTime<Utc>,
+            #[serde(with = "super::option", default)]
+            deleted: Option<DateTime<Utc>>,
+        }
+
+        let dates = Dates {
+            created: timestamp(5),
+            deleted: None,
+        };
+
+        let json = serde_json::to_string(&dates).expect("Failed to serialize");
+        assert_eq!(
+            json,
+            r#"{"created":"2023-07-01T12:00:00.005Z","deleted":null}"#
+        );
+        assert_eq!(
+            serde_json::from_str::<Dates>(&json).expect("Failed to deserialize"),
+            dates
+        );
+        assert_eq!(
+            serde_json::from_str::<Dates>(r#"{"created":"2023-07-01 12:00:00.005 UTC"}"#)
+                .expect("Failed to deserialize"),
+            dates
+        );
+    }
+}
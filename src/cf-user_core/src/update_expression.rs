@@ -0,0 +1,505 @@
+use super::ext::AttributeValuesExt;
+use aws_sdk_dynamodb::types::AttributeValue;
+use std::collections::HashMap;
+
+#[derive(Clone, Debug, PartialEq)]
+enum Action {
+    Set,
+    SetIfNotExists,
+    ListAppend,
+    Remove,
+    Add,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Condition {
+    AttributeExists,
+    AttributeNotExists,
+    Equals,
+    MissingOrEquals,
+    GreaterThan,
+}
+
+/// A single clause, holding the attribute it targets and the placeholders used to
+/// render it.
+#[derive(Clone, Debug)]
+struct Clause<T> {
+    kind: T,
+    attribute: String,
+    name: String,
+    values: Vec<String>,
+}
+
+/// Builds an `UpdateExpression` and optional `ConditionExpression` together with the
+/// `ExpressionAttributeNames` and `ExpressionAttributeValues` they reference.
+///
+/// Every attribute is addressed through a `#Name` placeholder, so reserved words such
+/// as `Name` or `Status` are always safe to use. Characters that are not valid in a
+/// placeholder are replaced with `_`, and clashing placeholders get a numeric suffix.
+/// Clauses render in the order they were added.
+#[derive(Clone, Debug, Default)]
+pub struct UpdateExpression {
+    updates: Vec<Clause<Action>>,
+    conditions: Vec<Clause<Condition>>,
+    names: HashMap<String, String>,
+    values: HashMap<String, AttributeValue>,
+}
+
+impl UpdateExpression {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// `SET #attribute = :attribute`
+    pub fn set(self, attribute: &str, value: AttributeValue) -> Self {
+        self.update(Action::Set, attribute, vec![value])
+    }
+
+    /// `SET #attribute = if_not_exists(#attribute, :attribute)`
+    pub fn set_if_not_exists(self, attribute: &str, value: AttributeValue) -> Self {
+        self.update(Action::SetIfNotExists, attribute, vec![value])
+    }
+
+    /// Appends the elements of a list value, creating the list when it is missing.
+    pub fn list_append(self, attribute: &str, value: AttributeValue) -> Self {
+        self.update(
+            Action::ListAppend,
+            attribute,
+            vec![value, AttributeValue::L(Vec::new())],
+        )
+    }
+
+    /// `REMOVE #attribute`
+    pub fn remove(self, attribute: &str) -> Self {
+        self.update(Action::Remove, attribute, Vec::new())
+    }
+
+    /// `ADD #attribute :attribute`, which increments a number attribute.
+    pub fn add(self, attribute: &str, value: AttributeValue) -> Self {
+        self.update(Action::Add, attribute, vec![value])
+    }
+
+    /// Sets the attribute when a value is given and removes it otherwise.
+    pub fn set_or_remove(self, attribute: &str, value: Option<AttributeValue>) -> Self {
+        match value {
+            Some(value) => self.set(attribute, value),
+            None => self.remove(attribute),
+        }
+    }
+
+    /// Requires `attribute_exists(#attribute)`.
+    pub fn condition_attribute_exists(self, attribute: &str) -> Self {
+        self.condition(Condition::AttributeExists, attribute, Vec::new())
+    }
+
+    /// Requires `attribute_not_exists(#attribute)`.
+    pub fn condition_attribute_not_exists(self, attribute: &str) -> Self {
+        self.condition(Condition::AttributeNotExists, attribute, Vec::new())
+    }
+
+    /// Requires `#attribute = :attribute`.
+    pub fn condition_equals(self, attribute: &str, value: AttributeValue) -> Self {
+        self.condition(Condition::Equals, attribute, vec![value])
+    }
+
+    /// Requires `(attribute_not_exists(#attribute) OR #attribute = :attribute)`, which
+    /// also holds when the item itself is missing.
+    pub fn condition_missing_or_equals(self, attribute: &str, value: AttributeValue) -> Self {
+        self.condition(Condition::MissingOrEquals, attribute, vec![value])
+    }
+
+    /// Requires `#attribute > :attribute`.
+    pub fn condition_greater_than(self, attribute: &str, value: AttributeValue) -> Self {
+        self.condition(Condition::GreaterThan, attribute, vec![value])
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.updates.is_empty()
+    }
+
+    pub fn update_expression(&self) -> String {
+        let mut set_clauses: Vec<String> = Vec::new();
+        let mut remove_clauses: Vec<String> = Vec::new();
+        let mut add_clauses: Vec<String> = Vec::new();
+
+        for clause in &self.updates {
+            let name = &clause.name;
+            match clause.kind {
+                Action::Set => set_clauses.push(format!("{} = {}", name, clause.values[0])),
+                Action::SetIfNotExists => set_clauses.push(format!(
+                    "{} = if_not_exists({}, {})",
+                    name, name, clause.values[0]
+                )),
+                Action::ListAppend => set_clauses.push(format!(
+                    "{} = list_append(if_not_exists({}, {}), {})",
+                    name, name, clause.values[1], clause.values[0]
+                )),
+                Action::Remove => remove_clauses.push(name.to_owned()),
+                Action::Add => add_clauses.push(format!("{} {}", name, clause.values[0])),
+            }
+        }
+
+        let mut sections: Vec<String> = Vec::new();
+        if !set_clauses.is_empty() {
+            sections.push(format!("SET {}", set_clauses.join(", ")));
+        }
+        if !remove_clauses.is_empty() {
+            sections.push(format!("REMOVE {}", remove_clauses.join(", ")));
+        }
+        if !add_clauses.is_empty() {
+            sections.push(format!("ADD {}", add_clauses.join(", ")));
+        }
+
+        sections.join(" ")
+    }
+
+    pub fn condition_expression(&self) -> Option<String> {
+        if self.conditions.is_empty() {
+            return None;
+        }
+
+        let conditions: Vec<String> = self
+            .conditions
+            .iter()
+            .map(|clause| match clause.kind {
+                Condition::AttributeExists => format!("attribute_exists({})", clause.name),
+                Condition::AttributeNotExists => {
+                    format!("attribute_not_exists({})", clause.name)
+                }
+                Condition::Equals => format!("{} = {}", clause.name, clause.values[0]),
+                Condition::MissingOrEquals => format!(
+                    "(attribute_not_exists({}) OR {} = {})",
+                    clause.name, clause.name, clause.values[0]
+                ),
+                Condition::GreaterThan => format!("{} > {}", clause.name, clause.values[0]),
+            })
+            .collect();
+
+        Some(conditions.join(" AND "))
+    }
+
+    /// `ExpressionAttributeNames`, or `None` when nothing was added, since DynamoDB
+    /// rejects an empty map.
+    pub fn names(&self) -> Option<HashMap<String, String>> {
+        match self.names.is_empty() {
+            true => None,
+            false => Some(self.names.clone()),
+        }
+    }
+
+    /// `ExpressionAttributeValues`, or `None` when no clause takes a value.
+    pub fn values(&self) -> Option<HashMap<String, AttributeValue>> {
+        match self.values.is_empty() {
+            true => None,
+            false => Some(self.values.clone()),
+        }
+    }
+
+    /// Evaluates the conditions against an item, or against a missing item when
+    /// `item` is `None`, the way DynamoDB would.
+    pub fn matches(&self, item: Option<&HashMap<String, AttributeValue>>) -> bool {
+        self.conditions.iter().all(|clause| {
+            let current = item.and_then(|item| item.get(&clause.attribute));
+            match clause.kind {
+                Condition::AttributeExists => current.is_some(),
+                Condition::AttributeNotExists => current.is_none(),
+                Condition::Equals => current == self.values.get(&clause.values[0]),
+                Condition::MissingOrEquals => {
+                    current.is_none() || current == self.values.get(&clause.values[0])
+                }
+                Condition::GreaterThan => match (current, self.values.get(&clause.values[0])) {
+                    (Some(AttributeValue::N(current)), Some(AttributeValue::N(value))) => {
+                        current.parse::<f64>().unwrap_or_default()
+                            > value.parse::<f64>().unwrap_or_default()
+                    }
+                    (Some(AttributeValue::S(current)), Some(AttributeValue::S(value))) => {
+                        current > value
+                    }
+                    _ => false,
+                },
+            }
+        })
+    }
+
+    /// Applies the update clauses to an item in place, the way DynamoDB would.
+    pub fn apply(&self, item: &mut HashMap<String, AttributeValue>) {
+        for clause in &self.updates {
+            let attribute = clause.attribute.to_owned();
+            let value = clause
+                .values
+                .first()
+                .map(|value| self.values[value].clone());
+
+            match (&clause.kind, value) {
+                (Action::Set, Some(value)) => {
+                    item.insert(attribute, value);
+                }
+                (Action::SetIfNotExists, Some(value)) => {
+                    item.entry(attribute).or_insert(value);
+                }
+                (Action::ListAppend, Some(AttributeValue::L(values))) => {
+                    let mut list = match item.remove(&attribute) {
+                        Some(AttributeValue::L(list)) => list,
+                        _ => Vec::new(),
+                    };
+                    list.extend(values);
+                    item.insert(attribute, AttributeValue::L(list));
+                }
+                (Action::Remove, _) => {
+                    item.remove(&attribute);
+                }
+                (Action::Add, Some(AttributeValue::N(value))) => {
+                    let total = item.get_n(&attribute).unwrap_or_default()
+                        + value.parse::<f64>().unwrap_or_default();
+                    item.insert(attribute, AttributeValue::N(total.to_string()));
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn update(mut self, kind: Action, attribute: &str, values: Vec<AttributeValue>) -> Self {
+        let clause = self.clause(kind, attribute, values);
+        self.updates.push(clause);
+        self
+    }
+
+    fn condition(mut self, kind: Condition, attribute: &str, values: Vec<AttributeValue>) -> Self {
+        let clause = self.clause(kind, attribute, values);
+        self.conditions.push(clause);
+        self
+    }
+
+    fn clause<T>(&mut self, kind: T, attribute: &str, values: Vec<AttributeValue>) -> Clause<T> {
+        let name = self.name_placeholder(attribute);
+        let values = values
+            .into_iter()
+            .map(|value| self.value_placeholder(attribute, value))
+            .collect();
+
+        Clause {
+            kind,
+            attribute: attribute.to_owned(),
+            name,
+            values,
+        }
+    }
+
+    fn name_placeholder(&mut self, attribute: &str) -> String {
+        if let Some((placeholder, _)) = self.names.iter().find(|(_, name)| *name == attribute) {
+            return placeholder.to_owned();
+        }
+
+        let placeholder = unique_placeholder(&format!("#{}", escape(attribute)), |p| {
+            self.names.contains_key(p)
+        });
+        self.names
+            .insert(placeholder.to_owned(), attribute.to_owned());
+
+        placeholder
+    }
+
+    fn value_placeholder(&mut self, attribute: &str, value: AttributeValue) -> String {
+        let placeholder = unique_placeholder(&format!(":{}", escape(attribute)), |p| {
+            self.values.contains_key(p)
+        });
+        self.values.insert(placeholder.to_owned(), value);
+
+        placeholder
+    }
+}
+
+/// Placeholders may only contain alphanumeric characters and underscores.
+fn escape(attribute: &str) -> String {
+    attribute
+        .chars()
+        .map(|c| match c.is_ascii_alphanumeric() {
+            true => c,
+            false => '_',
+        })
+        .collect()
+}
+
+fn unique_placeholder(base: &str, taken: impl Fn(&str) -> bool) -> String {
+    if !taken(base) {
+        return base.to_owned();
+    }
+
+    (1..)
+        .map(|i| format!("{}_{}", base, i))
+        .find(|placeholder| !taken(placeholder))
+        .expect("Exhausted placeholder suffixes")
+}
+
+#[cfg(test)]
+mod unit_tests {
+    use super::*;
+
+    fn s(value: &str) -> AttributeValue {
+        AttributeValue::S(value.to_string())
+    }
+
+    #[test]
+    fn should_render_set_remove_and_add_sections() {
+        let expression = UpdateExpression::new()
+            .set("Username", s("tester"))
+            .remove("Summary")
+            .set("Email", s("tester@example.com"))
+            .add("Version", AttributeValue::N("1".to_string()))
+            .remove("PhoneNumber");
+
+        assert_eq!(
+            expression.update_expression(),
+            "SET #Username = :Username, #Email = :Email REMOVE #Summary, #PhoneNumber ADD #Version :Version"
+        );
+        assert_eq!(expression.condition_expression(), None);
+        assert_eq!(expression.names().map(|names| names.len()), Some(5));
+        assert_eq!(
+            expression
+                .values()
+                .and_then(|values| values.get(":Email").cloned()),
+            Some(s("tester@example.com"))
+        );
+    }
+
+    #[test]
+    fn should_render_if_not_exists_and_list_append() {
+        let expression = UpdateExpression::new()
+            .set_if_not_exists("CreatedDate", s("2023-06-01"))
+            .list_append("Tags", AttributeValue::L(vec![s("admin")]));
+
+        assert_eq!(
+            expression.update_expression(),
+            "SET #CreatedDate = if_not_exists(#CreatedDate, :CreatedDate), #Tags = list_append(if_not_exists(#Tags, :Tags_1), :Tags)"
+        );
+        assert_eq!(
+            expression
+                .values()
+                .and_then(|values| values.get(":Tags_1").cloned()),
+            Some(AttributeValue::L(Vec::new()))
+        );
+    }
+
+    #[test]
+    fn should_render_conditions_sharing_placeholders() {
+        let expression = UpdateExpression::new()
+            .add("Version", AttributeValue::N("1".to_string()))
+            .condition_attribute_exists("PK")
+            .condition_equals("Version", AttributeValue::N("3".to_string()));
+
+        assert_eq!(expression.update_expression(), "ADD #Version :Version");
+        assert_eq!(
+            expression.condition_expression().as_deref(),
+            Some("attribute_exists(#PK) AND #Version = :Version_1")
+        );
+        assert_eq!(
+            expression.names(),
+            Some(HashMap::from([
+                ("#Version".to_string(), "Version".to_string()),
+                ("#PK".to_string(), "PK".to_string()),
+            ]))
+        );
+    }
+
+    #[test]
+    fn should_render_and_match_greater_than() {
+        let expression = UpdateExpression::new()
+            .remove("ExpiresAt")
+            .condition_greater_than("ExpiresAt", AttributeValue::N("100".to_string()));
+
+        assert_eq!(
+            expression.condition_expression().as_deref(),
+            Some("#ExpiresAt > :ExpiresAt")
+        );
+
+        let item = |expires_at: &str| {
+            HashMap::from([(
+                "ExpiresAt".to_string(),
+                AttributeValue::N(expires_at.to_string()),
+            )])
+        };
+        assert!(expression.matches(Some(&item("101"))));
+        assert!(!expression.matches(Some(&item("99"))));
+        assert!(!expression.matches(None));
+    }
+
+    #[test]
+    fn should_render_and_match_missing_or_equals() {
+        let expression = UpdateExpression::new()
+            .set("UserId", s("1"))
+            .condition_missing_or_equals("UserId", s("1"));
+
+        assert_eq!(
+            expression.condition_expression().as_deref(),
+            Some("(attribute_not_exists(#UserId) OR #UserId = :UserId_1)")
+        );
+
+        let item = |user_id: &str| HashMap::from([("UserId".to_string(), s(user_id))]);
+        assert!(expression.matches(Some(&item("1"))));
+        assert!(!expression.matches(Some(&item("2"))));
+        assert!(expression.matches(Some(&HashMap::new())));
+        assert!(expression.matches(None));
+    }
+
+    #[test]
+    fn should_escape_reserved_and_invalid_names() {
+        let expression = UpdateExpression::new()
+            .set("Status", s("active"))
+            .set("first-name", s("Test"))
+            .set("first_name", s("Other"))
+            .remove("Name");
+
+        assert_eq!(
+            expression.update_expression(),
+            "SET #Status = :Status, #first_name = :first_name, #first_name_1 = :first_name_1 REMOVE #Name"
+        );
+        assert_eq!(
+            expression
+                .names()
+                .and_then(|names| names.get("#first_name").cloned()),
+            Some("first-name".to_string())
+        );
+    }
+
+    #[test]
+    fn should_omit_empty_names_and_values() {
+        let expression = UpdateExpression::new();
+
+        assert!(expression.is_empty());
+        assert_eq!(expression.update_expression(), "");
+        assert_eq!(expression.names(), None);
+        assert_eq!(expression.values(), None);
+    }
+
+    #[test]
+    fn should_apply_and_match_locally() {
+        let mut item = HashMap::from([
+            ("PK".to_string(), s("USER#1")),
+            ("Summary".to_string(), s("old")),
+            ("Version".to_string(), AttributeValue::N("1".to_string())),
+        ]);
+
+        let expression = UpdateExpression::new()
+            .set("Username", s("tester"))
+            .set_if_not_exists("Username", s("ignored"))
+            .remove("Summary")
+            .add("Version", AttributeValue::N("1".to_string()))
+            .list_append("Tags", AttributeValue::L(vec![s("admin")]))
+            .condition_attribute_exists("PK")
+            .condition_equals("Version", AttributeValue::N("1".to_string()));
+
+        assert!(expression.matches(Some(&item)));
+        assert!(!expression.matches(None));
+
+        expression.apply(&mut item);
+
+        assert_eq!(item.get("Username"), Some(&s("tester")));
+        assert_eq!(item.get("Summary"), None);
+        assert_eq!(
+            item.get("Version"),
+            Some(&AttributeValue::N("2".to_string()))
+        );
+        assert_eq!(item.get("Tags"), Some(&AttributeValue::L(vec![s("admin")])));
+        assert!(!expression.matches(Some(&item)));
+    }
+}
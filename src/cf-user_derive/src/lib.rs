@@ -0,0 +1,334 @@
+//! `#[derive(DynamoItem)]`, which maps a struct to and from a DynamoDB item.
+//!
+//! The generated code implements `cf_user_core::item::DynamoItem`, along with
+//! `From<&T> for HashMap<String, AttributeValue>` and
+//! `TryFrom<HashMap<String, AttributeValue>> for T`. Each field is converted through
+//! `cf_user_core::item::AttributeField`.
+//!
+//! Container attributes:
+//!
+//! - `#[dynamo(rename_all = "PascalCase")]` names attributes after their fields.
+//! - `#[dynamo(key(name = "PK", field = "user_id", prefix = "USER#"))]` writes a key
+//!   attribute made of the prefix and a field. `names = ["PK", "SK"]` writes several
+//!   keys with the same value. `value = "OUTBOX"` writes a constant instead of a
+//!   field. Keys are ignored when reading, since the field is read from its own
+//!   attribute.
+//!
+//! Field attributes:
+//!
+//! - `#[dynamo(rename = "GSI1PK")]` names the attribute explicitly.
+//! - `#[dynamo(skip)]` leaves the field out of the item. It reads as its default.
+//! - `#[dynamo(default)]` reads a missing attribute as the default rather than
+//!   failing. `Option` fields are always optional, and `None` is never written.
+//! - `#[dynamo(flatten)]` holds a whole `HashMap<String, AttributeValue>`, which is
+//!   merged into the item and receives every attribute no other field reads.
+
+use proc_macro::TokenStream;
+use proc_macro2::Span;
+use quote::quote;
+use syn::punctuated::Punctuated;
+use syn::{parse_macro_input, Data, DeriveInput, Fields, Ident, LitStr, Token};
+
+#[proc_macro_derive(DynamoItem, attributes(dynamo))]
+pub fn derive_dynamo_item(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    expand(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+#[derive(Default)]
+struct ContainerOptions {
+    rename_all: Option<LitStr>,
+    keys: Vec<KeyOptions>,
+}
+
+struct KeyOptions {
+    names: Vec<LitStr>,
+    value: KeyValue,
+}
+
+enum KeyValue {
+    Field { field: Ident, prefix: LitStr },
+    Constant(LitStr),
+}
+
+#[derive(Default)]
+struct FieldOptions {
+    rename: Option<LitStr>,
+    skip: bool,
+    default: bool,
+    flatten: bool,
+}
+
+fn expand(input: DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+    let name = &input.ident;
+    let container = container_options(&input)?;
+
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => {
+                return Err(syn::Error::new_spanned(
+                    name,
+                    "DynamoItem requires a struct with named fields",
+                ))
+            }
+        },
+        _ => {
+            return Err(syn::Error::new_spanned(
+                name,
+                "DynamoItem can only be derived for structs",
+            ))
+        }
+    };
+
+    let mut attribute_names: Vec<LitStr> = Vec::new();
+    let mut writes: Vec<proc_macro2::TokenStream> = Vec::new();
+    let mut reads: Vec<proc_macro2::TokenStream> = Vec::new();
+    let mut flatten: Option<&Ident> = None;
+    let mut skipped: Vec<&Ident> = Vec::new();
+
+    for key in &container.keys {
+        let value = match &key.value {
+            KeyValue::Field { field, prefix } => {
+                if !fields.iter().any(|f| f.ident.as_ref() == Some(field)) {
+                    return Err(syn::Error::new_spanned(field, "Unknown key field"));
+                }
+                quote! { format!("{}{}", #prefix, self.#field) }
+            }
+            KeyValue::Constant(value) => quote! { #value.to_owned() },
+        };
+
+        for key_name in &key.names {
+            writes.push(quote! {
+                item.insert(
+                    #key_name.to_owned(),
+                    ::cf_user_core::item::AttributeValue::S(#value),
+                );
+            });
+            reads.push(quote! {
+                item.remove(#key_name);
+            });
+        }
+    }
+
+    for field in fields {
+        let ident = field.ident.as_ref().expect("Named fields have identifiers");
+        let ty = &field.ty;
+        let options = field_options(field)?;
+
+        if options.skip {
+            skipped.push(ident);
+            continue;
+        }
+        if options.flatten {
+            if flatten.replace(ident).is_some() {
+                return Err(syn::Error::new_spanned(
+                    ident,
+                    "Only one field can be flattened",
+                ));
+            }
+            continue;
+        }
+
+        let attribute = match (options.rename, &container.rename_all) {
+            (Some(rename), _) => rename,
+            (None, Some(rename_all)) => {
+                LitStr::new(&rename_field(&ident.to_string(), rename_all)?, ident.span())
+            }
+            (None, None) => LitStr::new(&ident.to_string(), ident.span()),
+        };
+        let missing = LitStr::new(
+            &format!("Missing or invalid {}", attribute.value()),
+            Span::call_site(),
+        );
+
+        writes.push(quote! {
+            if let Some(value) = ::cf_user_core::item::AttributeField::to_attribute(&self.#ident) {
+                item.insert(#attribute.to_owned(), value);
+            }
+        });
+
+        let read = quote! {
+            <#ty as ::cf_user_core::item::AttributeField>::from_attribute(item.remove(#attribute))
+        };
+        reads.push(if options.default {
+            quote! {
+                let #ident: #ty = #read.unwrap_or_default();
+            }
+        } else {
+            quote! {
+                let #ident: #ty = #read
+                    .ok_or(::cf_user_core::error::Error::InternalError(#missing))?;
+            }
+        });
+
+        attribute_names.push(attribute);
+    }
+
+    let read_fields: Vec<&Ident> = fields
+        .iter()
+        .filter_map(|field| field.ident.as_ref())
+        .filter(|ident| !skipped.contains(ident) && flatten != Some(*ident))
+        .collect();
+
+    let (flatten_write, flatten_read) = match flatten {
+        Some(ident) => (
+            quote! { item.extend(self.#ident.clone()); },
+            quote! { #ident: item, },
+        ),
+        None => (quote! {}, quote! {}),
+    };
+
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    Ok(quote! {
+        impl #impl_generics ::cf_user_core::item::DynamoItem for #name #ty_generics #where_clause {
+            const ATTRIBUTES: &'static [&'static str] = &[#(#attribute_names),*];
+
+            fn to_item(&self) -> ::cf_user_core::item::Item {
+                let mut item = ::cf_user_core::item::Item::new();
+                #flatten_write
+                #(#writes)*
+                item
+            }
+
+            fn from_item(
+                mut item: ::cf_user_core::item::Item,
+            ) -> ::std::result::Result<Self, ::cf_user_core::error::Error> {
+                #(#reads)*
+
+                ::std::result::Result::Ok(Self {
+                    #(#read_fields,)*
+                    #(#skipped: ::std::default::Default::default(),)*
+                    #flatten_read
+                })
+            }
+        }
+
+        impl #impl_generics ::std::convert::From<&#name #ty_generics> for ::cf_user_core::item::Item #where_clause {
+            fn from(value: &#name #ty_generics) -> Self {
+                ::cf_user_core::item::DynamoItem::to_item(value)
+            }
+        }
+
+        impl #impl_generics ::std::convert::TryFrom<::cf_user_core::item::Item> for #name #ty_generics #where_clause {
+            type Error = ::cf_user_core::error::Error;
+
+            fn try_from(item: ::cf_user_core::item::Item) -> ::std::result::Result<Self, Self::Error> {
+                ::cf_user_core::item::DynamoItem::from_item(item)
+            }
+        }
+    })
+}
+
+fn container_options(input: &DeriveInput) -> syn::Result<ContainerOptions> {
+    let mut options = ContainerOptions::default();
+
+    for attr in input
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("dynamo"))
+    {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("rename_all") {
+                options.rename_all = Some(meta.value()?.parse()?);
+                return Ok(());
+            }
+
+            if meta.path.is_ident("key") {
+                let (mut names, mut field, mut prefix, mut constant) =
+                    (Vec::new(), None, None, None);
+                meta.parse_nested_meta(|key| {
+                    if key.path.is_ident("names") {
+                        let value = key.value()?;
+                        let content;
+                        syn::bracketed!(content in value);
+                        names.extend(Punctuated::<LitStr, Token![,]>::parse_terminated(&content)?);
+                        return Ok(());
+                    }
+
+                    let value: LitStr = key.value()?.parse()?;
+                    if key.path.is_ident("name") {
+                        names.push(value);
+                    } else if key.path.is_ident("field") {
+                        field = Some(value.parse::<Ident>()?);
+                    } else if key.path.is_ident("prefix") {
+                        prefix = Some(value);
+                    } else if key.path.is_ident("value") {
+                        constant = Some(value);
+                    } else {
+                        return Err(key.error("Unknown key option"));
+                    }
+                    Ok(())
+                })?;
+
+                if names.is_empty() {
+                    return Err(meta.error("Key is missing a name"));
+                }
+                let value = match (field, constant) {
+                    (Some(field), None) => KeyValue::Field {
+                        field,
+                        prefix: prefix.unwrap_or_else(|| LitStr::new("", Span::call_site())),
+                    },
+                    (None, Some(constant)) if prefix.is_none() => KeyValue::Constant(constant),
+                    _ => return Err(meta.error("Key needs either a field or a value")),
+                };
+                options.keys.push(KeyOptions { names, value });
+                return Ok(());
+            }
+
+            Err(meta.error("Unknown dynamo option"))
+        })?;
+    }
+
+    Ok(options)
+}
+
+fn field_options(field: &syn::Field) -> syn::Result<FieldOptions> {
+    let mut options = FieldOptions::default();
+
+    for attr in field
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("dynamo"))
+    {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("rename") {
+                options.rename = Some(meta.value()?.parse()?);
+            } else if meta.path.is_ident("skip") {
+                options.skip = true;
+            } else if meta.path.is_ident("default") {
+                options.default = true;
+            } else if meta.path.is_ident("flatten") {
+                options.flatten = true;
+            } else {
+                return Err(meta.error("Unknown dynamo option"));
+            }
+            Ok(())
+        })?;
+    }
+
+    Ok(options)
+}
+
+fn rename_field(field: &str, rename_all: &LitStr) -> syn::Result<String> {
+    match rename_all.value().as_str() {
+        "PascalCase" => Ok(field
+            .split('_')
+            .map(|word| {
+                let mut chars = word.chars();
+                match chars.next() {
+                    Some(first) => first.to_uppercase().chain(chars).collect(),
+                    None => String::new(),
+                }
+            })
+            .collect()),
+        _ => Err(syn::Error::new_spanned(
+            rename_all,
+            "Only rename_all = \"PascalCase\" is supported",
+        )),
+    }
+}
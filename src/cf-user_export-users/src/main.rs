@@ -0,0 +1,190 @@
+use cf_user_core::{
+    args::export_users_args::ExportUsersArgs,
+    config::{self, Config},
+    dynamo, export,
+    file_format::FileFormat,
+    fn_handler,
+    repository::{DynamoUserRepository, UserRepository},
+};
+use lambda_http::lambda_runtime::{run, LambdaEvent};
+use lambda_http::service_fn;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::sync::Arc;
+use tokio::fs::File;
+use tokio::io::BufWriter;
+use tracing::info;
+
+/// Export request, sent by an async invocation rather than through API Gateway.
+#[derive(Clone, Debug, Default, Deserialize)]
+struct ExportUsersEvent {
+    /// `ndjson` (default) or `csv`.
+    #[serde(rename = "Format")]
+    format: Option<String>,
+    #[serde(rename = "Columns")]
+    columns: Option<Vec<String>>,
+    #[serde(rename = "CreatedFrom")]
+    created_from: Option<String>,
+    #[serde(rename = "CreatedTo")]
+    created_to: Option<String>,
+    #[serde(rename = "IncludeDeleted", default)]
+    include_deleted: bool,
+    #[serde(rename = "Segments")]
+    segments: Option<i32>,
+    /// Name of the file written to the export directory. Defaults to
+    /// `users-<request id>.<extension>`.
+    #[serde(rename = "FileName")]
+    file_name: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+struct ExportUsersResult {
+    #[serde(rename = "File")]
+    file: String,
+    #[serde(rename = "Exported")]
+    exported: usize,
+}
+
+async fn function_handler(
+    event: ExportUsersEvent,
+    request_id: &str,
+    repository: Arc<dyn UserRepository>,
+    directory: &Path,
+) -> Result<ExportUsersResult, Box<dyn std::error::Error>> {
+    let format = match &event.format {
+        Some(format) => format.parse::<FileFormat>()?,
+        None => FileFormat::default(),
+    };
+
+    let file_name = event
+        .file_name
+        .unwrap_or_else(|| format!("users-{}.{}", request_id, format.extension()));
+    if Path::new(&file_name).file_name() != Some(file_name.as_ref()) {
+        return Err("FileName must not contain a directory".into());
+    }
+
+    let args = ExportUsersArgs {
+        format,
+        columns: event.columns,
+        created_from: event.created_from,
+        created_to: event.created_to,
+        include_deleted: event.include_deleted,
+        segments: event
+            .segments
+            .unwrap_or(ExportUsersArgs::default().segments),
+    };
+
+    let path = directory.join(file_name);
+    let mut writer = BufWriter::new(File::create(&path).await?);
+    let exported = export::export_users(repository, args, &mut writer).await?;
+
+    info!("Exported {} users to {}", exported, path.display());
+
+    Ok(ExportUsersResult {
+        file: path.display().to_string(),
+        exported,
+    })
+}
+
+#[tokio::main]
+async fn main() -> Result<(), lambda_http::Error> {
+    tracing_subscriber::fmt()
+        .with_max_level(tracing::Level::INFO)
+        .with_target(false)
+        .with_ansi(false)
+        .without_time()
+        .init();
+
+    // Scan pages are only continued within an invocation
+    config::use_process_pagination_token_secret();
+    let config = Config::from_env();
+
+    run(service_fn(|event: LambdaEvent<ExportUsersEvent>| {
+        let directory = config.export_directory.clone();
+
+        async move {
+            let client = dynamo::get_client().await?;
+            let repository = Arc::new(DynamoUserRepository::new(
+                client,
+                fn_handler::table_name(&event.context.env_config.function_name).as_str(),
+            ));
+
+            function_handler(
+                event.payload,
+                &event.context.request_id,
+                repository,
+                Path::new(&directory),
+            )
+            .await
+            .map_err(|err| lambda_http::Error::from(err.to_string()))
+        }
+    }))
+    .await
+}
+
+#[cfg(test)]
+mod unit_tests {
+    use super::*;
+    use cf_user_core::{
+        args::create_user_args::CreateUserArgs, models::change_context::ChangeContext,
+        repository::InMemoryUserRepository,
+    };
+
+    #[tokio::test]
+    async fn export_users_should_succeed() {
+        let repository = Arc::new(InMemoryUserRepository::new());
+        repository
+            .create_user(
+                CreateUserArgs {
+                    username: "tester".to_string(),
+                    first_name: None,
+                    last_name: None,
+                    email: "tester@example.com".to_string(),
+                    profile_photo: None,
+                    summary: None,
+                    phone_number: None,
+                },
+                &ChangeContext::new("admin"),
+            )
+            .await
+            .expect("Failed to create user");
+
+        let directory = std::env::temp_dir();
+        let event = ExportUsersEvent {
+            format: Some("csv".to_string()),
+            columns: Some(vec!["Username".to_string(), "Email".to_string()]),
+            ..Default::default()
+        };
+
+        let result = function_handler(event, "request-1", repository, &directory)
+            .await
+            .expect("Export failed");
+
+        let path = directory.join("users-request-1.csv");
+        assert_eq!(
+            result,
+            ExportUsersResult {
+                file: path.display().to_string(),
+                exported: 1,
+            }
+        );
+        assert_eq!(
+            std::fs::read_to_string(&path).expect("Export file missing"),
+            "Username,Email\ntester,tester@example.com\n"
+        );
+        std::fs::remove_file(path).ok();
+    }
+
+    #[tokio::test]
+    async fn export_users_outside_directory_should_fail() {
+        let repository = Arc::new(InMemoryUserRepository::new());
+        let event = ExportUsersEvent {
+            file_name: Some("../users.ndjson".to_string()),
+            ..Default::default()
+        };
+
+        let result = function_handler(event, "request-1", repository, &std::env::temp_dir()).await;
+
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,22 @@
+use cf_user_core::{
+    fn_handler, handlers::get_user_history::function_handler, models::permissions::Permission,
+    secrets,
+};
+use lambda_http::{run, service_fn, Request};
+
+#[tokio::main]
+async fn main() -> Result<(), lambda_http::Error> {
+    tracing_subscriber::fmt()
+        .with_max_level(tracing::Level::INFO)
+        .with_target(false)
+        .with_ansi(false)
+        .without_time()
+        .init();
+
+    secrets::load_pagination_token_secret().await?;
+
+    run(service_fn(|event: Request| async {
+        fn_handler::handle_request(event, function_handler, None, Permission::UserHistory).await
+    }))
+    .await
+}
@@ -0,0 +1,97 @@
+use cf_user_core::{
+    dynamo,
+    event_sink::{EventSink, LogEventSink},
+    fn_handler,
+    outbox::{self, RelayReport},
+    repository::{DynamoUserRepository, UserRepository},
+};
+use lambda_http::lambda_runtime::{run, LambdaEvent};
+use lambda_http::service_fn;
+use serde_json::Value;
+use tracing::info;
+
+/// Relays the outbox on a schedule, so entries an earlier relay left behind are
+/// retried even when no new change reaches the stream processor. The payload is
+/// ignored, so the function can also be invoked by hand to drain the outbox.
+async fn function_handler(
+    repository: &dyn UserRepository,
+    sink: &dyn EventSink,
+) -> Result<RelayReport, Box<dyn std::error::Error>> {
+    let report = outbox::relay_outbox(repository, sink).await?;
+    info!("Relayed outbox: {:?}", report);
+
+    Ok(report)
+}
+
+#[tokio::main]
+async fn main() -> Result<(), lambda_http::Error> {
+    tracing_subscriber::fmt()
+        .with_max_level(tracing::Level::INFO)
+        .with_target(false)
+        .with_ansi(false)
+        .without_time()
+        .init();
+
+    let sink = LogEventSink;
+
+    run(service_fn(|event: LambdaEvent<Value>| {
+        let sink = &sink;
+
+        async move {
+            let client = dynamo::get_client().await?;
+            let repository = DynamoUserRepository::new(
+                client,
+                fn_handler::table_name(&event.context.env_config.function_name).as_str(),
+            );
+
+            function_handler(&repository, sink)
+                .await
+                .map_err(|err| lambda_http::Error::from(err.to_string()))
+        }
+    }))
+    .await
+}
+
+#[cfg(test)]
+mod unit_tests {
+    use super::*;
+    use cf_user_core::{
+        args::create_user_args::CreateUserArgs, event_sink::InMemoryEventSink,
+        models::change_context::ChangeContext, repository::InMemoryUserRepository,
+    };
+
+    #[tokio::test]
+    async fn relay_should_retry_entries_left_behind() {
+        let repository = InMemoryUserRepository::new();
+        let sink = InMemoryEventSink::new();
+        let user_id = repository
+            .create_user(
+                CreateUserArgs {
+                    username: "tester".to_string(),
+                    first_name: None,
+                    last_name: None,
+                    email: "tester@example.com".to_string(),
+                    profile_photo: None,
+                    summary: None,
+                    phone_number: None,
+                },
+                &ChangeContext::new("admin"),
+            )
+            .await
+            .expect("Failed to create user");
+        sink.fail_subject(&user_id);
+
+        let report = function_handler(&repository, &sink)
+            .await
+            .expect("Relay failed");
+        assert_eq!(report.failed, 1);
+
+        sink.recover_subject(&user_id);
+        let report = function_handler(&repository, &sink)
+            .await
+            .expect("Relay failed");
+
+        assert_eq!(report.published, 1);
+        assert_eq!(sink.events()[0].subject, user_id);
+    }
+}
@@ -0,0 +1,19 @@
+use cf_user_core::{
+    fn_handler, handlers::restore_user::function_handler, models::permissions::Permission,
+};
+use lambda_http::{run, service_fn, Request};
+
+#[tokio::main]
+async fn main() -> Result<(), lambda_http::Error> {
+    tracing_subscriber::fmt()
+        .with_max_level(tracing::Level::INFO)
+        .with_target(false)
+        .with_ansi(false)
+        .without_time()
+        .init();
+
+    run(service_fn(|event: Request| async {
+        fn_handler::handle_request(event, function_handler, None, Permission::UserRestore).await
+    }))
+    .await
+}
@@ -0,0 +1,364 @@
+use aws_lambda_events::event::dynamodb::{Event, EventRecord};
+use aws_lambda_events::event::streams::{DynamoDbBatchItemFailure, DynamoDbEventResponse};
+use aws_sdk_dynamodb::types::AttributeValue;
+use cf_user_core::{
+    config::Config,
+    dynamo,
+    event_sink::{EventSink, LogEventSink},
+    ext::AttributeValuesExt,
+    fn_handler,
+    models::{
+        user::User,
+        user_event::{CloudEvent, UserEvent},
+    },
+    outbox,
+    repository::{DynamoUserRepository, UserRepository},
+};
+use lambda_http::lambda_runtime::{run, LambdaEvent};
+use lambda_http::service_fn;
+use std::collections::HashMap;
+use tracing::{error, info};
+
+type Item = HashMap<String, AttributeValue>;
+
+/// Reads a stream image, which is empty rather than absent when the stream record
+/// has none.
+fn image(image: &serde_dynamo::Item) -> Option<Item> {
+    let item: Item = image.clone().into();
+
+    (!item.is_empty()).then_some(item)
+}
+
+fn is_outbox_insert(record: &EventRecord) -> bool {
+    image(&record.change.old_image).is_none()
+        && image(&record.change.new_image).is_some_and(|item| item.get_s("PK") == "OUTBOX")
+}
+
+/// The event for a user removed from the table, which only the TTL does once a
+/// deleted user's retention ends. Every other user change is published from the
+/// outbox entry written with it, which carries the request context a stream record
+/// lacks.
+fn purge_event(record: &EventRecord) -> Result<Option<UserEvent>, Box<dyn std::error::Error>> {
+    if image(&record.change.new_image).is_some() {
+        return Ok(None);
+    }
+    let Some(old) = image(&record.change.old_image).filter(dynamo::is_user_item) else {
+        return Ok(None);
+    };
+
+    Ok(UserEvent::from_change(Some(User::try_from(old)?), None))
+}
+
+/// Handles one record. The outbox is relayed on the first outbox insert of a batch,
+/// which publishes the entries of every later insert in the batch along with it.
+async fn process_record(
+    record: &EventRecord,
+    repository: &dyn UserRepository,
+    sink: &dyn EventSink,
+    source: &str,
+    relayed: &mut bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if is_outbox_insert(record) {
+        if !*relayed {
+            let report = outbox::relay_outbox(repository, sink).await?;
+            info!("Relayed outbox: {:?}", report);
+            *relayed = true;
+        }
+        return Ok(());
+    }
+
+    let Some(event) = purge_event(record)? else {
+        return Ok(());
+    };
+
+    let envelope = CloudEvent::new(
+        &record.event_id,
+        source,
+        record.change.approximate_creation_date_time,
+        &event,
+    )?;
+    sink.publish(&envelope).await?;
+
+    info!(
+        "Published {} for user {}",
+        envelope.event_type, envelope.subject
+    );
+
+    Ok(())
+}
+
+/// Publishes the events of a batch of stream records, in stream order. Processing
+/// stops at the first record that fails, which is reported so Lambda retries the
+/// batch from it, keeping events about a user in order.
+async fn function_handler(
+    event: Event,
+    repository: &dyn UserRepository,
+    sink: &dyn EventSink,
+    source: &str,
+) -> DynamoDbEventResponse {
+    let mut batch_item_failures = Vec::new();
+    let mut relayed = false;
+
+    for record in &event.records {
+        if let Err(err) = process_record(record, repository, sink, source, &mut relayed).await {
+            error!("Failed to process record {}: {}", record.event_id, err);
+            batch_item_failures.push(DynamoDbBatchItemFailure {
+                item_identifier: record.change.sequence_number.clone(),
+            });
+            break;
+        }
+    }
+
+    DynamoDbEventResponse {
+        batch_item_failures,
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<(), lambda_http::Error> {
+    tracing_subscriber::fmt()
+        .with_max_level(tracing::Level::INFO)
+        .with_target(false)
+        .with_ansi(false)
+        .without_time()
+        .init();
+
+    let config = Config::from_env();
+    let sink = LogEventSink;
+
+    run(service_fn(|event: LambdaEvent<Event>| {
+        let (sink, source) = (&sink, config.event_source.as_str());
+
+        async move {
+            let client = dynamo::get_client().await?;
+            let repository = DynamoUserRepository::new(
+                client,
+                fn_handler::table_name(&event.context.env_config.function_name).as_str(),
+            );
+
+            let response = function_handler(event.payload, &repository, sink, source).await;
+            Ok::<_, lambda_http::Error>(response)
+        }
+    }))
+    .await
+}
+
+#[cfg(test)]
+mod unit_tests {
+    use super::*;
+    use cf_user_core::{
+        args::create_user_args::CreateUserArgs, event_sink::InMemoryEventSink,
+        models::change_context::ChangeContext, repository::InMemoryUserRepository,
+    };
+    use serde_json::{json, Value};
+
+    fn user_image(user_id: &str, attributes: &[(&str, Value)]) -> Value {
+        let key = format!("USER#{}", user_id);
+        let mut image = json!({
+            "PK": { "S": key },
+            "SK": { "S": key },
+            "UserId": { "S": user_id },
+            "Username": { "S": "tester" },
+            "Email": { "S": "tester@example.com" },
+            "GSI1PK": { "S": "EMAIL#tester@example.com" },
+            "CreatedDate": { "S": "2023-07-01T12:00:00.000Z" },
+            "UpdatedDate": { "S": "2023-07-01T12:00:00.000Z" },
+            "Version": { "N": "2" },
+            "Status": { "S": "deleted" },
+            "DeletedAt": { "S": "2023-07-01T12:00:00.000Z" }
+        });
+        for (name, value) in attributes {
+            image[*name] = value.clone();
+        }
+        image
+    }
+
+    fn record(
+        sequence_number: &str,
+        event_name: &str,
+        old_image: Option<Value>,
+        new_image: Option<Value>,
+    ) -> Value {
+        let image = new_image.clone().or(old_image.clone()).unwrap();
+        let mut change = json!({
+            "ApproximateCreationDateTime": 1688212800,
+            "Keys": { "PK": image["PK"], "SK": image["SK"] },
+            "SequenceNumber": sequence_number,
+            "SizeBytes": 256,
+            "StreamViewType": "NEW_AND_OLD_IMAGES"
+        });
+        if let Some(old_image) = old_image {
+            change["OldImage"] = old_image;
+        }
+        if let Some(new_image) = new_image {
+            change["NewImage"] = new_image;
+        }
+
+        json!({
+            "awsRegion": "us-east-1",
+            "dynamodb": change,
+            "eventID": format!("event-{}", sequence_number),
+            "eventName": event_name,
+            "eventSource": "aws:dynamodb",
+            "eventVersion": "1.1"
+        })
+    }
+
+    fn event(records: Vec<Value>) -> Event {
+        serde_json::from_value(json!({ "Records": records })).expect("Invalid event")
+    }
+
+    async fn seed_user(repository: &InMemoryUserRepository, username: &str) -> String {
+        repository
+            .create_user(
+                CreateUserArgs {
+                    username: username.to_string(),
+                    first_name: None,
+                    last_name: None,
+                    email: format!("{}@example.com", username),
+                    profile_photo: None,
+                    summary: None,
+                    phone_number: None,
+                },
+                &ChangeContext::new("auth0|tester").with_request_id("request-1"),
+            )
+            .await
+            .expect("Failed to seed user")
+    }
+
+    fn outbox_record(sequence_number: &str) -> Value {
+        let image = json!({
+            "PK": { "S": "OUTBOX" },
+            "SK": { "S": format!("OUTBOX#{}", sequence_number) }
+        });
+        record(sequence_number, "INSERT", None, Some(image))
+    }
+
+    #[tokio::test]
+    async fn stream_outbox_insert_should_succeed() {
+        let repository = InMemoryUserRepository::new();
+        let sink = InMemoryEventSink::new();
+        let first = seed_user(&repository, "first").await;
+        let second = seed_user(&repository, "second").await;
+        let event = event(vec![outbox_record("1"), outbox_record("2")]);
+
+        let response = function_handler(event, &repository, &sink, "cf-user").await;
+
+        assert!(response.batch_item_failures.is_empty());
+        let events = sink.events();
+        let subjects: Vec<&str> = events.iter().map(|event| event.subject.as_str()).collect();
+        assert_eq!(subjects, vec![first.as_str(), second.as_str()]);
+        assert_eq!(events[0].event_type, "com.cf.user.created");
+        assert_eq!(events[0].principalid.as_deref(), Some("auth0|tester"));
+        assert_eq!(events[0].correlationid.as_deref(), Some("request-1"));
+        assert!(repository
+            .list_outbox_entries(10, None)
+            .await
+            .expect("Failed to list outbox")
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn stream_user_changes_should_succeed() {
+        let repository = InMemoryUserRepository::new();
+        let sink = InMemoryEventSink::new();
+        let active = user_image(
+            "abc123",
+            &[
+                ("Status", json!({ "S": "active" })),
+                ("DeletedAt", json!({ "NULL": true })),
+            ],
+        );
+        let event = event(vec![
+            record("1", "INSERT", None, Some(active.clone())),
+            record("2", "MODIFY", Some(active), Some(user_image("abc123", &[]))),
+        ]);
+
+        let response = function_handler(event, &repository, &sink, "cf-user").await;
+
+        assert!(response.batch_item_failures.is_empty());
+        assert!(sink.events().is_empty());
+    }
+
+    #[tokio::test]
+    async fn stream_purge_should_succeed() {
+        let repository = InMemoryUserRepository::new();
+        let sink = InMemoryEventSink::new();
+        let history = json!({
+            "PK": { "S": "USER#abc123" },
+            "SK": { "S": "HISTORY#01H4" },
+            "UserId": { "S": "abc123" }
+        });
+        let event = event(vec![
+            record("1", "REMOVE", Some(history), None),
+            record("2", "REMOVE", Some(user_image("abc123", &[])), None),
+        ]);
+
+        let response = function_handler(event, &repository, &sink, "cf-user").await;
+
+        assert!(response.batch_item_failures.is_empty());
+        let events = sink.events();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].id, "event-2");
+        assert_eq!(events[0].event_type, "com.cf.user.deleted");
+        assert_eq!(
+            events[0].data,
+            json!({ "UserId": "abc123", "Purged": true })
+        );
+    }
+
+    #[tokio::test]
+    async fn stream_publish_should_fail() {
+        let repository = InMemoryUserRepository::new();
+        let sink = InMemoryEventSink::new();
+        sink.fail_subject("def456");
+        let event = event(vec![
+            record("1", "REMOVE", Some(user_image("abc123", &[])), None),
+            record("2", "REMOVE", Some(user_image("def456", &[])), None),
+            record("3", "REMOVE", Some(user_image("ghi789", &[])), None),
+        ]);
+
+        let response = function_handler(event, &repository, &sink, "cf-user").await;
+
+        assert_eq!(
+            response.batch_item_failures,
+            vec![DynamoDbBatchItemFailure {
+                item_identifier: Some("2".to_string()),
+            }]
+        );
+        assert_eq!(sink.events().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn stream_outbox_relay_should_keep_rejected_entries() {
+        let repository = InMemoryUserRepository::new();
+        let sink = InMemoryEventSink::new();
+        let user_id = seed_user(&repository, "tester").await;
+        sink.fail_subject(&user_id);
+        let event = event(vec![outbox_record("5")]);
+
+        let response = function_handler(event, &repository, &sink, "cf-user").await;
+
+        // The entry waits for a later relay rather than failing the whole batch
+        assert!(response.batch_item_failures.is_empty());
+        let pending = repository
+            .list_outbox_entries(10, None)
+            .await
+            .expect("Failed to list outbox");
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].attempts, 1);
+    }
+
+    #[tokio::test]
+    async fn stream_invalid_image_should_fail() {
+        let repository = InMemoryUserRepository::new();
+        let sink = InMemoryEventSink::new();
+        let invalid = user_image("abc123", &[("Email", json!({ "N": "1" }))]);
+        let event = event(vec![record("7", "REMOVE", Some(invalid), None)]);
+
+        let response = function_handler(event, &repository, &sink, "cf-user").await;
+
+        assert_eq!(response.batch_item_failures.len(), 1);
+        assert!(sink.events().is_empty());
+    }
+}